@@ -0,0 +1,262 @@
+use std::fs::{File, OpenOptions, remove_file};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::iter::once;
+use std::path::Path;
+use std::thread::{self, available_parallelism};
+use std::time::{Duration, Instant};
+
+use crate::byte_size::ByteSize;
+use crate::errors::{AppError, AppErrorResult};
+use crate::hashing::{ALSO_HASH_ALGORITHMS, HasherFactory, MAIN_HASH_ALGORITHM};
+use crate::or_else;
+use crate::utils::check_exit_key_pressed;
+
+const BENCH_FILENAME: &str = ".hashfolder-bench.tmp";
+const WRITE_BLOCK_SIZE: usize = 1024 * 1024;
+const BENCH_BUFFER_SIZES: [usize; 5] = [
+    8 * 1024,
+    64 * 1024,
+    256 * 1024,
+    1024 * 1024,
+    4 * 1024 * 1024,
+];
+const DEFAULT_THREADS_BUFFER_SIZE: usize = 1024 * 1024;
+
+/// Recommendations accept a slower setting when it's within this fraction of the fastest
+const RECOMMENDATION_TOLERANCE: f64 = 0.1;
+
+pub fn bench(starting_dir: &Path, size: u64) -> Result<(), AppError> {
+    let bench_path = starting_dir.join(BENCH_FILENAME);
+
+    let result = run_bench(&bench_path, size);
+
+    _ = remove_file(&bench_path);
+
+    return result;
+}
+
+fn run_bench(bench_path: &Path, size: u64) -> Result<(), AppError> {
+    println!(
+        "Writing {} test file {}",
+        ByteSize(size),
+        bench_path.to_string_lossy()
+    );
+
+    write_test_file(bench_path, size)?;
+
+    println!("Reads after the first may be served from the page cache");
+
+    let mut algorithm_results: Vec<(&str, f64)> = Vec::default();
+
+    for algorithm in once(MAIN_HASH_ALGORITHM).chain(ALSO_HASH_ALGORITHMS) {
+        let hasher = or_else!(HasherFactory::by_name(algorithm), none => continue);
+
+        println!();
+        println!("{algorithm}");
+        println!("{:>12} {:>12}", "Buffer", "MB/s");
+
+        let mut buffer_results: Vec<(usize, f64)> = Vec::default();
+
+        for buffer_size in BENCH_BUFFER_SIZES {
+            let elapsed = time_hash(bench_path, &hasher, size, buffer_size, 1)?;
+            let throughput = throughput(size, elapsed);
+
+            println!(
+                "{:>12} {:>12.1}",
+                format_buffer_size(buffer_size),
+                throughput
+            );
+
+            buffer_results.push((buffer_size, throughput));
+        }
+
+        algorithm_results.push((
+            algorithm,
+            buffer_results
+                .iter()
+                .map(|(_, throughput)| *throughput)
+                .fold(0.0, f64::max),
+        ));
+
+        let max_threads = available_parallelism().map(|n| n.get()).unwrap_or(1);
+
+        let mut thread_counts: Vec<usize> = vec![1, 2, 4, 8, max_threads];
+        thread_counts.retain(|threads| *threads <= max_threads);
+        thread_counts.dedup();
+
+        println!();
+        println!("{:>12} {:>12}", "Threads", "MB/s");
+
+        let mut thread_results: Vec<(usize, f64)> = Vec::default();
+
+        for threads in thread_counts {
+            let elapsed = time_hash(
+                bench_path,
+                &hasher,
+                size,
+                DEFAULT_THREADS_BUFFER_SIZE,
+                threads,
+            )?;
+            let throughput = throughput(size, elapsed);
+
+            println!("{threads:>12} {throughput:>12.1}");
+
+            thread_results.push((threads, throughput));
+        }
+
+        println!();
+
+        if let Some(buffer_size) = recommend(&buffer_results) {
+            println!(
+                "Recommended --buffer-size {}",
+                format_buffer_size(buffer_size)
+            );
+        }
+
+        if let Some(threads) = recommend(&thread_results) {
+            println!("Recommended --threads {threads}");
+        }
+    }
+
+    println!();
+
+    if let Some(algorithm) = recommend(&algorithm_results) {
+        println!("Recommended algorithm {algorithm}");
+
+        // Hash data from different machines is compared by its main hash, so there's no option
+        // to choose it per machine
+        if algorithm != MAIN_HASH_ALGORITHM {
+            println!(
+                "The main hash is always {MAIN_HASH_ALGORITHM} so hash data stays comparable \
+                 between machines, {algorithm} can be added with --also-hash {algorithm}"
+            );
+        }
+    }
+
+    return Ok(());
+}
+
+fn write_test_file(bench_path: &Path, size: u64) -> Result<(), AppError> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(bench_path)
+        .app_err()?;
+
+    let mut block = vec![0u8; WRITE_BLOCK_SIZE];
+    let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+
+    let mut remaining = size;
+    while remaining > 0 {
+        check_exit_key_pressed()?;
+
+        for byte in block.iter_mut() {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            *byte = state as u8;
+        }
+
+        let length = remaining.min(WRITE_BLOCK_SIZE as u64) as usize;
+        file.write_all(&block[..length]).app_err()?;
+        remaining -= length as u64;
+    }
+
+    file.sync_all().app_err()?;
+
+    return Ok(());
+}
+
+fn time_hash(
+    bench_path: &Path,
+    hasher: &HasherFactory,
+    size: u64,
+    buffer_size: usize,
+    threads: usize,
+) -> Result<Duration, AppError> {
+    let part_size = size.div_ceil(threads as u64);
+
+    let start = Instant::now();
+
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads as u64)
+            .map(|part| {
+                scope.spawn(move || {
+                    let offset = part * part_size;
+                    hash_range(
+                        bench_path,
+                        hasher,
+                        offset,
+                        part_size.min(size - offset.min(size)),
+                        buffer_size,
+                    )
+                })
+            })
+            .collect();
+
+        for worker in workers {
+            match worker.join() {
+                Ok(result) => result?,
+                Err(_) => return Err(AppError::new("Benchmark thread panicked".into())),
+            }
+        }
+
+        return Ok(());
+    })?;
+
+    return Ok(start.elapsed());
+}
+
+fn hash_range(
+    bench_path: &Path,
+    hasher: &HasherFactory,
+    offset: u64,
+    length: u64,
+    buffer_size: usize,
+) -> Result<(), AppError> {
+    let mut file = File::open(bench_path).app_err()?;
+    file.seek(SeekFrom::Start(offset)).app_err()?;
+
+    let mut reader = file.take(length);
+    let mut hasher = hasher.create();
+    let mut buffer = vec![0u8; buffer_size];
+
+    loop {
+        check_exit_key_pressed()?;
+
+        let n = reader.read(&mut buffer).app_err()?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+    }
+
+    hasher.finalize();
+
+    return Ok(());
+}
+
+fn format_buffer_size(buffer_size: usize) -> String {
+    if buffer_size >= 1024 * 1024 {
+        return format!("{}MiB", buffer_size / (1024 * 1024));
+    }
+
+    return format!("{}KiB", buffer_size / 1024);
+}
+
+fn throughput(size: u64, elapsed: Duration) -> f64 {
+    return size as f64 / 1_000_000.0 / elapsed.as_secs_f64().max(f64::EPSILON);
+}
+
+fn recommend<T: Copy>(results: &[(T, f64)]) -> Option<T> {
+    let best = results
+        .iter()
+        .map(|(_, throughput)| *throughput)
+        .fold(0.0, f64::max);
+
+    return results
+        .iter()
+        .find(|(_, throughput)| *throughput >= best * (1.0 - RECOMMENDATION_TOLERANCE))
+        .map(|(setting, _)| *setting);
+}
@@ -1,37 +1,189 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+
 use clap::builder::TypedValueParser;
 use clap::error::{ContextKind, ContextValue};
 use clap::{Arg, Command};
 
-#[derive(Clone, Copy, Debug)]
-pub enum ByteSize {
-    Byte(u64),
-    KByte(u64),
-    KiByte(u64),
-    MByte(u64),
-    MiByte(u64),
-    GByte(u64),
-    GiByte(u64),
-    TByte(u64),
-    TiByte(u64),
-}
+use crate::or_else;
+
+static PLAIN_NUMBERS: AtomicBool = AtomicBool::new(false);
+
+const DECIMAL_UNITS: [&str; 6] = ["B", "KB", "MB", "GB", "TB", "PB"];
+const BINARY_UNITS: [&str; 6] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
+
+/// Suffixes accepted when parsing and their multipliers, including everything `SizeFormat`
+/// prints so shown sizes can be pasted back into options and config files
+const SUFFIXES: [(&str, u64); 17] = [
+    ("B", 1),
+    ("K", 1000),
+    ("KB", 1000),
+    ("KiB", 1 << 10),
+    ("M", 1_000_000),
+    ("MB", 1_000_000),
+    ("MiB", 1 << 20),
+    ("G", 1_000_000_000),
+    ("GB", 1_000_000_000),
+    ("GiB", 1 << 30),
+    ("T", 1_000_000_000_000),
+    ("TB", 1_000_000_000_000),
+    ("TiB", 1 << 40),
+    ("P", 1_000_000_000_000_000),
+    ("PB", 1_000_000_000_000_000),
+    ("PiB", 1 << 50),
+    ("", 1),
+];
+
+/// A number of bytes, parsed from sizes such as `4096`, `64KiB` or `1.5G` and shown in the
+/// same decimal units the reports use
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ByteSize(pub u64);
 
 impl From<ByteSize> for u64 {
     fn from(value: ByteSize) -> u64 {
-        match value {
-            ByteSize::Byte(value) => value,
-            ByteSize::KByte(value) => value * 1000,
-            ByteSize::KiByte(value) => value * 1024,
-            ByteSize::MByte(value) => value * 1_000_000,
-            ByteSize::MiByte(value) => value * 1_048_576,
-            ByteSize::GByte(value) => value * 1_000_000_000,
-            ByteSize::GiByte(value) => value * 1_073_741_824,
-            ByteSize::TByte(value) => value * 1_000_000_000_000,
-            ByteSize::TiByte(value) => value * 1_099_511_627_776,
+        return value.0;
+    }
+}
+
+impl fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let size_format = if plain_numbers() {
+            SizeFormat::Bytes
+        } else {
+            SizeFormat::Decimal
+        };
+
+        f.pad(&size_format.format(self.0))
+    }
+}
+
+/// A number of things, shown with thousands separators such as `1,234,567`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Count(pub usize);
+
+impl fmt::Display for Count {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = self.0.to_string();
+
+        if plain_numbers() {
+            return f.pad(&digits);
+        }
+
+        let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+
+        for (index, digit) in digits.chars().enumerate() {
+            if index > 0 && (digits.len() - index).is_multiple_of(3) {
+                grouped.push(',');
+            }
+
+            grouped.push(digit);
+        }
+
+        f.pad(&grouped)
+    }
+}
+
+/// Show counts without separators and sizes as exact bytes from now on, for scripts reading
+/// the output
+pub fn set_plain_numbers(plain: bool) {
+    PLAIN_NUMBERS.store(plain, Ordering::Relaxed);
+}
+
+pub fn plain_numbers() -> bool {
+    return PLAIN_NUMBERS.load(Ordering::Relaxed);
+}
+
+impl FromStr for ByteSize {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let split = value
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(value.len());
+        let (number, suffix) = value.split_at(split);
+
+        let multiplier = SUFFIXES
+            .iter()
+            .find(|(known, _)| *known == suffix)
+            .map(|(_, multiplier)| u128::from(*multiplier));
+
+        let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+
+        let (Some(multiplier), Ok(whole)) = (multiplier, whole.parse::<u128>()) else {
+            return Err(format!(
+                "Unknown \"{value}\", expected [number](KB,KiB,MB,MiB,GB,GiB,TB,TiB)"
+            ));
+        };
+
+        // Whole and fractional digits are kept as integers so 1.1GB is exactly 1,100,000,000
+        let fraction_bytes = if fraction.is_empty() {
+            0
+        } else {
+            let digits: u128 = or_else!(fraction.parse(), _ => {
+                return Err(format!("Unknown \"{value}\", expected a number such as 1.5GB"));
+            });
+            let scale = or_else!(10u128.checked_pow(fraction.len() as u32), none => {
+                return Err(format!("Too many decimals in \"{value}\""));
+            });
+
+            (digits * multiplier).div_ceil(scale)
+        };
+
+        let bytes = whole
+            .checked_mul(multiplier)
+            .and_then(|bytes| bytes.checked_add(fraction_bytes))
+            .and_then(|bytes| u64::try_from(bytes).ok());
+
+        return match bytes {
+            Some(bytes) => Ok(ByteSize(bytes)),
+            None => Err(format!("\"{value}\" is too large")),
+        };
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SizeFormat {
+    #[default]
+    Decimal,
+    Binary,
+    Bytes,
+}
+
+impl SizeFormat {
+    /// Format a size with one decimal in the largest unit it fills, or as exact bytes
+    pub fn format(self, size: u64) -> String {
+        let (base, units) = match self {
+            SizeFormat::Decimal => (1000.0, DECIMAL_UNITS),
+            SizeFormat::Binary => (1024.0, BINARY_UNITS),
+            SizeFormat::Bytes => return size.to_string(),
+        };
+
+        if (size as f64) < base {
+            return format!("{size}B");
+        }
+
+        let mut value = size as f64;
+        let mut unit = 0;
+
+        // Round before comparing so 999,999 B shows as 1.0MB rather than 1000.0KB
+        while unit < units.len() - 1 && (value * 10.0).round() / 10.0 >= base {
+            value /= base;
+            unit += 1;
+        }
+
+        return format!("{value:.1}{}", units[unit]);
+    }
+
+    pub fn width(self) -> usize {
+        match self {
+            SizeFormat::Bytes => 15,
+            _ => 9,
         }
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct ByteSizeValueParser {}
 
 impl ByteSizeValueParser {
@@ -70,48 +222,10 @@ impl TypedValueParser for ByteSizeValueParser {
             return err;
         })?;
 
-        if value.bytes().all(|c| c.is_ascii_digit())
-            && let Ok(value) = value.parse::<u64>()
-        {
-            return Ok(ByteSize::Byte(value));
-        }
-
-        let suffixes = [
-            "B", "KB", "K", "KiB", "MB", "M", "MiB", "GB", "G", "GiB", "TB", "T", "TiB",
-        ];
-
-        let valid_byte_size = suffixes
-            .iter()
-            .filter_map(|suffix| {
-                if let Some(value) = value.strip_suffix(suffix) {
-                    if value.bytes().all(|c| c.is_ascii_digit()) {
-                        Some((value, *suffix))
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                }
-            })
-            .next();
-
-        if let Some((value, suffix)) = valid_byte_size
-            && let Ok(value) = value.parse::<u64>()
-            && let Some(result) = match suffix {
-                "B" => Some(ByteSize::Byte(value)),
-                "KB" | "K" => Some(ByteSize::KByte(value)),
-                "KiB" => Some(ByteSize::KiByte(value)),
-                "MB" | "M" => Some(ByteSize::MByte(value)),
-                "MiB" => Some(ByteSize::MiByte(value)),
-                "GB" | "G" => Some(ByteSize::GByte(value)),
-                "GiB" => Some(ByteSize::GiByte(value)),
-                "TB" | "T" => Some(ByteSize::TByte(value)),
-                "TiB" => Some(ByteSize::TiByte(value)),
-                _ => None,
-            }
-        {
-            return Ok(result);
-        }
+        let message = match value.parse::<ByteSize>() {
+            Ok(byte_size) => return Ok(byte_size),
+            Err(message) => message,
+        };
 
         let mut err = clap::Error::new(clap::error::ErrorKind::ValueValidation).with_cmd(cmd);
         if let Some(arg) = arg {
@@ -121,12 +235,7 @@ impl TypedValueParser for ByteSizeValueParser {
             );
         }
 
-        err.insert(
-            ContextKind::InvalidValue,
-            ContextValue::String(format!(
-                "Unknown \"{value}\", expected [number](KB,KiB,MB,MiB,GB,GiB,TB,TiB)"
-            )),
-        );
+        err.insert(ContextKind::InvalidValue, ContextValue::String(message));
 
         return Err(err);
     }
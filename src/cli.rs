@@ -0,0 +1,1660 @@
+use std::env::{args_os, current_dir, temp_dir};
+use std::ffi::OsString;
+use std::fs::{OpenOptions, create_dir_all, read_to_string};
+use std::io::{IsTerminal, stdin};
+use std::mem::take;
+use std::path::{Path, PathBuf, absolute};
+use std::process::exit;
+use std::thread::sleep;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use clap::builder::BoolishValueParser;
+use clap::{ArgGroup, Parser, Subcommand};
+use globset::Glob;
+use regex::Regex;
+
+use crate::bench::bench;
+use crate::byte_size::{ByteSize, ByteSizeValueParser, Count, SizeFormat, set_plain_numbers};
+use crate::compare::{DiffFormat, compare_diff, compare_subset_check, compare_unique};
+use crate::config::{load_config, profile_args};
+use crate::dataset_info::{set_info, show_info};
+use crate::doctor::doctor;
+use crate::duplicate_report::{
+    ReportFormat, ReportOptions, Source, SourceLabels, duplicate_report,
+};
+use crate::errors::{AppError, AppErrorResult};
+use crate::export::{ExportFormat, export};
+use crate::find_copies::find_copies;
+use crate::forget::forget;
+use crate::git::save_git_heads;
+use crate::hash_cache::{HashCache, cache_directory};
+use crate::hash_data::{
+    DatasetInfo, FileEntry, ScanOrigin, back_up_hash_data, find_nested_roots,
+    get_hash_data_file_path, load_current_hash_data, load_hash_data_with_info,
+    open_hash_data_for_saving, read_dataset_info, save_hash_data, save_hash_data_to,
+    select_dataset, state_directory, try_lock_scan,
+};
+use crate::hash_group::{GroupSource, hash_group};
+use crate::hashing::{ALSO_HASH_ALGORITHMS, HasherFactory};
+use crate::history::{ScanRecord, append_scan_record, history_show};
+use crate::ignore_list::{IgnoreList, ignore_duplicates};
+use crate::last_report::ReportedGroups;
+use crate::listing::{compare_listing_subset_check, compare_listing_unique, import_listing};
+use crate::low_memory::low_memory_duplicate_report;
+use crate::notify::{scan_summary_message, send_desktop_notification, send_email};
+use crate::or_else;
+use crate::parity::create_parity;
+use crate::path_filter::PathFilter;
+use crate::privileges::{current_user_id, drop_privileges, user_id};
+use crate::prune::prune;
+use crate::query::Query;
+use crate::sandbox::restrict_filesystem;
+use crate::scan_folders::{
+    ChangeDetection, ScanOptions, ScanSummary, scan_folder_tree, scan_folder_tree_low_memory,
+};
+use crate::stats::stats;
+use crate::status::status;
+use crate::torrent::create_torrent;
+use crate::utils::{
+    AbortKey, OutputFormat, abort_requested, canonical_path, format_duration, parse_abort_key,
+    parse_interval, parse_percentage, random_duration, read_path_list,
+};
+use crate::verify::{SampleSize, VerifySelection, verify, verify_files};
+#[cfg(windows)]
+use crate::vss::ShadowCopy;
+
+#[derive(Parser)]
+#[command(version, about, long_about = None, args_override_self = true)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Commands>,
+
+    /// Base path to scan
+    #[arg(short, long, global = true, env = "HASHFOLDER_PATH")]
+    path: Option<PathBuf>,
+
+    /// Profile from the config file whose options apply unless given on the command line
+    #[arg(long, global = true, env = "HASHFOLDER_PROFILE")]
+    profile: Option<String>,
+
+    /// Named hash data to use instead of the default, so one folder can keep several
+    #[arg(long, global = true, env = "HASHFOLDER_DATASET")]
+    dataset: Option<String>,
+
+    /// Skip updating base path hashes
+    #[arg(short, long, env = "HASHFOLDER_SKIP", value_parser = BoolishValueParser::new())]
+    skip: bool,
+
+    /// Start over with empty hash data, moving the existing data file aside as a backup, such as
+    /// when it is damaged
+    #[arg(long, conflicts_with = "skip")]
+    reset_data: bool,
+
+    /// Path to compare
+    #[arg(short, long, env = "HASHFOLDER_OTHER")]
+    other: Option<PathBuf>,
+
+    /// Show report without other path
+    #[arg(short, long, env = "HASHFOLDER_REPORT", value_parser = BoolishValueParser::new())]
+    report: bool,
+
+    /// Minimum duplicate file size to report, empty files are left out unless this is 0
+    #[arg(short, long, value_parser = ByteSizeValueParser::new(), env = "HASHFOLDER_MINIMUM")]
+    minimum: Option<ByteSize>,
+
+    /// Leave out files and directories whose path relative to the base path matches this glob,
+    /// e.g. `**/node_modules` or `*.tmp`
+    #[arg(long, env = "HASHFOLDER_EXCLUDE", value_delimiter = ';')]
+    exclude: Vec<Glob>,
+
+    /// Leave out paths relative to the base path matching this regex, written with `/`
+    /// separators and a trailing `/` on directories, e.g. `^.*/cache-[0-9]{4}/`
+    #[arg(long, env = "HASHFOLDER_EXCLUDE_REGEX")]
+    exclude_regex: Vec<Regex>,
+
+    /// Also scan system files such as Thumbs.db, .DS_Store and desktop.ini, editor swap files
+    /// and browser caches, which are left out by default
+    #[arg(long)]
+    no_default_excludes: bool,
+
+    /// Leave dotfiles and dot-directories out of the scan
+    #[arg(long, env = "HASHFOLDER_SKIP_HIDDEN", value_parser = BoolishValueParser::new())]
+    skip_hidden: bool,
+
+    /// Only scan files owned by this user, by name or id, leaving out other users' files and
+    /// their directories that can't be read without reporting errors (Linux only)
+    #[arg(long, conflicts_with = "only_mine")]
+    owner: Option<String>,
+
+    /// Only scan files owned by the user running hashfolder, or the one that ran sudo (Linux
+    /// only)
+    #[arg(long)]
+    only_mine: bool,
+
+    /// Leave out subfolders that have hash data of their own, they are scanned as separate roots
+    #[arg(long, env = "HASHFOLDER_SKIP_NESTED_ROOTS", value_parser = BoolishValueParser::new())]
+    skip_nested_roots: bool,
+
+    /// Leave out .git directories
+    #[arg(long, env = "HASHFOLDER_GIT_AWARE", value_parser = BoolishValueParser::new())]
+    git_aware: bool,
+
+    /// Also leave out files tracked by git repositories, git checksums those already
+    #[arg(long, requires = "git_aware")]
+    skip_git_tracked: bool,
+
+    /// Save the commit checked out in each git repository to .hashfolder/git_heads.json
+    #[arg(long, requires = "git_aware")]
+    record_git_heads: bool,
+
+    /// Only scan files whose path relative to the base path matches this glob, e.g. `*.dng`
+    #[arg(long, env = "HASHFOLDER_INCLUDE", value_delimiter = ';')]
+    include: Vec<Glob>,
+
+    /// Skip hashing files smaller than this, they are left out of the hash data entirely
+    #[arg(long, value_parser = ByteSizeValueParser::new(), env = "HASHFOLDER_SCAN_MINIMUM")]
+    scan_minimum: Option<ByteSize>,
+
+    /// How strictly cached hashes are checked for file changes
+    #[arg(short, long, value_enum, default_value_t = ChangeDetection::Strict)]
+    change_detection: ChangeDetection,
+
+    /// Keep the entries of files under this path when they aren't found, such as on a network
+    /// share that may be offline, instead of dropping them
+    #[arg(long)]
+    preserve_missing: Vec<PathBuf>,
+
+    /// Stop without dropping any entries when more than this percentage of the known files are
+    /// missing, as when a disk isn't mounted or the path is mistyped
+    #[arg(long, default_value_t = 50, value_parser = clap::value_parser!(u8).range(0..=100))]
+    purge_limit: u8,
+
+    /// Drop the entries of missing files however many there are
+    #[arg(long)]
+    force_purge: bool,
+
+    /// Also store these digests for each file, computed in the same read as SHA-256
+    #[arg(long, value_delimiter = ',', value_parser = ALSO_HASH_ALGORITHMS)]
+    also_hash: Vec<String>,
+
+    /// Memory map large files for hashing, faster on fast disks but files truncated during
+    /// hashing can crash the scan
+    #[arg(long)]
+    mmap: bool,
+
+    /// Read buffer size for hashing, chosen from file size and disk type by default
+    #[arg(short, long, value_parser = ByteSizeValueParser::new())]
+    buffer_size: Option<ByteSize>,
+
+    /// Hash files in inode order with large reads to reduce seeking on rotational disks
+    #[arg(long)]
+    hdd_mode: bool,
+
+    /// Read batches of files concurrently with io_uring
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    #[arg(long)]
+    io_uring: bool,
+
+    /// Number of threads hashing files
+    #[arg(short, long, default_value_t = 1, env = "HASHFOLDER_THREADS")]
+    threads: usize,
+
+    /// Pause hashing while running on battery
+    #[arg(long, env = "HASHFOLDER_ONLY_ON_AC", value_parser = BoolishValueParser::new())]
+    only_on_ac: bool,
+
+    /// Pause hashing while the CPU is at its hot or critical trip point, Linux only
+    #[arg(long, env = "HASHFOLDER_PAUSE_WHEN_HOT", value_parser = BoolishValueParser::new())]
+    pause_when_hot: bool,
+
+    /// Print files matching content already in the hash data as soon as they are hashed
+    #[arg(long)]
+    live_duplicates: bool,
+
+    /// List empty directories found during the scan
+    #[arg(long)]
+    report_empty_dirs: bool,
+
+    /// List FIFOs, sockets and device nodes skipped during the scan
+    #[arg(long)]
+    report_special_files: bool,
+
+    /// Times to retry a failed open, read or metadata call before giving up on the file
+    #[arg(long, default_value_t = 2, env = "HASHFOLDER_RETRIES")]
+    retries: u32,
+
+    /// Leave files that are locked or open for writing by another process until the next run
+    #[arg(long)]
+    skip_in_use: bool,
+
+    /// When started as root, open the data file then switch to the sudo user keeping only the
+    /// ability to read files
+    #[arg(long)]
+    drop_privileges: bool,
+
+    /// Restrict the process to reading the scanned and compared paths, with writes only to
+    /// the base path's .hashfolder directory (Linux only)
+    #[arg(long)]
+    sandbox: bool,
+
+    /// Scan a Volume Shadow Copy of the drive so locked files are hashed as of one moment
+    /// (needs an elevated prompt)
+    #[cfg(windows)]
+    #[arg(long)]
+    vss: bool,
+
+    /// Email the scan summary using the email settings in the config file, which happens anyway
+    /// when running without a terminal
+    #[arg(long, env = "HASHFOLDER_NOTIFY", value_parser = BoolishValueParser::new())]
+    notify: bool,
+
+    /// Share hashes with other scans on this machine through a cache keyed by device and inode,
+    /// so files reachable under several roots are read once
+    #[arg(long, env = "HASHFOLDER_SHARED_CACHE", value_parser = BoolishValueParser::new())]
+    shared_cache: bool,
+
+    /// Progress display redraws per second, lower suits slow terminals such as SSH sessions
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u8).range(1..=60))]
+    refresh_rate: u8,
+
+    /// Show scan progress on a single status line instead of a line per hash thread
+    #[arg(long)]
+    status_line: bool,
+
+    /// Append the time, path, size, hash and read time of every hashed file to this file
+    #[arg(long, env = "HASHFOLDER_LOG_FILE")]
+    log_file: Option<PathBuf>,
+
+    /// Key that stops the scan and saves progress: a plain key such as q must be pressed twice
+    /// within 2 seconds, a combination such as ctrl+q only once
+    #[arg(long, default_value = "q", value_parser = parse_abort_key)]
+    abort_key: AbortKey,
+
+    /// Show a desktop notification when the scan finishes or stops
+    #[arg(long)]
+    desktop_notification: bool,
+
+    /// Keep running, scanning again at this interval (e.g. 30m, 24h, 7d)
+    #[arg(long, value_parser = parse_interval, conflicts_with_all = ["drop_privileges", "sandbox"])]
+    every: Option<Duration>,
+
+    /// Delay each scheduled scan by a random time up to this, so machines sharing storage
+    /// don't all scan at once
+    #[arg(long, value_parser = parse_interval, requires = "every")]
+    jitter: Option<Duration>,
+
+    /// Scan and group duplicates with on-disk sorts instead of in memory, for trees with
+    /// millions of files. Files moved to a new path are hashed again.
+    #[arg(short, long, conflicts_with_all = ["live_duplicates", "shared_cache"])]
+    low_memory: bool,
+
+    /// Show report sizes in binary units (KiB, MiB)
+    #[arg(long, conflicts_with = "bytes")]
+    binary_units: bool,
+
+    /// Show report sizes as exact byte counts
+    #[arg(long)]
+    bytes: bool,
+
+    /// Print counts without thousands separators and sizes as exact bytes, for scripts
+    #[arg(long, env = "HASHFOLDER_PLAIN_NUMBERS", value_parser = BoolishValueParser::new())]
+    plain_numbers: bool,
+
+    /// Report which directory pairs share the most duplicated content
+    #[arg(long, conflicts_with = "by_extension")]
+    by_directory: bool,
+
+    /// Summarize duplicate files and wasted space per file extension
+    #[arg(long)]
+    by_extension: bool,
+
+    /// Also report duplicates across the hash data of every folder below the base path that has
+    /// its own, for one report over per-project hash data
+    #[arg(long)]
+    recursive_manifests: bool,
+
+    /// Only report duplicate groups that weren't in the previous report or have gained copies
+    #[arg(long)]
+    since_last: bool,
+
+    /// Report output format, html writes a standalone page with sorting and filtering
+    #[arg(short, long, value_enum, default_value_t = ReportFormat::Text)]
+    format: ReportFormat,
+
+    /// Render the report with a minijinja template file instead of a built-in format
+    #[arg(long)]
+    template: Option<PathBuf>,
+
+    /// Leave out files of the other path whose path relative to it matches this glob, without
+    /// rescanning it
+    #[arg(long, requires = "other")]
+    other_exclude: Vec<Glob>,
+
+    /// Name shown for files from the base path in reports, defaults to the label set with `info`
+    /// or `base`
+    #[arg(long)]
+    base_label: Option<String>,
+
+    /// Name shown for files from the other path in reports, defaults to the label set with
+    /// `info` or `other`
+    #[arg(long)]
+    other_label: Option<String>,
+
+    /// Only report duplicate groups with a copy under this path, relative to the base path
+    #[arg(long)]
+    within: Vec<PathBuf>,
+
+    /// Only report duplicate groups with a copy outside this path, relative to the base path.
+    /// With `--within` on the same path, shows what in it already exists elsewhere
+    #[arg(long)]
+    not_within: Vec<PathBuf>,
+
+    /// Pause the report after this many duplicate groups instead of after each screenful
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    page_size: Option<u64>,
+
+    /// Print the whole report without pausing, even in a terminal
+    #[arg(long, env = "HASHFOLDER_NO_PAGER", value_parser = BoolishValueParser::new(), conflicts_with = "page_size")]
+    no_pager: bool,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Remove hash data entries under a path without scanning
+    Forget {
+        /// Path whose entries are dropped from the hash data
+        prefix: PathBuf,
+    },
+
+    /// Remove hash data entries no scan has found for a long time, such as those kept with
+    /// `--preserve-missing`, or under a path without scanning
+    #[command(group(ArgGroup::new("selection").required(true).multiple(true)))]
+    Prune {
+        /// Drop entries not found by a scan or checked by `verify` for longer than this, such
+        /// as `90d` or `1y`
+        #[arg(long, value_parser = parse_interval, group = "selection")]
+        older_than: Option<Duration>,
+
+        /// Drop entries under this path, only those also older than `--older-than` if given
+        #[arg(long, group = "selection")]
+        under: Vec<PathBuf>,
+
+        /// Count the entries that would be dropped without saving anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// List every known copy of one content hash in the base, `--other` and extra hash data,
+    /// with sizes and modified times
+    Group {
+        /// Hash of the content to list
+        hash: String,
+
+        /// More hash data files or folders to search
+        #[arg(short = 'i', long = "in")]
+        data_paths: Vec<PathBuf>,
+    },
+
+    /// Hash a file and search hash data for copies of it
+    Find {
+        /// File to look for
+        file: PathBuf,
+
+        /// Hash data files or folders to search, defaults to the base path
+        #[arg(short = 'i', long = "in")]
+        data_paths: Vec<PathBuf>,
+    },
+
+    /// Show statistics for hash data
+    Stats {
+        /// Hash data file or folder, defaults to the base path
+        data_path: Option<PathBuf>,
+
+        /// Number of entries in the largest and most duplicated lists
+        #[arg(short, long, default_value_t = 10)]
+        top: usize,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+
+    /// Show the label, description, origin and timestamps saved with the hash data, or set the
+    /// label and description
+    Info {
+        /// Name shown for this hash data in reports comparing it with other hash data, empty to
+        /// clear it
+        #[arg(long, conflicts_with = "data_path")]
+        label: Option<String>,
+
+        /// Free text describing the hash data, empty to clear it
+        #[arg(long, conflicts_with = "data_path")]
+        description: Option<String>,
+
+        /// Hash data file or folder to show, defaults to the base path
+        #[arg(short = 'i', long = "in")]
+        data_path: Option<PathBuf>,
+    },
+
+    /// Check hash data for problems
+    Doctor {
+        /// Remove problem entries and save the hash data
+        #[arg(short, long)]
+        repair: bool,
+    },
+
+    /// Measure hashing throughput on the base path's disk and recommend settings
+    Bench {
+        /// Size of the test file
+        #[arg(short, long, value_parser = ByteSizeValueParser::new(), default_value = "256MiB")]
+        size: ByteSize,
+    },
+
+    /// Scan history of the base path
+    History {
+        #[command(subcommand)]
+        command: HistoryCommands,
+    },
+
+    /// Compare the base hash data with another by content
+    #[command(group(ArgGroup::new("mode").required(true)))]
+    Compare {
+        /// Hash data file or folder to compare with
+        other: PathBuf,
+
+        /// Compare with a directory listing from `import-listing`, `find -printf '%s %T@ %p\n'`
+        /// or `ls -lR` instead, matching files by size and modified time without their contents
+        #[arg(long)]
+        listing: bool,
+
+        /// List files whose content exists in only one of the two
+        #[arg(long, group = "mode")]
+        unique: bool,
+
+        /// Fail unless every file in the other has a copy in the base, listing exceptions
+        #[arg(long, group = "mode")]
+        subset_check: bool,
+
+        /// List files added, removed, modified or moved going from the other to the base, by
+        /// their path relative to the folder each was scanned from
+        #[arg(long, group = "mode", conflicts_with = "listing")]
+        diff: bool,
+
+        /// Output format for `--diff`, `unified` for a patch of the two manifests
+        #[arg(long, value_enum, default_value = "text", requires = "diff")]
+        format: DiffFormat,
+    },
+
+    /// Hash a file and search the hash data for copies of it like `find`, or with `--files-from`
+    /// hash the listed files into the hash data, keeping the entries of all other files, e.g. at
+    /// the end of a `find` pipeline
+    #[command(group(ArgGroup::new("files").required(true)))]
+    Hash {
+        /// File to look for, the same as `find <file>`
+        #[arg(group = "files")]
+        file: Option<PathBuf>,
+
+        /// File of NUL or newline separated paths, `-` for stdin
+        #[arg(long, group = "files")]
+        files_from: Option<PathBuf>,
+
+        /// Compare the listed files with the hash data instead of updating it, failing if any
+        /// differ
+        #[arg(long, requires = "files_from")]
+        check: bool,
+    },
+
+    /// Convert a directory listing from `find -printf '%s %T@ %p\n'` or `ls -lR` for
+    /// `compare --listing`
+    ImportListing {
+        /// Listing to read
+        listing: PathBuf,
+
+        /// File to write
+        output: PathBuf,
+    },
+
+    /// Accept reviewed duplicates so later reports only show new ones
+    Ignore {
+        /// Hashes of duplicate groups to accept
+        hashes: Vec<String>,
+
+        /// Two paths to accept as copies of each other, may be repeated
+        #[arg(long, num_args = 2, value_names = ["PATH", "COPY"])]
+        pair: Vec<PathBuf>,
+
+        /// Accept every duplicate group in the current hash data
+        #[arg(long)]
+        all: bool,
+    },
+
+    /// Export hash data to a Parquet file for analysis in other tools, or a CRC32 `.sfv` file
+    Export {
+        /// File to write
+        output: PathBuf,
+
+        /// Output format, picked from the file extension by default
+        #[arg(short, long, value_enum)]
+        format: Option<ExportFormat>,
+
+        /// Hash data file or folder, defaults to the base path
+        #[arg(short = 'i', long = "in")]
+        data_path: Option<PathBuf>,
+
+        /// Only export entries matching this filter, e.g. `size > 1GiB and ext in (mkv, iso)`,
+        /// comparing size, age, ext, name, path or hash and combined with and, or and not
+        #[arg(long = "where", value_name = "QUERY")]
+        filter: Option<Query>,
+    },
+
+    /// List files new, changed or missing since the last scan by size and modified time alone,
+    /// failing if there are any
+    Status,
+
+    /// Hash every file again and report any whose content no longer matches the hash data
+    Verify {
+        /// Only verify a random sample of this percentage of the data, larger files being more
+        /// likely picked, such as `5%`
+        #[arg(long, value_parser = parse_percentage)]
+        sample: Option<f64>,
+
+        /// Only verify a random sample of about this much data
+        #[arg(long, value_parser = ByteSizeValueParser::new(), conflicts_with = "sample")]
+        sample_bytes: Option<ByteSize>,
+
+        /// Verify the files verified longest ago first, those never verified before all others
+        #[arg(long, conflicts_with_all = ["sample", "sample_bytes"])]
+        oldest_first: bool,
+
+        /// Stop starting files after this long, such as `2h`, to spread verification over runs
+        #[arg(long, value_parser = parse_interval, requires = "oldest_first")]
+        budget: Option<Duration>,
+
+        /// Stop once about this much data has been read
+        #[arg(long, value_parser = ByteSizeValueParser::new(), requires = "oldest_first")]
+        budget_bytes: Option<ByteSize>,
+    },
+
+    /// Create PAR2 recovery files for each scanned directory with par2cmdline
+    Par2 {
+        /// Recovery data as a percentage of each directory's size
+        #[arg(short, long, default_value_t = 10, value_parser = clap::value_parser!(u8).range(1..=100))]
+        redundancy: u8,
+    },
+
+    /// Write a .torrent file for the scanned files so the base path can be seeded as it is
+    Torrent {
+        /// Torrent file to write
+        output: PathBuf,
+
+        /// Tracker announce URL, may be repeated
+        #[arg(long)]
+        tracker: Vec<String>,
+
+        /// Size of each hashed piece, chosen from the total size by default
+        #[arg(long, value_parser = ByteSizeValueParser::new())]
+        piece_size: Option<ByteSize>,
+    },
+}
+
+#[derive(Subcommand)]
+enum HistoryCommands {
+    /// List recent scans with their change counts, flagging unusually large changes
+    Show {
+        /// Number of scans to list
+        #[arg(short = 'n', long, default_value_t = 20)]
+        last: usize,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+}
+
+pub fn main() {
+    let args = or_else!(parse_args(), err => {
+        println!("{err}");
+        return;
+    });
+
+    set_plain_numbers(args.plain_numbers);
+
+    let starting_dir = or_else!(get_starting_dir(&args), err => {
+        println!("{err:?}");
+        return;
+    });
+
+    if !starting_dir.exists() {
+        println!("Path not found: {}", starting_dir.to_string_lossy());
+        return;
+    }
+
+    if !starting_dir.is_dir() {
+        println!(
+            "Path is not a directory: {}",
+            starting_dir.to_string_lossy()
+        );
+        return;
+    }
+
+    if let Some(dataset) = &args.dataset
+        && let Err(err) = select_dataset(dataset)
+    {
+        println!("{err}");
+        return;
+    }
+
+    if let Some(command) = &args.command {
+        let result = match command {
+            Commands::Forget { prefix } => forget(&starting_dir, prefix),
+            Commands::Prune {
+                older_than,
+                under,
+                dry_run,
+            } => prune(&starting_dir, *older_than, under, *dry_run),
+            Commands::Group { hash, data_paths } => {
+                hash_group(hash, &group_sources(&starting_dir, &args, data_paths))
+            }
+            Commands::Find { file, data_paths } => find_copies(&starting_dir, file, data_paths),
+            Commands::Stats {
+                data_path,
+                top,
+                format,
+            } => stats(data_path.as_deref().unwrap_or(&starting_dir), *top, *format),
+            Commands::Info {
+                label: None,
+                description: None,
+                data_path,
+            } => show_info(data_path.as_deref().unwrap_or(&starting_dir)),
+            Commands::Info {
+                label, description, ..
+            } => set_info(&starting_dir, label.as_deref(), description.as_deref()),
+            Commands::Doctor { repair } => doctor(&starting_dir, *repair),
+            Commands::Bench { size } => bench(&starting_dir, (*size).into()),
+            Commands::History {
+                command: HistoryCommands::Show { last, format },
+            } => history_show(&starting_dir, *last, *format),
+            Commands::Compare {
+                other,
+                listing: true,
+                subset_check: true,
+                ..
+            } => compare_listing_subset_check(&starting_dir, other),
+            Commands::Compare {
+                other,
+                listing: true,
+                ..
+            } => compare_listing_unique(&starting_dir, other),
+            Commands::Compare {
+                other,
+                subset_check: true,
+                ..
+            } => compare_subset_check(&starting_dir, other),
+            Commands::Compare {
+                other,
+                diff: true,
+                format,
+                ..
+            } => compare_diff(&starting_dir, other, *format),
+            Commands::Compare { other, .. } => compare_unique(&starting_dir, other),
+            Commands::ImportListing { listing, output } => import_listing(listing, output),
+            Commands::Hash {
+                file: Some(file), ..
+            } => find_copies(&starting_dir, file, &[]),
+            Commands::Hash {
+                files_from: Some(files_from),
+                check: true,
+                ..
+            } => read_listed_files(&starting_dir, files_from).and_then(|files| {
+                verify_files(&starting_dir, &files, args.abort_key, args.refresh_rate)
+            }),
+            Commands::Hash { .. } => {
+                scan_and_report(&starting_dir, &args);
+                Ok(())
+            }
+            Commands::Ignore { hashes, pair, all } => {
+                ignore_duplicates(&starting_dir, hashes, pair, *all)
+            }
+            Commands::Export {
+                output,
+                format,
+                data_path,
+                filter,
+            } => export(
+                data_path.as_deref().unwrap_or(&starting_dir),
+                output,
+                format.unwrap_or_else(|| ExportFormat::from_output(output)),
+                filter.as_ref(),
+            ),
+            Commands::Status => {
+                get_walk_options(&args).and_then(|options| status(&starting_dir, &options))
+            }
+            Commands::Verify {
+                sample,
+                sample_bytes,
+                oldest_first,
+                budget,
+                budget_bytes,
+            } => verify(
+                &starting_dir,
+                verify_selection(
+                    *sample,
+                    *sample_bytes,
+                    *oldest_first,
+                    *budget,
+                    *budget_bytes,
+                ),
+                args.abort_key,
+                args.refresh_rate,
+            ),
+            Commands::Par2 { redundancy } => create_parity(&starting_dir, *redundancy),
+            Commands::Torrent {
+                output,
+                tracker,
+                piece_size,
+            } => create_torrent(&starting_dir, output, tracker, piece_size.map(u64::from)),
+        };
+
+        if let Err(err) = result {
+            println!("{err}");
+            exit(1);
+        }
+
+        return;
+    }
+
+    match args.every {
+        Some(every) => run_on_schedule(&starting_dir, &args, every),
+        None => scan_and_report(&starting_dir, &args),
+    }
+}
+
+/// Scan and report now and then again every `every`, each wait lengthened by up to `--jitter`
+fn run_on_schedule(starting_dir: &Path, args: &Args, every: Duration) {
+    loop {
+        let started = Instant::now();
+
+        scan_and_report(starting_dir, args);
+
+        if abort_requested() {
+            return;
+        }
+
+        let jitter = args.jitter.map(random_duration).unwrap_or_default();
+        let wait = (every + jitter).saturating_sub(started.elapsed());
+
+        println!("Next scan in {}", format_duration(wait));
+
+        sleep(wait);
+    }
+}
+
+fn scan_and_report(starting_dir: &Path, args: &Args) {
+    if args.skip && args.low_memory {
+        if args.other.is_some() || args.report {
+            match read_dataset_info(starting_dir) {
+                Ok(info) => run_low_memory_report(starting_dir, args, &info),
+                Err(err) => println!("{err}"),
+            }
+        }
+
+        return;
+    }
+
+    // Held until the data is saved, so overlapping runs such as a slow scheduled scan and the
+    // next one don't scan the same tree at once
+    let _scan_lock = if args.skip {
+        None
+    } else {
+        match try_lock_scan(starting_dir) {
+            Ok(Some(scan_lock)) => Some(scan_lock),
+            Ok(None) => {
+                println!(
+                    "A previous scan of {} is still running, skipping this one",
+                    starting_dir.to_string_lossy()
+                );
+                return;
+            }
+            Err(err) => {
+                println!("{err}");
+                return;
+            }
+        }
+    };
+
+    let (mut data_file, mut info) = if args.reset_data {
+        // The label and description outlive the entries, unless the data file can't be read
+        let previous_info = read_dataset_info(starting_dir).unwrap_or_default();
+        let info = DatasetInfo {
+            label: previous_info.label,
+            description: previous_info.description,
+            ..DatasetInfo::default()
+        };
+
+        match back_up_hash_data(starting_dir) {
+            Ok(Some(backup_path)) => {
+                println!("Moved hash data to {}", backup_path.to_string_lossy());
+                (Vec::default(), info)
+            }
+            Ok(None) => (Vec::default(), info),
+            Err(err) => {
+                println!("{err}");
+                return;
+            }
+        }
+    } else if args.low_memory && !args.skip {
+        // The entries are streamed from disk by the scan
+        let info = match get_hash_data_file_path(starting_dir, true) {
+            Ok(data_path) if data_path.exists() => read_dataset_info(starting_dir),
+            Ok(_) => Ok(DatasetInfo::default()),
+            Err(err) => Err(err),
+        };
+
+        (
+            Vec::default(),
+            or_else!(info, err => {
+                println!("{err}");
+                println!("Run with --reset-data to move the hash data aside and start over");
+                return;
+            }),
+        )
+    } else {
+        or_else!(load_hash_data_with_info(starting_dir, true), err => {
+            println!("{err}");
+            println!("Run with --reset-data to move the hash data aside and start over");
+            return;
+        })
+    };
+
+    if !args.skip {
+        // Worked out before privileges are dropped, reading the devices may need them
+        let origin = ScanOrigin::of(starting_dir);
+
+        // A data file copied or moved with the tree, or a mistyped path, would otherwise have
+        // every entry treated as a missing file
+        for difference in info
+            .origin
+            .iter()
+            .flat_map(|saved| saved.differences(&origin))
+        {
+            println!("WARNING: {difference}");
+        }
+
+        info.origin = Some(origin);
+
+        // Opened up front so it can still be saved after privileges are dropped or sandboxed
+        let mut opened_data_file = if args.drop_privileges || args.sandbox {
+            let hash_data_file = or_else!(open_hash_data_for_saving(starting_dir), err => {
+                println!("{err}");
+                return;
+            });
+
+            if args.drop_privileges
+                && let Err(err) = drop_privileges()
+            {
+                println!("{err}");
+                return;
+            }
+
+            if args.sandbox
+                && let Err(err) = enter_sandbox(starting_dir, args)
+            {
+                println!("{err}");
+                return;
+            }
+
+            Some(hash_data_file)
+        } else {
+            None
+        };
+
+        // Kept until the scan is done, dropping it deletes the snapshot
+        #[cfg(windows)]
+        let shadow_copy = if args.vss {
+            Some(or_else!(ShadowCopy::create(starting_dir), err => {
+                println!("{err}");
+                return;
+            }))
+        } else {
+            None
+        };
+
+        #[cfg(windows)]
+        let walk_root = shadow_copy
+            .as_ref()
+            .map(|shadow_copy| shadow_copy.snapshot_path(starting_dir));
+        #[cfg(not(windows))]
+        let walk_root = None;
+
+        let shared_cache = if args.shared_cache {
+            Some(or_else!(HashCache::load(), err => {
+                println!("{err}");
+                return;
+            }))
+        } else {
+            None
+        };
+
+        let path_filter = or_else!(get_path_filter(args), err => {
+            println!("{err}");
+            return;
+        });
+
+        let hasher = or_else!(HasherFactory::default().with_also(&args.also_hash), err => {
+            println!("{err}");
+            return;
+        });
+
+        let preserve_missing = or_else!(base_relative_paths(starting_dir, &args.preserve_missing), err => {
+            println!("{err}");
+            return;
+        });
+
+        let owner = or_else!(get_owner(args), err => {
+            println!("{err}");
+            return;
+        });
+
+        let files_from = match &args.command {
+            Some(Commands::Hash {
+                files_from: Some(files_from),
+                ..
+            }) => Some(
+                or_else!(read_listed_files(starting_dir, files_from), err => {
+                    println!("{err}");
+                    return;
+                }),
+            ),
+            _ => None,
+        };
+
+        if args.low_memory && files_from.is_some() {
+            println!("--low-memory can't be used to hash only listed files");
+            return;
+        }
+
+        let scan_options = ScanOptions {
+            change_detection: args.change_detection,
+            mmap: args.mmap,
+            buffer_size: args.buffer_size.map(u64::from),
+            hdd_mode: args.hdd_mode,
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            io_uring: args.io_uring,
+            threads: args.threads,
+            live_duplicates: args.live_duplicates,
+            retries: args.retries,
+            skip_in_use: args.skip_in_use,
+            walk_root,
+            shared_cache,
+            refresh_rate: args.refresh_rate,
+            status_line: args.status_line,
+            log_file: args.log_file.clone(),
+            abort_key: args.abort_key,
+            scan_minimum: args.scan_minimum.map(u64::from).unwrap_or_default(),
+            path_filter,
+            skip_hidden: args.skip_hidden,
+            hasher,
+            git_aware: args.git_aware,
+            skip_git_tracked: args.skip_git_tracked,
+            only_on_ac: args.only_on_ac,
+            pause_when_hot: args.pause_when_hot,
+            skip_nested_roots: args.skip_nested_roots,
+            files_from,
+            preserve_missing,
+            purge_limit: (!args.force_purge).then_some(args.purge_limit),
+            owner,
+        };
+
+        let mut scan_summary = ScanSummary::default();
+
+        let started = SystemTime::now();
+        let timer = Instant::now();
+
+        let (returned_data_file, saved, scan_err) = if args.low_memory {
+            // Written as the scan goes rather than saved at the end
+            let hash_data_file = match opened_data_file.take() {
+                Some(hash_data_file) => Ok(hash_data_file),
+                None => open_hash_data_for_saving(starting_dir),
+            };
+
+            match hash_data_file {
+                Ok(hash_data_file) => {
+                    let (saved, scan_err) = scan_folder_tree_low_memory(
+                        starting_dir,
+                        &hash_data_file,
+                        &info,
+                        &scan_options,
+                        &mut scan_summary,
+                    );
+
+                    (None, saved, scan_err)
+                }
+                Err(err) => (None, None, Some(err)),
+            }
+        } else {
+            let (returned_data_file, scan_err) = scan_folder_tree(
+                take(&mut data_file),
+                starting_dir,
+                &scan_options,
+                &mut scan_summary,
+            );
+
+            let saved = returned_data_file.as_ref().map(|data_file| {
+                (
+                    data_file.len(),
+                    data_file.iter().map(|file| file.file_size).sum(),
+                )
+            });
+
+            (returned_data_file, saved, scan_err)
+        };
+
+        if abort_requested() {
+            println!("Aborting, saving progress…");
+        } else if let Some(scan_err) = &scan_err {
+            println!("{scan_err}");
+        }
+
+        print_failed_files(&scan_summary);
+
+        if !scan_summary.in_use_files.is_empty() {
+            println!(
+                "{} files in use were skipped until the next run",
+                Count(scan_summary.in_use_files.len())
+            );
+        }
+
+        if scan_summary.hidden_skipped > 0 {
+            println!(
+                "{} hidden files and directories skipped",
+                Count(scan_summary.hidden_skipped)
+            );
+        }
+
+        if scan_summary.other_owner_skipped > 0 {
+            println!(
+                "{} files and directories of other users skipped",
+                Count(scan_summary.other_owner_skipped)
+            );
+        }
+
+        if scan_summary.git_tracked_skipped > 0 {
+            println!(
+                "{} files tracked by git skipped in {} repositories",
+                Count(scan_summary.git_tracked_skipped),
+                Count(scan_summary.git_repositories.len())
+            );
+        }
+
+        if !scan_summary.nested_roots.is_empty() {
+            println!(
+                "{} folders with their own hash data skipped",
+                Count(scan_summary.nested_roots.len())
+            );
+        }
+
+        if scan_summary.preserved_missing > 0 {
+            println!(
+                "{} entries of missing files under preserved paths kept",
+                Count(scan_summary.preserved_missing)
+            );
+        }
+
+        if args.record_git_heads
+            && let Err(err) = save_git_heads(starting_dir, &scan_summary.git_repositories)
+        {
+            println!("Could not save the git repository commits: {err}");
+        }
+
+        if args.report_empty_dirs {
+            print_empty_directories(&scan_summary);
+        }
+
+        if args.report_special_files {
+            print_special_files(&scan_summary);
+        }
+
+        notify_scan_result(starting_dir, args, &scan_summary, scan_err.as_ref());
+
+        let (files, bytes) = or_else!(saved, none => return);
+
+        let record = ScanRecord {
+            started: started
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            duration_secs: timer.elapsed().as_secs_f64(),
+            files,
+            bytes,
+            hashed_bytes: scan_summary.hashed_bytes,
+            new_files: scan_summary.new_files,
+            changed_files: scan_summary.changed_files,
+            removed_files: scan_summary.removed_files,
+            moved_files: scan_summary.moved_files,
+            errors: scan_summary.failed_files.len() + scan_summary.unreadable_paths.len(),
+            completed: scan_err.is_none(),
+        };
+
+        if let Err(err) = append_scan_record(starting_dir, &record) {
+            println!("Could not record the scan in the history: {err}");
+        }
+
+        if let Some(returned_data_file) = returned_data_file {
+            data_file = returned_data_file;
+
+            let save_result = match &opened_data_file {
+                Some(hash_data_file) => save_hash_data_to(hash_data_file, &info, &data_file),
+                None => save_hash_data(starting_dir, &info, &data_file),
+            };
+
+            if let Err(err) = save_result {
+                println!("{err}");
+            }
+
+            if args.shared_cache
+                && let Err(err) = HashCache::save_entries(&data_file)
+            {
+                println!("Could not update the shared hash cache: {err}");
+            }
+        }
+
+        if scan_err.is_some() {
+            return;
+        }
+    }
+
+    if args.other.is_some() || args.report {
+        if args.low_memory {
+            drop(data_file);
+            run_low_memory_report(starting_dir, args, &info);
+            return;
+        }
+
+        let other = or_else!(
+            get_other_data_file(args.other.as_deref()),
+            err => {
+                println!("{err}");
+                return;
+            }
+        );
+
+        let (mut other_data_file, other_info) = other.unzip();
+
+        let report_options = or_else!(
+            get_report_options(starting_dir, args, &info, other_info.as_ref()),
+            err => {
+                println!("{err}");
+                return;
+            }
+        );
+
+        if let Some(other_data_file) = &mut other_data_file {
+            let found_under_root = other_data_file
+                .iter()
+                .any(|file| report_options.is_under_other_root(&file.file_name));
+
+            if let Err(err) = report_options.check_other_root(found_under_root) {
+                println!("{err}");
+                return;
+            }
+        }
+
+        if args.recursive_manifests {
+            data_file = or_else!(merge_nested_hash_data(starting_dir, data_file), err => {
+                println!("{err}");
+                return;
+            });
+        }
+
+        let mut reported = ReportedGroups::default();
+
+        duplicate_report(
+            &data_file,
+            other_data_file.as_deref(),
+            &report_options,
+            &mut reported,
+        );
+
+        if let Err(err) = reported.save(starting_dir) {
+            println!("{err}");
+        }
+    }
+}
+
+fn enter_sandbox(starting_dir: &Path, args: &Args) -> Result<(), AppError> {
+    let temp_dir = temp_dir();
+
+    let mut read_paths: Vec<&Path> = vec![starting_dir, Path::new("/proc"), Path::new("/sys")];
+    read_paths.extend(args.other.as_deref());
+    read_paths.extend(args.template.as_deref());
+
+    let state_directory = state_directory(starting_dir);
+
+    let mut read_write_paths: Vec<&Path> = vec![Path::new("/dev/tty"), &state_directory];
+
+    if args.low_memory {
+        read_write_paths.push(&temp_dir);
+    }
+
+    if let Some(log_file) = &args.log_file {
+        // Landlock rules need the file to exist
+        OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(log_file)
+            .app_err()?;
+
+        read_write_paths.push(log_file);
+    }
+
+    let cache_directory = cache_directory();
+
+    if args.shared_cache
+        && let Some(cache_directory) = &cache_directory
+    {
+        create_dir_all(cache_directory).app_err()?;
+        read_write_paths.push(cache_directory);
+    }
+
+    return restrict_filesystem(&read_paths, &read_write_paths);
+}
+
+/// Report the outcome of a scan on the desktop when asked to, and by email when asked to or
+/// when nobody is watching the terminal and the config file has email settings
+fn notify_scan_result(
+    starting_dir: &Path,
+    args: &Args,
+    scan_summary: &ScanSummary,
+    scan_err: Option<&AppError>,
+) {
+    let (subject, body) = scan_summary_message(starting_dir, scan_summary, scan_err);
+
+    if args.desktop_notification
+        && let Err(err) = send_desktop_notification(&subject, &body)
+    {
+        println!("Could not show a desktop notification: {err}");
+    }
+
+    if !args.notify && stdin().is_terminal() {
+        return;
+    }
+
+    let config = or_else!(load_config(), err => {
+        println!("{err}");
+        return;
+    });
+
+    let email = or_else!(&config.email, none => {
+        if args.notify {
+            println!("No email settings in the config file");
+        }
+        return;
+    });
+
+    if let Err(err) = send_email(email, &subject, body) {
+        println!("Could not email the scan summary: {err}");
+    }
+}
+
+fn print_failed_files(scan_summary: &ScanSummary) {
+    if !scan_summary.unreadable_paths.is_empty() {
+        println!(
+            "{} paths skipped for lack of permission",
+            Count(scan_summary.unreadable_paths.len())
+        );
+
+        for path in &scan_summary.unreadable_paths {
+            println!("  {path}");
+        }
+
+        println!();
+    }
+
+    if scan_summary.failed_files.is_empty() {
+        return;
+    }
+
+    println!(
+        "{} files could not be read",
+        Count(scan_summary.failed_files.len())
+    );
+
+    for (file_name, err) in &scan_summary.failed_files {
+        println!("  {file_name}: {err}");
+    }
+
+    println!();
+}
+
+fn print_empty_directories(scan_summary: &ScanSummary) {
+    if scan_summary.empty_directories.is_empty() {
+        println!("No empty directories found");
+        return;
+    }
+
+    println!(
+        "{} empty directories",
+        Count(scan_summary.empty_directories.len())
+    );
+
+    for directory in &scan_summary.empty_directories {
+        println!("  {}", directory.to_string_lossy());
+    }
+
+    println!();
+}
+
+fn print_special_files(scan_summary: &ScanSummary) {
+    if scan_summary.special_files.is_empty() {
+        println!("No special files found");
+        return;
+    }
+
+    println!(
+        "{} special files skipped",
+        Count(scan_summary.special_files.len())
+    );
+
+    for (path, kind) in &scan_summary.special_files {
+        println!("  {} ({kind})", path.to_string_lossy());
+    }
+
+    println!();
+}
+
+fn run_low_memory_report(starting_dir: &Path, args: &Args, info: &DatasetInfo) {
+    let nested_roots = if args.recursive_manifests {
+        find_nested_roots(starting_dir)
+    } else {
+        Vec::default()
+    };
+
+    let mut data_paths: Vec<(Source, &Path)> = vec![(Source::Base, starting_dir)];
+
+    data_paths.extend(
+        nested_roots
+            .iter()
+            .map(|root| (Source::Base, root.as_path())),
+    );
+
+    if let Some(other) = &args.other {
+        data_paths.push((Source::Other, other));
+    }
+
+    let mut reported = ReportedGroups::default();
+
+    let result = args
+        .other
+        .as_deref()
+        .map(read_dataset_info)
+        .transpose()
+        .and_then(|other_info| get_report_options(starting_dir, args, info, other_info.as_ref()))
+        .and_then(|report_options| {
+            low_memory_duplicate_report(&data_paths, &report_options, &mut reported)
+        })
+        .and_then(|()| reported.save(starting_dir));
+
+    if let Err(err) = result {
+        println!("{err}");
+    }
+}
+
+/// Add the hash data of each nested folder to the base path's, keeping one entry per path
+fn merge_nested_hash_data(
+    starting_dir: &Path,
+    mut data_file: Vec<FileEntry>,
+) -> Result<Vec<FileEntry>, AppError> {
+    let nested_roots = find_nested_roots(starting_dir);
+
+    for nested_root in &nested_roots {
+        data_file.extend(load_current_hash_data(nested_root, false)?);
+    }
+
+    data_file.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+    data_file.dedup_by(|a, b| a.file_name == b.file_name);
+
+    println!(
+        "Merged hash data from {} nested folders",
+        Count(nested_roots.len())
+    );
+
+    return Ok(data_file);
+}
+
+fn get_path_filter(args: &Args) -> Result<PathFilter, AppError> {
+    return PathFilter::new(
+        &args.exclude,
+        &args.exclude_regex,
+        &args.include,
+        !args.no_default_excludes,
+    );
+}
+
+/// Options deciding which files a scan looks at, for commands that walk the tree like one
+fn get_walk_options(args: &Args) -> Result<ScanOptions, AppError> {
+    return Ok(ScanOptions {
+        scan_minimum: args.scan_minimum.map(u64::from).unwrap_or_default(),
+        path_filter: get_path_filter(args)?,
+        skip_hidden: args.skip_hidden,
+        git_aware: args.git_aware,
+        skip_git_tracked: args.skip_git_tracked,
+        skip_nested_roots: args.skip_nested_roots,
+        owner: get_owner(args)?,
+        ..ScanOptions::default()
+    });
+}
+
+/// The user id scans are limited to with `--owner` or `--only-mine`
+fn get_owner(args: &Args) -> Result<Option<u32>, AppError> {
+    if args.only_mine {
+        return current_user_id().map(Some);
+    }
+
+    return args.owner.as_deref().map(user_id).transpose();
+}
+
+/// How reports are shown, using the details already read with the base and other hash data
+fn get_report_options(
+    starting_dir: &Path,
+    args: &Args,
+    info: &DatasetInfo,
+    other_info: Option<&DatasetInfo>,
+) -> Result<ReportOptions, AppError> {
+    let size_format = if args.bytes || args.plain_numbers {
+        SizeFormat::Bytes
+    } else if args.binary_units {
+        SizeFormat::Binary
+    } else {
+        SizeFormat::Decimal
+    };
+
+    let template = match &args.template {
+        Some(template_path) => Some(read_to_string(template_path).app_err()?),
+        None => None,
+    };
+
+    let since_last = if args.since_last {
+        Some(ReportedGroups::load(starting_dir)?)
+    } else {
+        None
+    };
+
+    return Ok(ReportOptions {
+        minimum: args.minimum,
+        size_format,
+        by_directory: args.by_directory,
+        by_extension: args.by_extension,
+        format: args.format,
+        template,
+        ignore_list: IgnoreList::load(starting_dir)?,
+        since_last,
+        // A scheduled report left waiting for a key would hold up the next scan
+        pager: !args.no_pager && args.every.is_none(),
+        page_size: args.page_size.map(|page_size| page_size as usize),
+        other_exclude: get_other_exclude(args, other_info)?,
+        labels: SourceLabels {
+            base: source_label(args.base_label.as_deref(), Some(info), Source::Base),
+            other: source_label(args.other_label.as_deref(), other_info, Source::Other),
+        },
+        within: base_relative_paths(starting_dir, &args.within)?,
+        not_within: base_relative_paths(starting_dir, &args.not_within)?,
+    });
+}
+
+fn get_other_exclude(
+    args: &Args,
+    other_info: Option<&DatasetInfo>,
+) -> Result<Option<(PathBuf, PathFilter)>, AppError> {
+    let other = or_else!(&args.other, none => return Ok(None));
+
+    if args.other_exclude.is_empty() {
+        return Ok(None);
+    }
+
+    // The stored paths are under the folder the other data was scanned from, which --other
+    // needn't name when it points at the data file itself
+    let root = match other_info.and_then(|info| info.origin.as_ref()) {
+        Some(origin) => PathBuf::from(&origin.root),
+        None if other.is_dir() => canonical_path(other)?,
+        None => {
+            return Err(AppError::new(format!(
+                "{} doesn't record the folder it was scanned from, scan it again to use --other-exclude",
+                other.to_string_lossy()
+            )));
+        }
+    };
+
+    return Ok(Some((
+        root,
+        PathFilter::new(&args.other_exclude, &[], &[], false)?,
+    )));
+}
+
+/// Files listed for `hash --files-from` as stored in the hash data, leaving out any outside the
+/// base path
+fn read_listed_files(starting_dir: &Path, files_from: &Path) -> Result<Vec<PathBuf>, AppError> {
+    let mut files = Vec::default();
+
+    for file in read_path_list(files_from)? {
+        let file = absolute(&file).app_err_at("Resolving", &file)?;
+
+        // Only the folder is resolved, so a listed symlink keeps the name the scan stores it by
+        let file = match (file.parent(), file.file_name()) {
+            (Some(parent), Some(name)) => canonical_path(parent)?.join(name),
+            _ => canonical_path(&file)?,
+        };
+
+        if !file.starts_with(starting_dir) {
+            println!(
+                "Skipping {}, it isn't under the base path",
+                file.to_string_lossy()
+            );
+            continue;
+        }
+
+        files.push(file);
+    }
+
+    files.sort_unstable();
+    files.dedup();
+
+    return Ok(files);
+}
+
+/// The base path, `--other` and any extra hash data, labelled as in reports with extra hash
+/// data named by its saved label or its path
+fn group_sources(starting_dir: &Path, args: &Args, data_paths: &[PathBuf]) -> Vec<GroupSource> {
+    let mut sources = vec![GroupSource {
+        label: args.base_label.clone(),
+        default_label: Source::Base.label().into(),
+        data_path: starting_dir.to_owned(),
+    }];
+
+    if let Some(other) = &args.other {
+        sources.push(GroupSource {
+            label: args.other_label.clone(),
+            default_label: Source::Other.label().into(),
+            data_path: other.clone(),
+        });
+    }
+
+    for data_path in data_paths {
+        sources.push(GroupSource {
+            label: None,
+            default_label: data_path.to_string_lossy().into_owned(),
+            data_path: data_path.clone(),
+        });
+    }
+
+    return sources;
+}
+
+/// Which files `verify` reads, from its options
+fn verify_selection(
+    sample: Option<f64>,
+    sample_bytes: Option<ByteSize>,
+    oldest_first: bool,
+    budget: Option<Duration>,
+    budget_bytes: Option<ByteSize>,
+) -> VerifySelection {
+    if oldest_first {
+        return VerifySelection::OldestFirst {
+            time: budget,
+            bytes: budget_bytes.map(u64::from),
+        };
+    }
+
+    return match (sample, sample_bytes) {
+        (Some(percentage), _) => VerifySelection::Sample(SampleSize::Percentage(percentage)),
+        (None, Some(bytes)) => VerifySelection::Sample(SampleSize::Bytes(bytes.into())),
+        (None, None) => VerifySelection::All,
+    };
+}
+
+/// The label given for a source, or the one saved with its hash data, or its default
+fn source_label(given: Option<&str>, info: Option<&DatasetInfo>, source: Source) -> String {
+    if let Some(given) = given {
+        return given.into();
+    }
+
+    return info
+        .and_then(|info| info.label.clone())
+        .unwrap_or_else(|| source.label().into());
+}
+
+/// Paths given relative to the base path in the form the hash data stores them
+fn base_relative_paths(starting_dir: &Path, paths: &[PathBuf]) -> Result<Vec<PathBuf>, AppError> {
+    return paths
+        .iter()
+        .map(|path| canonical_path(&starting_dir.join(path)))
+        .collect();
+}
+
+/// Parse the command line with the chosen profile's options in front, so anything given on the
+/// command line overrides them
+fn parse_args() -> Result<Args, AppError> {
+    let args = Args::parse();
+
+    let profile = or_else!(&args.profile, none => return Ok(args));
+
+    let mut command_line: Vec<OsString> = args_os().collect();
+    command_line.splice(1..1, profile_args(&load_config()?, profile)?);
+
+    return Ok(Args::parse_from(command_line));
+}
+
+fn get_starting_dir(args: &Args) -> Result<PathBuf, AppError> {
+    if let Some(path) = &args.path {
+        return canonical_path(path);
+    }
+
+    return canonical_path(&current_dir().app_err()?);
+}
+
+fn get_other_data_file(
+    other: Option<&Path>,
+) -> Result<Option<(Vec<FileEntry>, DatasetInfo)>, AppError> {
+    let other_path = or_else!(other, none => return Ok(None));
+
+    return load_hash_data_with_info(other_path, false).map(Some);
+}
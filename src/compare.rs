@@ -0,0 +1,435 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::stdout;
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::byte_size::{ByteSize, Count};
+use crate::duplicate_report::Source;
+use crate::errors::{AppError, AppErrorResult};
+use crate::hash_data::{FileEntry, load_current_hash_data, load_hash_data_with_info};
+use crate::utils::canonical_path;
+
+/// Unchanged files shown around each change in the unified format, as `diff -u` does
+const UNIFIED_CONTEXT: usize = 3;
+
+/// Characters of each hash shown in the unified format
+const UNIFIED_HASH_LENGTH: usize = 16;
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum DiffFormat {
+    Text,
+    Json,
+    /// Patch of the manifests as `<hash prefix>  <path>` lines sorted by path, for review tools
+    Unified,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeType {
+    Added,
+    Removed,
+    Modified,
+    Moved,
+}
+
+impl ChangeType {
+    fn label(self) -> &'static str {
+        match self {
+            ChangeType::Added => "added",
+            ChangeType::Removed => "removed",
+            ChangeType::Modified => "modified",
+            ChangeType::Moved => "moved",
+        }
+    }
+}
+
+/// A file that differs between the other hash data and the base, with paths relative to the
+/// folder each was scanned from
+#[derive(Serialize)]
+pub struct Change<'a> {
+    pub change: ChangeType,
+    /// Path in the base, or in the other for removed files
+    pub path: String,
+    /// Path in the other for moved files
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_path: Option<String>,
+    pub old: Option<&'a FileEntry>,
+    pub new: Option<&'a FileEntry>,
+}
+
+#[derive(Serialize)]
+struct ManifestDiff<'a> {
+    changes: Vec<Change<'a>>,
+    unchanged: usize,
+}
+
+/// List files whose content exists in only one of the base and other hash data, ignoring paths
+pub fn compare_unique(starting_dir: &Path, other_path: &Path) -> Result<(), AppError> {
+    let base = load_current_hash_data(starting_dir, false)?;
+    let other = load_current_hash_data(other_path, false)?;
+
+    let base_hashes: HashSet<&str> = base.iter().map(|file| file.hash.as_str()).collect();
+    let other_hashes: HashSet<&str> = other.iter().map(|file| file.hash.as_str()).collect();
+
+    print_unique(Source::Base, &base, &other_hashes);
+    print_unique(Source::Other, &other, &base_hashes);
+
+    return Ok(());
+}
+
+/// Check that every file in the other hash data has a copy somewhere in the base, by content
+pub fn compare_subset_check(starting_dir: &Path, other_path: &Path) -> Result<(), AppError> {
+    let base = load_current_hash_data(starting_dir, false)?;
+    let other = load_current_hash_data(other_path, false)?;
+
+    let base_hashes: HashSet<&str> = base.iter().map(|file| file.hash.as_str()).collect();
+
+    let missing = print_unique(Source::Other, &other, &base_hashes);
+
+    if missing > 0 {
+        return Err(AppError::new(format!(
+            "{} of {} files in other have no copy in base",
+            Count(missing),
+            Count(other.len())
+        )));
+    }
+
+    println!(
+        "All {} files in other have a copy in base",
+        Count(other.len())
+    );
+
+    return Ok(());
+}
+
+/// Print files whose hash is missing from the other side and return how many there were
+fn print_unique(source: Source, files: &[FileEntry], missing_from: &HashSet<&str>) -> usize {
+    let unique: Vec<&FileEntry> = files
+        .iter()
+        .filter(|file| !missing_from.contains(file.hash.as_str()))
+        .collect();
+
+    if unique.is_empty() {
+        println!("No files only in {}", source.label());
+        return 0;
+    }
+
+    let size = ByteSize(unique.iter().map(|file| file.file_size).sum());
+
+    println!(
+        "Only in {}: {} files ({size})",
+        source.label(),
+        Count(unique.len())
+    );
+
+    for file in &unique {
+        println!("  {}", file.file_name);
+    }
+
+    println!();
+
+    return unique.len();
+}
+
+/// List how the base hash data differs from the other by path: files added to the base, removed
+/// from it, modified in place or moved with their content unchanged
+pub fn compare_diff(
+    starting_dir: &Path,
+    other_path: &Path,
+    format: DiffFormat,
+) -> Result<(), AppError> {
+    let (base, base_root) = load_with_root(starting_dir)?;
+    let (other, other_root) = load_with_root(other_path)?;
+
+    let base_files = relative_paths(&base, &base_root);
+    let other_files = relative_paths(&other, &other_root);
+
+    let diff = diff_manifests(&base_files, &other_files);
+
+    match format {
+        DiffFormat::Text => print_diff(&diff),
+        DiffFormat::Json => {
+            serde_json::to_writer_pretty(stdout(), &diff).app_err()?;
+            println!();
+        }
+        DiffFormat::Unified => print_unified_diff(
+            &other_path.to_string_lossy(),
+            &starting_dir.to_string_lossy(),
+            &unified_lines(&base_files, &other_files),
+        ),
+    }
+
+    return Ok(());
+}
+
+/// Hash data along with the folder it was scanned from, so its paths can be made relative
+fn load_with_root(data_path: &Path) -> Result<(Vec<FileEntry>, PathBuf), AppError> {
+    let (data_file, info) = load_hash_data_with_info(data_path, false)?;
+
+    let root = match info.origin {
+        Some(origin) => PathBuf::from(origin.root),
+        None if data_path.is_dir() => canonical_path(data_path)?,
+        None => {
+            return Err(AppError::new(format!(
+                "Can't tell which folder {} was scanned from, give the folder instead or scan it \
+                 again",
+                data_path.to_string_lossy()
+            )));
+        }
+    };
+
+    return Ok((data_file, root));
+}
+
+fn relative_paths<'a>(data_file: &'a [FileEntry], root: &Path) -> BTreeMap<String, &'a FileEntry> {
+    return data_file
+        .iter()
+        .map(|file| {
+            let path = Path::new(&file.file_name);
+            let relative = path.strip_prefix(root).unwrap_or(path);
+
+            (relative.to_string_lossy().into_owned(), file)
+        })
+        .collect();
+}
+
+fn diff_manifests<'a>(
+    base: &BTreeMap<String, &'a FileEntry>,
+    other: &BTreeMap<String, &'a FileEntry>,
+) -> ManifestDiff<'a> {
+    let mut changes = Vec::default();
+    let mut unchanged = 0;
+
+    // Files only in the other, by hash, so files only in the base can be matched up as moves
+    let mut removed: HashMap<&str, Vec<(&String, &FileEntry)>> = HashMap::default();
+
+    for (path, file) in other {
+        if !base.contains_key(path) {
+            removed
+                .entry(file.hash.as_str())
+                .or_default()
+                .push((path, file));
+        }
+    }
+
+    for (path, file) in base {
+        let change = match other.get(path) {
+            Some(old) if old.hash == file.hash => {
+                unchanged += 1;
+                continue;
+            }
+            Some(old) => Change {
+                change: ChangeType::Modified,
+                path: path.clone(),
+                old_path: None,
+                old: Some(old),
+                new: Some(file),
+            },
+            None => match removed
+                .get_mut(file.hash.as_str())
+                .and_then(|moved| moved.pop())
+            {
+                Some((old_path, old)) => Change {
+                    change: ChangeType::Moved,
+                    path: path.clone(),
+                    old_path: Some(old_path.clone()),
+                    old: Some(old),
+                    new: Some(file),
+                },
+                None => Change {
+                    change: ChangeType::Added,
+                    path: path.clone(),
+                    old_path: None,
+                    old: None,
+                    new: Some(file),
+                },
+            },
+        };
+
+        changes.push(change);
+    }
+
+    for (path, old) in removed.into_values().flatten() {
+        changes.push(Change {
+            change: ChangeType::Removed,
+            path: path.clone(),
+            old_path: None,
+            old: Some(old),
+            new: None,
+        });
+    }
+
+    changes.sort_by(|a, b| a.path.cmp(&b.path));
+
+    return ManifestDiff { changes, unchanged };
+}
+
+fn print_diff(diff: &ManifestDiff) {
+    for change in &diff.changes {
+        match &change.old_path {
+            Some(old_path) => {
+                println!("{:<8} {old_path} -> {}", change.change.label(), change.path)
+            }
+            None => println!("{:<8} {}", change.change.label(), change.path),
+        }
+    }
+
+    let count = |change_type: ChangeType| {
+        diff.changes
+            .iter()
+            .filter(|change| change.change == change_type)
+            .count()
+    };
+
+    println!(
+        "{} added, {} removed, {} modified, {} moved, {} unchanged",
+        Count(count(ChangeType::Added)),
+        Count(count(ChangeType::Removed)),
+        Count(count(ChangeType::Modified)),
+        Count(count(ChangeType::Moved)),
+        Count(diff.unchanged)
+    );
+}
+
+/// A manifest line of the unified format
+enum DiffLine {
+    Same(String),
+    Removed(String),
+    Added(String),
+}
+
+impl DiffLine {
+    fn in_old(&self) -> bool {
+        return !matches!(self, DiffLine::Added(_));
+    }
+
+    fn in_new(&self) -> bool {
+        return !matches!(self, DiffLine::Removed(_));
+    }
+}
+
+/// Both manifests merged by path, a modified file as its old line followed by its new one
+fn unified_lines(
+    base: &BTreeMap<String, &FileEntry>,
+    other: &BTreeMap<String, &FileEntry>,
+) -> Vec<DiffLine> {
+    let manifest_line = |path: &str, file: &FileEntry| {
+        let hash = file.hash.get(..UNIFIED_HASH_LENGTH).unwrap_or(&file.hash);
+        format!("{hash}  {path}")
+    };
+
+    let mut lines = Vec::with_capacity(base.len().max(other.len()));
+    let mut base = base.iter().peekable();
+    let mut other = other.iter().peekable();
+
+    loop {
+        let line = match (other.peek().copied(), base.peek().copied()) {
+            (None, None) => break,
+            (Some((path, old)), Some((new_path, new))) if path == new_path => {
+                other.next();
+                base.next();
+
+                if old.hash == new.hash {
+                    DiffLine::Same(manifest_line(path, new))
+                } else {
+                    lines.push(DiffLine::Removed(manifest_line(path, old)));
+                    DiffLine::Added(manifest_line(path, new))
+                }
+            }
+            (Some((path, old)), None) => {
+                other.next();
+                DiffLine::Removed(manifest_line(path, old))
+            }
+            (Some((path, old)), Some((new_path, _))) if path < new_path => {
+                other.next();
+                DiffLine::Removed(manifest_line(path, old))
+            }
+            (_, Some((path, new))) => {
+                base.next();
+                DiffLine::Added(manifest_line(path, new))
+            }
+        };
+
+        lines.push(line);
+    }
+
+    return lines;
+}
+
+/// Print the changed lines in hunks with their surrounding context, as `diff -u` would for the
+/// two manifests
+fn print_unified_diff(old_name: &str, new_name: &str, lines: &[DiffLine]) {
+    let changed: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, DiffLine::Same(_)))
+        .map(|(index, _)| index)
+        .collect();
+
+    if changed.is_empty() {
+        return;
+    }
+
+    println!("--- {old_name}");
+    println!("+++ {new_name}");
+
+    let mut hunk_start = 0;
+
+    // Lines of each manifest ahead of `counted`, carried forward from hunk to hunk
+    let mut counted = 0;
+    let mut old_before = 0;
+    let mut new_before = 0;
+
+    while hunk_start < changed.len() {
+        // Changes close enough that their context would touch share a hunk
+        let mut hunk_end = hunk_start;
+        while hunk_end + 1 < changed.len()
+            && changed[hunk_end + 1] - changed[hunk_end] <= 2 * UNIFIED_CONTEXT + 1
+        {
+            hunk_end += 1;
+        }
+
+        let first = changed[hunk_start].saturating_sub(UNIFIED_CONTEXT);
+        let last = (changed[hunk_end] + UNIFIED_CONTEXT).min(lines.len() - 1);
+        let hunk = &lines[first..=last];
+
+        for line in &lines[counted..first] {
+            old_before += usize::from(line.in_old());
+            new_before += usize::from(line.in_new());
+        }
+
+        let old_count = hunk.iter().filter(|line| line.in_old()).count();
+        let new_count = hunk.iter().filter(|line| line.in_new()).count();
+
+        println!(
+            "@@ -{} +{} @@",
+            hunk_range(old_before, old_count),
+            hunk_range(new_before, new_count)
+        );
+
+        for line in hunk {
+            match line {
+                DiffLine::Same(line) => println!(" {line}"),
+                DiffLine::Removed(line) => println!("-{line}"),
+                DiffLine::Added(line) => println!("+{line}"),
+            }
+        }
+
+        old_before += old_count;
+        new_before += new_count;
+        counted = last + 1;
+
+        hunk_start = hunk_end + 1;
+    }
+}
+
+/// Line range of a hunk, starting after the line before it when it is empty
+fn hunk_range(before: usize, count: usize) -> String {
+    return match count {
+        0 => format!("{before},0"),
+        1 => format!("{}", before + 1),
+        _ => format!("{},{count}", before + 1),
+    };
+}
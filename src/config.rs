@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+use std::env::var_os;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::errors::{AppError, AppErrorResult};
+use crate::notify::EmailConfig;
+use crate::or_else;
+
+const CONFIG_FILENAME: &str = "config.json";
+
+/// User settings, read from `hashfolder/config.json` in the platform config directory
+#[derive(Default, Deserialize)]
+pub struct Config {
+    /// Named sets of options keyed by their long name, e.g.
+    /// `{"photos": {"path": "D:/Photos", "minimum": "1MB", "report": true}}`
+    #[serde(default)]
+    pub profiles: HashMap<String, HashMap<String, Value>>,
+    /// Where to send scan summaries
+    #[serde(default)]
+    pub email: Option<EmailConfig>,
+}
+
+fn config_file_path() -> Option<PathBuf> {
+    #[cfg(windows)]
+    let config_dir = PathBuf::from(var_os("APPDATA")?);
+
+    #[cfg(not(windows))]
+    let config_dir = match var_os("XDG_CONFIG_HOME") {
+        Some(config_home) => PathBuf::from(config_home),
+        None => PathBuf::from(var_os("HOME")?).join(".config"),
+    };
+
+    return Some(config_dir.join("hashfolder").join(CONFIG_FILENAME));
+}
+
+pub fn load_config() -> Result<Config, AppError> {
+    let config_path = or_else!(config_file_path(), none => return Ok(Config::default()));
+
+    if !config_path.is_file() {
+        return Ok(Config::default());
+    }
+
+    let reader = BufReader::new(File::open(&config_path).app_err()?);
+
+    return serde_json::from_reader(reader).app_err();
+}
+
+/// Command line arguments for the options stored in a profile
+pub fn profile_args(config: &Config, name: &str) -> Result<Vec<OsString>, AppError> {
+    let profile = or_else!(config.profiles.get(name), none => {
+        return Err(AppError::new(format!("Profile {name} not found in the config file")));
+    });
+
+    let mut args: Vec<OsString> = Vec::default();
+
+    for (option, value) in profile {
+        let values = match value {
+            Value::Array(values) => values.iter().collect(),
+            value => vec![value],
+        };
+
+        for value in values {
+            match value {
+                Value::Bool(true) => args.push(format!("--{option}").into()),
+                Value::Bool(false) | Value::Null => {}
+                Value::String(value) => {
+                    args.push(format!("--{option}").into());
+                    args.push(value.into());
+                }
+                Value::Number(value) => {
+                    args.push(format!("--{option}").into());
+                    args.push(value.to_string().into());
+                }
+                _ => {
+                    return Err(AppError::new(format!(
+                        "Profile {name} option {option} must be a string, number or boolean"
+                    )));
+                }
+            }
+        }
+    }
+
+    return Ok(args);
+}
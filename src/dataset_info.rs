@@ -0,0 +1,66 @@
+use std::path::Path;
+
+use crate::byte_size::{ByteSize, Count};
+use crate::errors::AppError;
+use crate::hash_data::{
+    DatasetInfo, for_each_hash_entry, load_hash_data_with_info, save_hash_data,
+};
+use crate::utils::format_timestamp;
+
+/// Print the details saved with hash data and how many files it lists
+pub fn show_info(data_path: &Path) -> Result<(), AppError> {
+    let mut files = 0;
+    let mut total_size = 0;
+
+    let info = for_each_hash_entry(data_path, |entry| {
+        files += 1;
+        total_size += entry.file_size;
+        return Ok(());
+    })?;
+
+    print_field("Label", info.label);
+    print_field("Description", info.description);
+    print_field(
+        "Root",
+        info.origin.as_ref().map(|origin| origin.root.clone()),
+    );
+    print_field(
+        "File system",
+        info.origin.and_then(|origin| origin.filesystem_uuid),
+    );
+    print_field("Created", info.created.map(format_timestamp));
+    print_field("Updated", info.updated.map(format_timestamp));
+    print_field(
+        "Files",
+        Some(format!("{} ({})", Count(files), ByteSize(total_size))),
+    );
+
+    return Ok(());
+}
+
+/// Set the label and description saved with the base path's hash data, an empty value clears it
+pub fn set_info(
+    starting_dir: &Path,
+    label: Option<&str>,
+    description: Option<&str>,
+) -> Result<(), AppError> {
+    let (data_file, info) = load_hash_data_with_info(starting_dir, false)?;
+
+    let non_empty = |value: &str| (!value.is_empty()).then(|| value.to_string());
+
+    let info = DatasetInfo {
+        label: label.map_or(info.label.clone(), non_empty),
+        description: description.map_or(info.description.clone(), non_empty),
+        ..info
+    };
+
+    save_hash_data(starting_dir, &info, &data_file)?;
+
+    return show_info(starting_dir);
+}
+
+fn print_field(name: &str, value: Option<String>) {
+    if let Some(value) = value {
+        println!("{:<12} {value}", format!("{name}:"));
+    }
+}
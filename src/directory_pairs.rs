@@ -0,0 +1,94 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+
+use crate::duplicate_report::{ReportStyle, Source, size_color};
+
+const FILES_WIDTH: usize = 6;
+
+type DirectoryKey = (Source, String);
+
+#[derive(Default)]
+struct SharedContent {
+    bytes: u64,
+    files: u64,
+}
+
+/// Totals of duplicated content shared between each pair of directories
+#[derive(Default)]
+pub struct DirectoryPairs {
+    pairs: HashMap<(DirectoryKey, DirectoryKey), SharedContent>,
+}
+
+impl DirectoryPairs {
+    pub fn add_group<'a>(
+        &mut self,
+        file_size: u64,
+        files: impl Iterator<Item = (Source, &'a str)>,
+    ) {
+        let directories: BTreeSet<(Source, &str)> = files
+            .map(|(source, file_name)| {
+                let directory = Path::new(file_name)
+                    .parent()
+                    .and_then(Path::to_str)
+                    .unwrap_or_default();
+
+                (source, directory)
+            })
+            .collect();
+
+        let directories: Vec<(Source, &str)> = directories.into_iter().collect();
+
+        for (index, (a_source, a_directory)) in directories.iter().enumerate() {
+            for (b_source, b_directory) in &directories[index + 1..] {
+                let key = (
+                    (*a_source, a_directory.to_string()),
+                    (*b_source, b_directory.to_string()),
+                );
+
+                let shared = self.pairs.entry(key).or_default();
+                shared.bytes += file_size;
+                shared.files += 1;
+            }
+        }
+    }
+
+    pub fn print(self, style: &ReportStyle) {
+        let mut pairs: Vec<_> = self.pairs.into_iter().collect();
+
+        pairs.sort_unstable_by(|(a_key, a), (b_key, b)| {
+            (Reverse(a.bytes), a_key).cmp(&(Reverse(b.bytes), b_key))
+        });
+
+        let size_width = style.size_width();
+        let source_width = style.source_width();
+
+        println!(
+            "{}",
+            style.bold(format!(
+                "{:>size_width$} {:>FILES_WIDTH$}  {:<source_width$}  Directory",
+                "Shared", "Files", "Source"
+            ))
+        );
+
+        for (((a_source, a_directory), (b_source, b_directory)), shared) in pairs {
+            let size = style.format_size(shared.bytes);
+
+            println!();
+            println!(
+                "{} {:>FILES_WIDTH$}  {}  {}",
+                style.paint(format!("{size:>size_width$}"), size_color(shared.bytes)),
+                shared.files,
+                style.source_label(a_source),
+                a_directory
+            );
+            println!(
+                "{:>size_width$} {:>FILES_WIDTH$}  {}  {}",
+                "",
+                "",
+                style.source_label(b_source),
+                b_directory
+            );
+        }
+    }
+}
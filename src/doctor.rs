@@ -0,0 +1,83 @@
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::byte_size::Count;
+use crate::errors::{AppError, AppErrorResult};
+use crate::hash_data::{FileEntry, read_hash_data_with_info, save_hash_data};
+use crate::hashing::HasherFactory;
+
+const MTIME_TOLERANCE_SECS: u64 = 86_400;
+
+pub fn doctor(starting_dir: &Path, repair: bool) -> Result<(), AppError> {
+    let (mut hash_data, info) = read_hash_data_with_info(starting_dir, false)?;
+
+    println!("Checking {} entries", Count(hash_data.len()));
+
+    let mut problems = 0;
+
+    if !hash_data.is_sorted_by_key(|entry| &entry.file_name) {
+        println!("Entries are not sorted by path");
+        problems += 1;
+    }
+
+    hash_data.sort_by(|a, b| {
+        a.file_name
+            .cmp(&b.file_name)
+            .then_with(|| b.modified.cmp(&a.modified))
+    });
+
+    let latest_modified = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .app_err()?
+        .as_secs()
+        + MTIME_TOLERANCE_SECS;
+
+    let hash_length = HasherFactory::default().create().digest_len() * 2;
+
+    let mut seen_paths: HashSet<String> = HashSet::with_capacity(hash_data.len());
+
+    let mut checked: Vec<FileEntry> = Vec::with_capacity(hash_data.len());
+
+    for entry in hash_data {
+        let problem = if !seen_paths.insert(entry.file_name.clone()) {
+            Some("Duplicate path")
+        } else if entry.hash.is_empty() {
+            Some("Empty hash")
+        } else if hex::decode(&entry.hash).is_err() {
+            Some("Invalid hash")
+        } else if entry.hash.len() != hash_length {
+            Some("Wrong hash length")
+        } else if !Path::new(&entry.file_name).starts_with(starting_dir) {
+            Some("Path outside root")
+        } else if entry.modified > latest_modified {
+            Some("Modified time in the future")
+        } else {
+            None
+        };
+
+        match problem {
+            Some(problem) => {
+                println!("{problem}: {}", entry.file_name);
+                problems += 1;
+            }
+            None => checked.push(entry),
+        }
+    }
+
+    if problems == 0 {
+        println!("No problems found");
+        return Ok(());
+    }
+
+    println!("{} problems found", Count(problems));
+
+    if repair {
+        save_hash_data(starting_dir, &info, &checked)?;
+        println!("Repaired hash data, {} entries kept", Count(checked.len()));
+    } else {
+        println!("Run with --repair to fix them");
+    }
+
+    return Ok(());
+}
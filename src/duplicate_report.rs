@@ -1,71 +1,480 @@
 use std::cmp::Reverse;
-use std::collections::HashMap;
-use std::mem::take;
+use std::io::{IsTerminal, stdout};
+use std::path::{Path, PathBuf};
 
-use crate::byte_size::ByteSize;
+use clap::ValueEnum;
+use crossterm::style::{Color, Stylize};
+use serde::{Deserialize, Serialize};
+
+use crate::byte_size::{ByteSize, Count, SizeFormat};
+use crate::directory_pairs::DirectoryPairs;
+use crate::errors::AppError;
+use crate::extension_summary::ExtensionSummary;
 use crate::hash_data::FileEntry;
+use crate::html_report::HtmlReport;
+use crate::ignore_list::IgnoreList;
+use crate::last_report::ReportedGroups;
+use crate::or_else;
+use crate::pager::Pager;
+use crate::path_filter::PathFilter;
+use crate::template_report::TemplateReport;
+
+const COPIES_WIDTH: usize = 6;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Source {
+    Base,
+    Other,
+}
+
+impl Source {
+    pub fn label(self) -> &'static str {
+        match self {
+            Source::Base => "base",
+            Source::Other => "other",
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            Source::Base => Color::Cyan,
+            Source::Other => Color::Magenta,
+        }
+    }
+}
+
+/// Names shown for each source in reports, `base` and `other` unless given
+#[derive(Clone, Debug)]
+pub struct SourceLabels {
+    pub base: String,
+    pub other: String,
+}
+
+impl Default for SourceLabels {
+    fn default() -> Self {
+        return SourceLabels {
+            base: Source::Base.label().into(),
+            other: Source::Other.label().into(),
+        };
+    }
+}
+
+impl SourceLabels {
+    pub fn label(&self, source: Source) -> &str {
+        return match source {
+            Source::Base => &self.base,
+            Source::Other => &self.other,
+        };
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    Text,
+    Html,
+}
+
+pub struct ReportStyle {
+    color: bool,
+    labels: SourceLabels,
+    source_width: usize,
+    size_format: SizeFormat,
+}
+
+impl ReportStyle {
+    /// Color is only used when stdout is a terminal
+    pub fn new(with_other: bool, size_format: SizeFormat, labels: SourceLabels) -> Self {
+        let source_width = if with_other {
+            labels
+                .base
+                .chars()
+                .count()
+                .max(labels.other.chars().count())
+        } else {
+            labels.base.chars().count()
+        };
+
+        return ReportStyle {
+            color: stdout().is_terminal(),
+            labels,
+            source_width: source_width.max("Source".len()),
+            size_format,
+        };
+    }
+
+    pub fn size_width(&self) -> usize {
+        return self.size_format.width();
+    }
+
+    pub fn source_width(&self) -> usize {
+        return self.source_width;
+    }
+
+    pub fn format_size(&self, size: u64) -> String {
+        return self.size_format.format(size);
+    }
+
+    /// Source label padded to the source column
+    pub fn source_label(&self, source: Source) -> String {
+        return self.paint(
+            format!("{:<1$}", self.labels.label(source), self.source_width),
+            source.color(),
+        );
+    }
 
+    pub fn paint(&self, text: String, color: Color) -> String {
+        if self.color {
+            return text.with(color).to_string();
+        }
+
+        return text;
+    }
+
+    pub fn bold(&self, text: String) -> String {
+        if self.color {
+            return text.bold().to_string();
+        }
+
+        return text;
+    }
+}
+
+pub struct ReportOptions {
+    pub minimum: Option<ByteSize>,
+    pub size_format: SizeFormat,
+    pub by_directory: bool,
+    pub by_extension: bool,
+    pub format: ReportFormat,
+    pub template: Option<String>,
+    /// Duplicates already reviewed, left out of the report
+    pub ignore_list: IgnoreList,
+    /// Groups from the previous report, when only new ones are shown
+    pub since_last: Option<ReportedGroups>,
+    /// Pause the printed groups once a page is full when run in a terminal
+    pub pager: bool,
+    /// Groups per page, otherwise a screenful
+    pub page_size: Option<usize>,
+    /// Root of the other hash data and the paths under it left out of the report
+    pub other_exclude: Option<(PathBuf, PathFilter)>,
+    /// Names for the sources in every output format
+    pub labels: SourceLabels,
+    /// Only groups with a copy under one of these paths
+    pub within: Vec<PathBuf>,
+    /// Only groups with a copy outside all of these paths
+    pub not_within: Vec<PathBuf>,
+}
+
+impl ReportOptions {
+    /// Whether an entry is left out of the groups the report shows
+    pub fn excludes(&self, source: Source, file_name: &str) -> bool {
+        let (root, filter) = or_else!(&self.other_exclude, none => return false);
+
+        return source == Source::Other && filter.excludes_file(root, Path::new(file_name));
+    }
+
+    /// Whether a file of the other hash data lies under the root `--other-exclude` matches from
+    pub fn is_under_other_root(&self, file_name: &str) -> bool {
+        return self
+            .other_exclude
+            .as_ref()
+            .is_some_and(|(root, _)| Path::new(file_name).starts_with(root));
+    }
+
+    /// Refuse an `--other-exclude` that can't match anything because no file of the other hash
+    /// data is under its root
+    pub fn check_other_root(&self, found_under_root: bool) -> Result<(), AppError> {
+        let (root, _) = or_else!(&self.other_exclude, none => return Ok(()));
+
+        if found_under_root {
+            return Ok(());
+        }
+
+        return Err(AppError::new(format!(
+            "No file in the other hash data is under {}, so --other-exclude can't match anything",
+            root.to_string_lossy()
+        )));
+    }
+
+    /// Whether a group found in the hash data is left in the report
+    pub fn shows(&self, hash: &str, files: &[&str]) -> bool {
+        let inside = |file: &str, paths: &[PathBuf]| {
+            paths.iter().any(|path| Path::new(file).starts_with(path))
+        };
+
+        return !self.ignore_list.accepts(hash, files)
+            && self
+                .since_last
+                .as_ref()
+                .is_none_or(|previous| previous.is_new(hash, files))
+            && (self.within.is_empty() || files.iter().any(|file| inside(file, &self.within)))
+            && (self.not_within.is_empty()
+                || files.iter().any(|file| !inside(file, &self.not_within)));
+    }
+}
+
+/// Files with the same content
+#[derive(Clone, Debug, Serialize)]
+pub struct DuplicateGroup {
+    pub hash: String,
+    /// Size of each copy
+    pub size: u64,
+    pub members: Vec<DuplicateMember>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct DuplicateMember {
+    pub source: Source,
+    pub file_name: String,
+}
+
+impl DuplicateGroup {
+    /// The hash data the copies were found in, in order
+    pub fn sources(&self) -> Vec<Source> {
+        let mut sources: Vec<Source> = self.members.iter().map(|member| member.source).collect();
+        sources.dedup();
+
+        return sources;
+    }
+
+    /// Space that removing all but one copy would free
+    pub fn waste(&self) -> u64 {
+        return self.size * (self.members.len() as u64).saturating_sub(1);
+    }
+}
+
+/// Print the duplicate groups the options leave in, recording every group found into `reported`
 pub fn duplicate_report(
-    data_file: Vec<FileEntry>,
-    other_data_file: Option<Vec<FileEntry>>,
-    minimum: Option<ByteSize>,
+    data_file: &[FileEntry],
+    other_data_file: Option<&[FileEntry]>,
+    options: &ReportOptions,
+    reported: &mut ReportedGroups,
 ) {
-    let mut hash_index: HashMap<String, Vec<FileEntry>> = HashMap::with_capacity(data_file.len());
+    let minimum: u64 = options.minimum.unwrap_or(ByteSize(1)).into();
 
-    for mut file in data_file {
-        let hash = take(&mut file.hash);
+    let mut output = ReportOutput::new(options, other_data_file.is_some());
 
-        let hash_group = hash_index.entry(hash).or_default();
+    for group in find_duplicate_groups(data_file, other_data_file) {
+        let files: Vec<&str> = group
+            .members
+            .iter()
+            .map(|member| member.file_name.as_str())
+            .collect();
 
-        hash_group.push(file);
-    }
+        reported.record(&group.hash, &files);
 
-    if let Some(other_data_file) = other_data_file {
-        for mut file in other_data_file {
-            let hash = take(&mut file.hash);
+        // Left out only once the group is recorded, like small files, so `--since-last` sees the
+        // same groups whatever is excluded
+        let members: Vec<(Source, &str)> = group
+            .members
+            .iter()
+            .map(|member| (member.source, member.file_name.as_str()))
+            .filter(|(source, file_name)| !options.excludes(*source, file_name))
+            .collect();
 
-            let hash_group = hash_index.entry(hash).or_default();
+        let files: Vec<&str> = members.iter().map(|(_, file_name)| *file_name).collect();
 
-            hash_group.push(file);
+        if members.len() < 2 || group.size < minimum || !options.shows(&group.hash, &files) {
+            continue;
         }
+
+        output.add_group(group.size, members.into_iter());
     }
 
-    let mut hash_list: Vec<Vec<FileEntry>> = hash_index
-        .into_values()
-        .filter(|hash| hash.len() > 1)
+    output.finish();
+}
+
+/// Group the entries of one or two sets of hash data by content, largest first
+pub fn find_duplicate_groups(
+    data_file: &[FileEntry],
+    other_data_file: Option<&[FileEntry]>,
+) -> Vec<DuplicateGroup> {
+    // Grouped by sorting references to the entries, so memory grows by two words per file
+    // instead of a second copy of every entry
+    let mut entries: Vec<(Source, &FileEntry)> = data_file
+        .iter()
+        .map(|file| (Source::Base, file))
+        .chain(
+            other_data_file
+                .unwrap_or_default()
+                .iter()
+                .map(|file| (Source::Other, file)),
+        )
         .collect();
 
-    hash_list.sort_unstable_by_key(|entry| {
-        Reverse(entry.first().map(|file| file.file_size).unwrap_or_default())
+    entries.sort_unstable_by(|(a_source, a), (b_source, b)| {
+        (&a.hash, a_source, &a.file_name).cmp(&(&b.hash, b_source, &b.file_name))
     });
 
-    for hash_group in hash_list {
-        let size = hash_group
+    let mut hash_list: Vec<&[(Source, &FileEntry)]> = entries
+        .chunk_by(|(_, a), (_, b)| a.hash == b.hash)
+        .filter(|hash_group| hash_group.len() >= 2)
+        .collect();
+
+    hash_list.sort_unstable_by_key(|hash_group| {
+        hash_group
             .first()
-            .map(|file| file.file_size)
-            .unwrap_or_default();
+            .map(|(_, file)| (Reverse(file.content_size()), &file.file_name))
+    });
 
-        if size < minimum.unwrap_or(ByteSize::Byte(1)).into() {
-            continue;
+    return hash_list
+        .into_iter()
+        .filter_map(|hash_group| {
+            let (_, first) = hash_group.first()?;
+
+            return Some(DuplicateGroup {
+                hash: first.hash.clone(),
+                size: first.content_size(),
+                members: hash_group
+                    .iter()
+                    .map(|(source, file)| DuplicateMember {
+                        source: *source,
+                        file_name: file.file_name.clone(),
+                    })
+                    .collect(),
+            });
+        })
+        .collect();
+}
+
+/// Destination for duplicate groups, printed as they arrive or collected for a summary
+pub enum ReportOutput {
+    Groups(ReportStyle, Option<Pager>),
+    DirectoryPairs(ReportStyle, DirectoryPairs),
+    ExtensionSummary(ReportStyle, ExtensionSummary),
+    Html(HtmlReport),
+    Template(TemplateReport),
+}
+
+impl ReportOutput {
+    pub fn new(options: &ReportOptions, with_other: bool) -> Self {
+        let style = ReportStyle::new(with_other, options.size_format, options.labels.clone());
+
+        if let Some(template) = &options.template {
+            return ReportOutput::Template(TemplateReport::new(
+                template.clone(),
+                options.size_format,
+                options.labels.clone(),
+            ));
         }
 
-        let (size, unit) = format_file_size(size);
+        if options.format == ReportFormat::Html {
+            return ReportOutput::Html(HtmlReport::new(
+                options.size_format,
+                options.labels.clone(),
+            ));
+        }
+
+        if options.by_directory {
+            return ReportOutput::DirectoryPairs(style, DirectoryPairs::default());
+        }
+
+        if options.by_extension {
+            return ReportOutput::ExtensionSummary(style, ExtensionSummary::default());
+        }
+
+        print_report_header(&style);
+
+        let pager = if options.pager {
+            Pager::new(options.page_size)
+        } else {
+            None
+        };
+
+        return ReportOutput::Groups(style, pager);
+    }
+
+    pub fn add_group<'a>(
+        &mut self,
+        file_size: u64,
+        files: impl ExactSizeIterator<Item = (Source, &'a str)>,
+    ) {
+        match self {
+            ReportOutput::Groups(style, pager) => {
+                // A blank line and one line per copy
+                if pager
+                    .as_mut()
+                    .is_none_or(|pager| pager.next_group(files.len() + 1))
+                {
+                    print_duplicate_group(style, file_size, files)
+                }
+            }
+            ReportOutput::DirectoryPairs(_, directory_pairs) => {
+                directory_pairs.add_group(file_size, files)
+            }
+            ReportOutput::ExtensionSummary(_, extension_summary) => {
+                extension_summary.add_group(file_size, files)
+            }
+            ReportOutput::Html(html_report) => html_report.add_group(file_size, files),
+            ReportOutput::Template(template_report) => template_report.add_group(file_size, files),
+        }
+    }
 
-        println!();
-        println!("{} files {}{} each", hash_group.len(), size, unit);
-        for file in hash_group {
-            println!("{}", file.file_name);
+    pub fn finish(self) {
+        match self {
+            ReportOutput::Groups(..) => {}
+            ReportOutput::DirectoryPairs(style, directory_pairs) => directory_pairs.print(&style),
+            ReportOutput::ExtensionSummary(style, extension_summary) => {
+                extension_summary.print(&style)
+            }
+            ReportOutput::Html(html_report) => html_report.print(),
+            ReportOutput::Template(template_report) => {
+                if let Err(err) = template_report.print() {
+                    println!("{err}");
+                }
+            }
         }
     }
 }
 
-#[allow(clippy::match_overlapping_arm)]
-fn format_file_size(size: u64) -> (u64, &'static str) {
-    match size {
-        ..1_000 => (size, "B"),
-        ..1_000_000 => (size / 1_000, "KB"),
-        ..1_000_000_000 => (size / 1_000_000, "MB"),
-        ..1_000_000_000_000 => (size / 1_000_000_000, "GB"),
-        _ => (size / 1_000_000_000_000, "TB"),
+pub fn print_report_header(style: &ReportStyle) {
+    let size_width = style.size_format.width();
+    let source_width = style.source_width;
+
+    println!(
+        "{}",
+        style.bold(format!(
+            "{:>size_width$} {:>COPIES_WIDTH$}  {:<source_width$}  Path",
+            "Size", "Copies", "Source"
+        ))
+    );
+}
+
+pub fn print_duplicate_group<'a>(
+    style: &ReportStyle,
+    file_size: u64,
+    files: impl ExactSizeIterator<Item = (Source, &'a str)>,
+) {
+    let size = style.size_format.format(file_size);
+
+    let size_color = size_color(file_size);
+
+    let copies = files.len();
+    let size_width = style.size_format.width();
+
+    println!();
+    for (index, (source, file_name)) in files.enumerate() {
+        let (size, copies) = if index == 0 {
+            (size.clone(), Count(copies).to_string())
+        } else {
+            (String::default(), String::default())
+        };
+
+        println!(
+            "{} {:>COPIES_WIDTH$}  {}  {}",
+            style.paint(format!("{size:>size_width$}"), size_color),
+            copies,
+            style.source_label(source),
+            file_name
+        );
     }
 }
+
+/// Color for a size column, brighter for larger sizes
+pub fn size_color(size: u64) -> Color {
+    return match size {
+        1_000_000_000.. => Color::Red,
+        1_000_000.. => Color::Yellow,
+        1_000.. => Color::Green,
+        _ => Color::DarkGrey,
+    };
+}
@@ -1,5 +1,6 @@
 use std::error::Error;
 use std::fmt::Display;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Default)]
 pub struct AbortError {
@@ -15,18 +16,42 @@ impl Display for AbortError {
 #[derive(Debug)]
 pub struct CaughtError {
     pub caller: String,
-    pub error: Box<dyn Error>,
+    pub error: Box<dyn Error + Send + Sync>,
+    /// File on disk the error is about
+    pub subject: Option<PathBuf>,
+    /// What was being done to the subject, e.g. "Reading metadata of"
+    pub context: Option<String>,
+}
+
+impl CaughtError {
+    /// The context and subject path, e.g. "Reading metadata of /data/file: ", when known
+    fn subject_prefix(&self) -> String {
+        return match (&self.context, &self.subject) {
+            (Some(context), Some(subject)) => {
+                format!("{context} {}: ", subject.to_string_lossy())
+            }
+            (None, Some(subject)) => format!("{}: ", subject.to_string_lossy()),
+            (Some(context), None) => format!("{context}: "),
+            (None, None) => String::default(),
+        };
+    }
 }
 
 impl Display for CaughtError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}\n{}", self.caller, self.error)
+        write!(
+            f,
+            "{}\n{}{}",
+            self.caller,
+            self.subject_prefix(),
+            self.error
+        )
     }
 }
 
 impl<T> From<Box<T>> for CaughtError
 where
-    T: Error + 'static,
+    T: Error + Send + Sync + 'static,
 {
     #[track_caller]
     fn from(error: Box<T>) -> Self {
@@ -35,17 +60,22 @@ where
         CaughtError {
             caller: format!("Error at {}:{}", loc.file(), loc.line()),
             error,
+            subject: None,
+            context: None,
         }
     }
 }
 
 pub trait AppErrorResult<T> {
     fn app_err(self) -> Result<T, AppError>;
+
+    /// Like `app_err`, also naming the file on disk and what was being done to it
+    fn app_err_at(self, context: &str, subject: &Path) -> Result<T, AppError>;
 }
 
 impl<T1, T2> AppErrorResult<T1> for Result<T1, T2>
 where
-    T2: Error + 'static,
+    T2: Error + Send + Sync + 'static,
 {
     #[track_caller]
     fn app_err(self) -> Result<T1, AppError> {
@@ -55,6 +85,22 @@ where
             AppError::Caught(CaughtError {
                 caller: format!("Error at {}:{}", loc.file(), loc.line()),
                 error: Box::new(err),
+                subject: None,
+                context: None,
+            })
+        })
+    }
+
+    #[track_caller]
+    fn app_err_at(self, context: &str, subject: &Path) -> Result<T1, AppError> {
+        let loc = std::panic::Location::caller();
+
+        self.map_err(|err| {
+            AppError::Caught(CaughtError {
+                caller: format!("Error at {}:{}", loc.file(), loc.line()),
+                error: Box::new(err),
+                subject: Some(subject.to_path_buf()),
+                context: Some(context.into()),
             })
         })
     }
@@ -70,7 +116,13 @@ impl Display for AppError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             AppError::Abort(abort) => write!(f, "{}", abort.message),
-            AppError::Caught(caught) => write!(f, "{} {:?}", caught.caller, caught.error),
+            AppError::Caught(caught) => write!(
+                f,
+                "{} {}{:?}",
+                caught.caller,
+                caught.subject_prefix(),
+                caught.error
+            ),
         }
     }
 }
@@ -79,6 +131,18 @@ impl AppError {
     pub fn new(message: String) -> AppError {
         return AppError::Abort(AbortError { message });
     }
+
+    /// Name the file on disk a caught error is about, if it doesn't already name one
+    pub fn with_subject(self, context: &str, subject: &Path) -> AppError {
+        return match self {
+            AppError::Caught(mut caught) if caught.subject.is_none() => {
+                caught.subject = Some(subject.to_path_buf());
+                caught.context = Some(context.into());
+                AppError::Caught(caught)
+            }
+            err => err,
+        };
+    }
 }
 
 #[macro_export]
@@ -0,0 +1,270 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+#[cfg(feature = "parquet")]
+use std::sync::Arc;
+
+use clap::ValueEnum;
+#[cfg(feature = "parquet")]
+use parquet::{
+    basic::Compression,
+    data_type::{ByteArray, ByteArrayType, Int32Type, Int64Type},
+    file::properties::WriterProperties,
+    file::writer::{SerializedFileWriter, SerializedRowGroupWriter},
+    schema::parser::parse_message_type,
+};
+
+use crate::byte_size::Count;
+use crate::errors::{AppError, AppErrorResult};
+#[cfg(feature = "parquet")]
+use crate::hash_data::FileEntry;
+use crate::hash_data::for_each_hash_entry;
+use crate::or_else;
+use crate::query::Query;
+use crate::utils::{canonical_path, unix_now};
+
+#[cfg(feature = "parquet")]
+const ROW_GROUP_ENTRIES: usize = 1_000_000;
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum ExportFormat {
+    /// Every field, for analysis in other tools
+    Parquet,
+    /// CRC32 checksum list read by older verification tools
+    Sfv,
+}
+
+impl ExportFormat {
+    /// Format picked from the output file's extension, `.sfv` or otherwise Parquet
+    pub fn from_output(output: &Path) -> ExportFormat {
+        if output
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("sfv"))
+        {
+            return ExportFormat::Sfv;
+        }
+
+        return ExportFormat::Parquet;
+    }
+}
+
+/// Export the entries of hash data, only those matching `filter` when given
+pub fn export(
+    data_path: &Path,
+    output: &Path,
+    format: ExportFormat,
+    filter: Option<&Query>,
+) -> Result<(), AppError> {
+    return match format {
+        ExportFormat::Parquet => export_parquet(data_path, output, filter),
+        ExportFormat::Sfv => export_sfv(data_path, output, filter),
+    };
+}
+
+/// Write the CRC32 of each file to an `.sfv` file, with paths relative to the folder it's
+/// written in where they can be. Only entries scanned with `--also-hash crc32` have one.
+fn export_sfv(data_path: &Path, output: &Path, filter: Option<&Query>) -> Result<(), AppError> {
+    let output = canonical_path(output)?;
+    let sfv_dir = output.parent().unwrap_or(&output).to_path_buf();
+
+    let mut writer = BufWriter::new(File::create(&output).app_err_at("Creating", &output)?);
+
+    writeln!(writer, "; Generated by hashfolder").app_err_at("Writing", &output)?;
+
+    let mut exported = 0;
+    let mut missing = 0;
+
+    // Ages in the filter are measured from the start of the export
+    let now = unix_now();
+
+    for_each_hash_entry(data_path, |entry| {
+        if filter.is_some_and(|filter| !filter.matches_at(&entry, now)) {
+            return Ok(());
+        }
+
+        let crc32 = or_else!(entry.also_hashes.get("crc32"), none => {
+            missing += 1;
+            return Ok(());
+        });
+
+        let file_name = Path::new(&entry.file_name);
+        let file_name = file_name.strip_prefix(&sfv_dir).unwrap_or(file_name);
+
+        writeln!(
+            writer,
+            "{} {}",
+            file_name.to_string_lossy(),
+            crc32.to_uppercase()
+        )
+        .app_err_at("Writing", &output)?;
+        exported += 1;
+
+        return Ok(());
+    })?;
+
+    writer.flush().app_err_at("Writing", &output)?;
+
+    println!(
+        "Exported {} entries to {}",
+        Count(exported),
+        output.to_string_lossy()
+    );
+
+    if missing > 0 {
+        println!(
+            "{} entries have no CRC32, scan with --also-hash crc32 to add them",
+            Count(missing)
+        );
+    }
+
+    return Ok(());
+}
+
+#[cfg(feature = "parquet")]
+const PARQUET_SCHEMA: &str = "
+    message file_entry {
+        required binary file_name (STRING);
+        required int64 file_size (INTEGER(64, false));
+        required binary hash (STRING);
+        required int64 modified (INTEGER(64, false));
+        optional int32 modified_nanos (INTEGER(32, false));
+        required int64 ctime (INTEGER(64, false));
+        required int64 inode (INTEGER(64, false));
+        required int64 device (INTEGER(64, false));
+    }
+";
+
+/// Write hash data entries to a Parquet file, streaming them in row groups
+#[cfg(feature = "parquet")]
+fn export_parquet(data_path: &Path, output: &Path, filter: Option<&Query>) -> Result<(), AppError> {
+    let schema = Arc::new(parse_message_type(PARQUET_SCHEMA).app_err()?);
+
+    let properties = Arc::new(
+        WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build(),
+    );
+
+    let file = File::create(output).app_err()?;
+
+    let mut writer = SerializedFileWriter::new(file, schema, properties).app_err()?;
+
+    let mut rows: Vec<FileEntry> = Vec::with_capacity(ROW_GROUP_ENTRIES);
+    let mut exported = 0;
+
+    // Ages in the filter are measured from the start of the export
+    let now = unix_now();
+
+    for_each_hash_entry(data_path, |entry| {
+        if filter.is_some_and(|filter| !filter.matches_at(&entry, now)) {
+            return Ok(());
+        }
+
+        rows.push(entry);
+
+        if rows.len() >= ROW_GROUP_ENTRIES {
+            exported += rows.len();
+            write_row_group(writer.next_row_group().app_err()?, &mut rows)?;
+        }
+
+        return Ok(());
+    })?;
+
+    if !rows.is_empty() {
+        exported += rows.len();
+        write_row_group(writer.next_row_group().app_err()?, &mut rows)?;
+    }
+
+    writer.close().app_err()?;
+
+    println!(
+        "Exported {} entries to {}",
+        Count(exported),
+        output.to_string_lossy()
+    );
+
+    return Ok(());
+}
+
+#[cfg(not(feature = "parquet"))]
+fn export_parquet(
+    _data_path: &Path,
+    _output: &Path,
+    _filter: Option<&Query>,
+) -> Result<(), AppError> {
+    return Err(AppError::new(
+        "Parquet export needs hashfolder built with the parquet feature".into(),
+    ));
+}
+
+#[cfg(feature = "parquet")]
+fn write_row_group(
+    mut row_group: SerializedRowGroupWriter<File>,
+    rows: &mut Vec<FileEntry>,
+) -> Result<(), AppError> {
+    let strings = |value: fn(&FileEntry) -> &str| -> Vec<ByteArray> {
+        rows.iter().map(|row| ByteArray::from(value(row))).collect()
+    };
+
+    // Unsigned values are stored in signed physical columns with an unsigned logical type
+    let integers = |value: fn(&FileEntry) -> u64| -> Vec<i64> {
+        rows.iter().map(|row| value(row) as i64).collect()
+    };
+
+    let file_names = strings(|row| &row.file_name);
+    let hashes = strings(|row| &row.hash);
+    let file_sizes = integers(|row| row.file_size);
+    let modified = integers(|row| row.modified);
+    let ctimes = integers(|row| row.ctime);
+    let inodes = integers(|row| row.inode);
+    let devices = integers(|row| row.device);
+
+    let modified_nanos: Vec<i32> = rows
+        .iter()
+        .filter_map(|row| row.modified_nanos)
+        .map(|nanos| nanos as i32)
+        .collect();
+    let modified_nanos_levels: Vec<i16> = rows
+        .iter()
+        .map(|row| i16::from(row.modified_nanos.is_some()))
+        .collect();
+
+    let mut index = 0;
+
+    while let Some(mut column) = row_group.next_column().app_err()? {
+        match index {
+            0 => column
+                .typed::<ByteArrayType>()
+                .write_batch(&file_names, None, None),
+            1 => column
+                .typed::<Int64Type>()
+                .write_batch(&file_sizes, None, None),
+            2 => column
+                .typed::<ByteArrayType>()
+                .write_batch(&hashes, None, None),
+            3 => column
+                .typed::<Int64Type>()
+                .write_batch(&modified, None, None),
+            4 => column.typed::<Int32Type>().write_batch(
+                &modified_nanos,
+                Some(&modified_nanos_levels),
+                None,
+            ),
+            5 => column.typed::<Int64Type>().write_batch(&ctimes, None, None),
+            6 => column.typed::<Int64Type>().write_batch(&inodes, None, None),
+            _ => column
+                .typed::<Int64Type>()
+                .write_batch(&devices, None, None),
+        }
+        .app_err()?;
+
+        column.close().app_err()?;
+        index += 1;
+    }
+
+    row_group.close().app_err()?;
+
+    rows.clear();
+
+    return Ok(());
+}
@@ -0,0 +1,83 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::byte_size::Count;
+use crate::duplicate_report::{ReportStyle, Source, size_color};
+
+const EXTENSION_WIDTH: usize = 12;
+const FILES_WIDTH: usize = 8;
+const SHARE_WIDTH: usize = 6;
+
+#[derive(Default)]
+struct ExtensionWaste {
+    files: u64,
+    wasted: u64,
+}
+
+/// Duplicate files and wasted bytes totalled per file extension
+#[derive(Default)]
+pub struct ExtensionSummary {
+    extensions: HashMap<String, ExtensionWaste>,
+}
+
+impl ExtensionSummary {
+    /// Every file in the group counts as a duplicate, all but the first count as waste
+    pub fn add_group<'a>(
+        &mut self,
+        file_size: u64,
+        files: impl Iterator<Item = (Source, &'a str)>,
+    ) {
+        for (index, (_, file_name)) in files.enumerate() {
+            let extension = Path::new(file_name)
+                .extension()
+                .map(|extension| extension.to_string_lossy().to_lowercase())
+                .unwrap_or_else(|| "(none)".into());
+
+            let waste = self.extensions.entry(extension).or_default();
+            waste.files += 1;
+
+            if index > 0 {
+                waste.wasted += file_size;
+            }
+        }
+    }
+
+    pub fn print(self, style: &ReportStyle) {
+        let total_wasted: u64 = self.extensions.values().map(|waste| waste.wasted).sum();
+
+        let mut extensions: Vec<(String, ExtensionWaste)> = self.extensions.into_iter().collect();
+
+        extensions.sort_unstable_by(|(a_extension, a), (b_extension, b)| {
+            (Reverse(a.wasted), a_extension).cmp(&(Reverse(b.wasted), b_extension))
+        });
+
+        let size_width = style.size_width();
+
+        println!(
+            "{}",
+            style.bold(format!(
+                "{:<EXTENSION_WIDTH$} {:>FILES_WIDTH$} {:>size_width$} {:>SHARE_WIDTH$}",
+                "Extension", "Files", "Wasted", "Share"
+            ))
+        );
+
+        for (extension, waste) in extensions {
+            let size = style.format_size(waste.wasted);
+
+            let share = if total_wasted > 0 {
+                waste.wasted as f64 * 100.0 / total_wasted as f64
+            } else {
+                0.0
+            };
+
+            println!(
+                "{:<EXTENSION_WIDTH$} {:>FILES_WIDTH$} {} {:>5.1}%",
+                extension,
+                Count(waste.files as usize),
+                style.paint(format!("{size:>size_width$}"), size_color(waste.wasted)),
+                share
+            );
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
+
+use indicatif::ProgressBar;
+
+use crate::errors::{AppError, AppErrorResult};
+use crate::hash_data::load_current_hash_data;
+use crate::hashing::HasherFactory;
+use crate::scan_folders::{hash_file, max_read_buffer_size, read_buffer_size};
+use crate::utils::canonical_path;
+
+/// Print a file's hash and the other paths with the same content in the hash data, without
+/// scanning, noting when the file's own entry has a different hash
+pub fn find_copies(
+    starting_dir: &Path,
+    file_path: &Path,
+    data_paths: &[PathBuf],
+) -> Result<(), AppError> {
+    let (file_path, hash) = hash_single_file(file_path)?;
+
+    println!("{hash} {}", file_path.to_string_lossy());
+
+    let file_name = file_path.to_string_lossy();
+
+    let mut found = 0;
+    let mut changed = false;
+
+    let data_paths = if data_paths.is_empty() {
+        &[starting_dir.to_owned()][..]
+    } else {
+        data_paths
+    };
+
+    for data_path in data_paths {
+        let data_file = load_current_hash_data(data_path, false)?;
+
+        for entry in data_file {
+            if entry.file_name == file_name {
+                changed |= entry.hash != hash;
+            } else if entry.hash == hash {
+                println!("{}", entry.file_name);
+                found += 1;
+            }
+        }
+    }
+
+    if changed {
+        println!("Changed since it was last hashed");
+    }
+
+    if found == 0 {
+        println!("No copies found");
+    } else {
+        println!("Found {found} copies");
+    }
+
+    return Ok(());
+}
+
+fn hash_single_file(file_path: &Path) -> Result<(PathBuf, String), AppError> {
+    let file_path = canonical_path(file_path)?;
+
+    if !file_path.is_file() {
+        return Err(AppError::new(format!(
+            "Expected {} to be a file",
+            file_path.to_string_lossy()
+        )));
+    }
+
+    let file = OpenOptions::new().read(true).open(&file_path).app_err()?;
+    let buffer_size = read_buffer_size(
+        file.metadata().app_err()?.len(),
+        max_read_buffer_size(&file_path),
+    );
+    let hash = hash_file(
+        file,
+        HasherFactory::default().create(),
+        buffer_size,
+        0,
+        &ProgressBar::hidden(),
+    )?
+    .hash;
+
+    return Ok((file_path, hash));
+}
@@ -0,0 +1,34 @@
+use std::path::Path;
+
+use crate::byte_size::{ByteSize, Count};
+use crate::errors::AppError;
+use crate::hash_data::{FileEntry, load_hash_data_with_info, save_hash_data};
+use crate::utils::canonical_path;
+
+pub fn forget(starting_dir: &Path, prefix: &Path) -> Result<(), AppError> {
+    let prefix = canonical_path(prefix)?;
+
+    let (data_file, info) = load_hash_data_with_info(starting_dir, true)?;
+
+    let (forgotten, kept): (Vec<FileEntry>, Vec<FileEntry>) = data_file
+        .into_iter()
+        .partition(|file| Path::new(&file.file_name).starts_with(&prefix));
+
+    if forgotten.is_empty() {
+        println!("No entries found under {}", prefix.to_string_lossy());
+        return Ok(());
+    }
+
+    save_hash_data(starting_dir, &info, &kept)?;
+
+    let forgotten_size: u64 = forgotten.iter().map(|file| file.file_size).sum();
+
+    println!(
+        "Forgot {} entries ({}) under {}",
+        Count(forgotten.len()),
+        ByteSize(forgotten_size),
+        prefix.to_string_lossy()
+    );
+
+    return Ok(());
+}
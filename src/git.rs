@@ -0,0 +1,76 @@
+use std::collections::BTreeMap;
+use std::fs::{File, create_dir_all};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::errors::{AppError, AppErrorResult};
+use crate::hash_data::state_directory;
+
+pub const GIT_DIRECTORY: &str = ".git";
+const GIT_HEADS_FILENAME: &str = "git_heads.json";
+
+/// Files tracked by the repository at `repository`, git checksums these already
+pub fn tracked_files(repository: &Path) -> io::Result<Vec<PathBuf>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repository)
+        .args(["ls-files", "-z"])
+        .output()?;
+
+    if !output.status.success() {
+        return Err(io::Error::other(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+
+    return Ok(output
+        .stdout
+        .split(|byte| *byte == 0)
+        .filter(|file| !file.is_empty())
+        .map(|file| {
+            let mut path = repository.to_path_buf();
+            path.extend(String::from_utf8_lossy(file).split('/'));
+            path
+        })
+        .collect());
+}
+
+/// Commit checked out in the repository, if it has one
+pub fn head_commit(repository: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repository)
+        .args(["rev-parse", "--verify", "--quiet", "HEAD"])
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    return Some(String::from_utf8_lossy(&output.stdout).trim().to_string());
+}
+
+/// Write the HEAD commit of each repository found by the scan to `.hashfolder/git_heads.json`
+pub fn save_git_heads(
+    starting_dir: &Path,
+    repositories: &[(PathBuf, Option<String>)],
+) -> Result<(), AppError> {
+    let heads: BTreeMap<String, Option<&str>> = repositories
+        .iter()
+        .map(|(path, head)| (path.to_string_lossy().to_string(), head.as_deref()))
+        .collect();
+
+    let state_directory = state_directory(starting_dir);
+
+    create_dir_all(&state_directory).app_err_at("Creating", &state_directory)?;
+
+    let heads_path = state_directory.join(GIT_HEADS_FILENAME);
+
+    let mut writer = BufWriter::new(File::create(&heads_path).app_err_at("Creating", &heads_path)?);
+    serde_json::to_writer_pretty(&mut writer, &heads).app_err_at("Writing", &heads_path)?;
+    writer.flush().app_err_at("Writing", &heads_path)?;
+
+    return Ok(());
+}
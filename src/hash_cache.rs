@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+use std::env::var_os;
+use std::fs::{File, OpenOptions, create_dir_all, rename};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{AppError, AppErrorResult};
+use crate::hash_data::FileEntry;
+use crate::or_else;
+
+const CACHE_FILENAME: &str = "hashes.json";
+const CACHE_LOCK_FILENAME: &str = "hashes.lock";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedHash {
+    device: u64,
+    inode: u64,
+    file_size: u64,
+    modified: u64,
+    modified_nanos: Option<u32>,
+    hash: String,
+}
+
+/// Machine-wide hashes keyed by device and inode, shared by every scan that opts in so the same
+/// file seen under different roots, such as through a bind mount, is only read once
+#[derive(Debug, Default)]
+pub struct HashCache {
+    entries: HashMap<(u64, u64), CachedHash>,
+}
+
+pub fn cache_directory() -> Option<PathBuf> {
+    #[cfg(windows)]
+    let cache_dir = PathBuf::from(var_os("LOCALAPPDATA")?);
+
+    #[cfg(not(windows))]
+    let cache_dir = match var_os("XDG_CACHE_HOME") {
+        Some(cache_home) => PathBuf::from(cache_home),
+        None => PathBuf::from(var_os("HOME")?).join(".cache"),
+    };
+
+    return Some(cache_dir.join("hashfolder"));
+}
+
+impl HashCache {
+    pub fn load() -> Result<HashCache, AppError> {
+        let cache_dir = or_else!(cache_directory(), none => return Ok(HashCache::default()));
+
+        return read_cache(&cache_dir);
+    }
+
+    /// Hash of the file with this identity, if it hasn't changed since it was cached
+    pub fn lookup(
+        &self,
+        device: u64,
+        inode: u64,
+        file_size: u64,
+        modified: u64,
+        modified_nanos: u32,
+    ) -> Option<&str> {
+        let cached = self.entries.get(&(device, inode))?;
+
+        if cached.file_size != file_size
+            || cached.modified != modified
+            || cached
+                .modified_nanos
+                .is_some_and(|nanos| nanos != modified_nanos)
+        {
+            return None;
+        }
+
+        return Some(&cached.hash);
+    }
+
+    /// Merge a tree's hash data into the cache file on disk, under a lock so concurrent scans
+    /// don't lose each other's entries
+    pub fn save_entries(hash_data: &[FileEntry]) -> Result<(), AppError> {
+        let cache_dir = or_else!(cache_directory(), none => {
+            return Err(AppError::new("No cache directory for the shared hash cache".into()));
+        });
+
+        create_dir_all(&cache_dir).app_err()?;
+
+        let lock_file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(cache_dir.join(CACHE_LOCK_FILENAME))
+            .app_err()?;
+
+        lock_file.lock().app_err()?;
+
+        let mut cache = read_cache(&cache_dir)?;
+
+        for entry in hash_data {
+            if entry.device == 0 || entry.inode == 0 || entry.hash.is_empty() {
+                continue;
+            }
+
+            // A Git LFS or annex pointer's hash is that of the file it refers to, which another
+            // scan finding the pointer itself through the cache would take for its content. Any
+            // cached before pointers were left out are dropped too.
+            if entry.pointer.is_some() {
+                cache.entries.remove(&(entry.device, entry.inode));
+                continue;
+            }
+
+            cache.entries.insert(
+                (entry.device, entry.inode),
+                CachedHash {
+                    device: entry.device,
+                    inode: entry.inode,
+                    file_size: entry.file_size,
+                    modified: entry.modified,
+                    modified_nanos: entry.modified_nanos,
+                    hash: entry.hash.clone(),
+                },
+            );
+        }
+
+        // Written beside the cache and renamed over it, so readers never see a partial file
+        let temp_path = cache_dir.join(format!("{CACHE_FILENAME}.tmp"));
+
+        let mut writer = BufWriter::new(File::create(&temp_path).app_err()?);
+        let cached: Vec<&CachedHash> = cache.entries.values().collect();
+        serde_json::to_writer(&mut writer, &cached).app_err()?;
+        writer.flush().app_err()?;
+        drop(writer);
+
+        rename(&temp_path, cache_dir.join(CACHE_FILENAME)).app_err()?;
+
+        return Ok(());
+    }
+}
+
+fn read_cache(cache_dir: &Path) -> Result<HashCache, AppError> {
+    let cache_path = cache_dir.join(CACHE_FILENAME);
+
+    if !cache_path.is_file() {
+        return Ok(HashCache::default());
+    }
+
+    let reader = BufReader::new(File::open(cache_path).app_err()?);
+
+    let cached: Vec<CachedHash> = serde_json::from_reader(reader).app_err()?;
+
+    return Ok(HashCache {
+        entries: cached
+            .into_iter()
+            .map(|cached| ((cached.device, cached.inode), cached))
+            .collect(),
+    });
+}
@@ -1,12 +1,25 @@
-use std::fs::{File, OpenOptions};
-use std::io::BufWriter;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{File, OpenOptions, TryLockError, create_dir_all, read_dir, rename};
+use std::io::{BufReader, BufWriter, ErrorKind, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use serde::{Deserialize, Serialize};
+use serde::de::{self, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
 
+use crate::byte_size::Count;
 use crate::errors::{AppError, AppErrorResult};
+use crate::or_else;
+use crate::pointer::Pointer;
 
+/// Directory at the root of a scanned tree holding the tool's state
+pub const STATE_DIRECTORY: &str = ".hashfolder";
 const HASH_DATA_FILENAME: &str = "hash.json";
+const SCAN_LOCK_FILENAME: &str = "scan.lock";
+
+static DATASET: OnceLock<String> = OnceLock::new();
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct FileEntry {
@@ -14,17 +27,223 @@ pub struct FileEntry {
     pub file_size: u64,
     pub hash: String,
     pub modified: u64,
+    #[serde(default)]
+    pub modified_nanos: Option<u32>,
+    #[serde(default)]
+    pub ctime: u64,
+    #[serde(default)]
+    pub inode: u64,
+    /// Device holding the inode, so moved files can be recognised by (device, inode)
+    #[serde(default)]
+    pub device: u64,
+    /// Digests from `--also-hash`, by algorithm
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub also_hashes: BTreeMap<String, String>,
+    /// Set for Git LFS pointers and git-annex links, whose hash is that of the file they refer to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pointer: Option<Pointer>,
+    /// Seconds since the Unix epoch when `verify` last found the file matching its hash
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_verified: Option<u64>,
+    /// Seconds since the Unix epoch when a scan last found the file, which stops for entries
+    /// kept while their files are missing, such as with `--preserve-missing`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_seen: Option<u64>,
+    #[serde(skip)]
+    pub seen: bool,
+}
+
+impl FileEntry {
+    /// Size of the content the hash is of, which for a pointer is the file it refers to
+    pub fn content_size(&self) -> u64 {
+        return self
+            .pointer
+            .as_ref()
+            .map_or(self.file_size, |pointer| pointer.size);
+    }
+}
+
+/// Where hash data was scanned, saved with it so a scan of another tree against the same data
+/// file is noticed
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanOrigin {
+    /// Canonical path of the scanned folder
+    pub root: String,
+    /// UUID of the file system the folder is on, where it has one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filesystem_uuid: Option<String>,
+}
+
+impl ScanOrigin {
+    pub fn of(starting_dir: &Path) -> Self {
+        return ScanOrigin {
+            root: starting_dir.to_string_lossy().into(),
+            filesystem_uuid: filesystem_uuid(starting_dir),
+        };
+    }
+
+    /// How the place hash data was scanned differs from where it's scanned now
+    pub fn differences(&self, current: &ScanOrigin) -> Vec<String> {
+        let mut differences = Vec::default();
+
+        if self.root != current.root {
+            differences.push(format!(
+                "The hash data was scanned from {}, not {}",
+                self.root, current.root
+            ));
+        }
+
+        if let (Some(saved), Some(current)) = (&self.filesystem_uuid, &current.filesystem_uuid)
+            && saved != current
+        {
+            differences.push(format!(
+                "The hash data was scanned on file system {saved}, this folder is on {current}"
+            ));
+        }
+
+        return differences;
+    }
+}
+
+/// Details saved in a data file ahead of the entries
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct DatasetInfo {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<ScanOrigin>,
+    /// Name shown for the hash data in reports comparing it with other hash data
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Seconds since the Unix epoch when the hash data was first saved
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<u64>,
+    /// Seconds since the Unix epoch when the hash data was last saved
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated: Option<u64>,
+}
+
+/// How a data file ends after its last entry
+const FILES_END: &[u8] = b"]}";
+
+/// Format version of the data file layout below. Files written before it had a version are
+/// just the list of entries, read as version 1.
+const HASH_DATA_VERSION: u64 = 2;
+
+/// Layout of a data file, the dataset details ahead of the entries. Files written by older
+/// versions are just the list of entries.
+#[derive(Serialize)]
+struct HashDataFile<'a> {
+    version: u64,
+    #[serde(flatten)]
+    info: &'a DatasetInfo,
+    files: &'a [FileEntry],
+}
+
+/// Use the named hash data under `.hashfolder/` instead of the default one, for every folder
+/// read or saved from now on
+pub fn select_dataset(name: &str) -> Result<(), AppError> {
+    let is_valid = !name.is_empty()
+        && name != "hash"
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    if !is_valid {
+        return Err(AppError::new(format!(
+            "Dataset name {name} must use only letters, digits, - and _"
+        )));
+    }
+
+    _ = DATASET.set(name.into());
+
+    return Ok(());
+}
+
+fn data_file_name() -> String {
+    return match DATASET.get() {
+        Some(name) => format!("{name}.json"),
+        None => HASH_DATA_FILENAME.into(),
+    };
 }
 
 pub fn load_current_hash_data(
     source_path: &Path,
     create: bool,
 ) -> Result<Vec<FileEntry>, AppError> {
+    return load_hash_data_with_info(source_path, create).map(|(hash_data, _)| hash_data);
+}
+
+/// Hash data along with the details saved with it, empty for data files of older versions
+pub fn load_hash_data_with_info(
+    source_path: &Path,
+    create: bool,
+) -> Result<(Vec<FileEntry>, DatasetInfo), AppError> {
+    let (mut hash_data, info) = read_hash_data_with_info(source_path, create)?;
+
+    // Strictly sorted means no path appears twice, which binary searches rely on
+    if !hash_data.is_sorted_by(|a, b| a.file_name < b.file_name) {
+        // Newest entry for each path first, so it's the one kept
+        hash_data.sort_by(|a, b| {
+            a.file_name
+                .cmp(&b.file_name)
+                .then(b.modified.cmp(&a.modified))
+                .then(b.modified_nanos.cmp(&a.modified_nanos))
+        });
+
+        let previous_len = hash_data.len();
+        hash_data.dedup_by(|entry, kept| entry.file_name == kept.file_name);
+
+        let collapsed = previous_len - hash_data.len();
+
+        if collapsed > 0 {
+            println!(
+                "Collapsed {} duplicate entries for the same path in the hash data of {}",
+                Count(collapsed),
+                source_path.to_string_lossy()
+            );
+        }
+    }
+
+    return Ok((hash_data, info));
+}
+
+/// Move the base path's data file aside so the next scan starts from scratch, returning where
+/// it was moved to
+pub fn back_up_hash_data(starting_dir: &Path) -> Result<Option<PathBuf>, AppError> {
+    let hash_data_file_path = get_hash_data_file_path(starting_dir, true)?;
+
+    if !hash_data_file_path.exists() {
+        return Ok(None);
+    }
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    let mut backup_path = hash_data_file_path.clone().into_os_string();
+    backup_path.push(format!(".{timestamp}.bak"));
+    let backup_path = PathBuf::from(backup_path);
+
+    rename(&hash_data_file_path, &backup_path).app_err_at("Moving", &hash_data_file_path)?;
+
+    return Ok(Some(backup_path));
+}
+
+pub fn read_hash_data(source_path: &Path, create: bool) -> Result<Vec<FileEntry>, AppError> {
+    return read_hash_data_with_info(source_path, create).map(|(hash_data, _)| hash_data);
+}
+
+pub fn read_hash_data_with_info(
+    source_path: &Path,
+    create: bool,
+) -> Result<(Vec<FileEntry>, DatasetInfo), AppError> {
     let hash_data_file_path = get_hash_data_file_path(source_path, create)?;
 
     if !hash_data_file_path.exists() {
         if create {
-            return Ok(Vec::default());
+            return Ok((Vec::default(), DatasetInfo::default()));
         } else {
             return Err(AppError::new("Comparison hash data file not found".into()));
         }
@@ -37,15 +256,206 @@ pub fn load_current_hash_data(
         )))?;
     }
 
-    let file = File::open(hash_data_file_path).app_err()?;
+    let mut hash_data: Vec<FileEntry> = Vec::default();
+
+    let info = read_hash_data_file(
+        &hash_data_file_path,
+        Some(|entry| {
+            hash_data.push(entry);
+            return Ok(());
+        }),
+    )?;
+
+    return Ok((hash_data, info));
+}
+
+/// Read hash data one entry at a time without holding the whole file in memory, returning the
+/// details saved with it from the same pass
+pub fn for_each_hash_entry<F>(source_path: &Path, f: F) -> Result<DatasetInfo, AppError>
+where
+    F: FnMut(FileEntry) -> Result<(), AppError>,
+{
+    let hash_data_file_path = get_hash_data_file_path(source_path, false)?;
+
+    return read_hash_data_file(&hash_data_file_path, Some(f));
+}
+
+/// The details saved with hash data, skipping over its entries
+pub fn read_dataset_info(source_path: &Path) -> Result<DatasetInfo, AppError> {
+    let hash_data_file_path = get_hash_data_file_path(source_path, false)?;
+
+    return read_hash_data_file::<fn(FileEntry) -> Result<(), AppError>>(
+        &hash_data_file_path,
+        None,
+    );
+}
+
+/// Pass each entry of a data file to `f`, or skip them without it, returning the details saved
+/// with them
+fn read_hash_data_file<F>(hash_data_file_path: &Path, f: Option<F>) -> Result<DatasetInfo, AppError>
+where
+    F: FnMut(FileEntry) -> Result<(), AppError>,
+{
+    let reader =
+        BufReader::new(File::open(hash_data_file_path).app_err_at("Opening", hash_data_file_path)?);
+
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+
+    return deserializer
+        .deserialize_any(HashDataVisitor { f })
+        .app_err_at("Reading hash data from", hash_data_file_path);
+}
+
+/// Reads a data file either as the entry list older versions wrote or as details and entries
+struct HashDataVisitor<F> {
+    f: Option<F>,
+}
+
+impl<'de, F> Visitor<'de> for HashDataVisitor<F>
+where
+    F: FnMut(FileEntry) -> Result<(), AppError>,
+{
+    type Value = DatasetInfo;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("hash data")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        match self.f {
+            Some(mut f) => visit_entries(seq, &mut f)?,
+            None => while seq.next_element::<IgnoredAny>()?.is_some() {},
+        }
+
+        return Ok(DatasetInfo::default());
+    }
+
+    fn visit_map<A>(mut self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut info = serde_json::Map::default();
+        let mut version = None;
+
+        while let Some(key) = map.next_key::<String>()? {
+            match (key.as_str(), &mut self.f) {
+                ("version", _) => version = Some(map.next_value::<u64>()?),
+                ("files", Some(f)) => {
+                    check_version(version)?;
+                    map.next_value_seed(EntriesSeed { f })?;
+                }
+                ("files", None) => _ = map.next_value::<IgnoredAny>()?,
+                _ => _ = info.insert(key, map.next_value()?),
+            }
+        }
+
+        check_version(version)?;
+
+        return serde_json::from_value(info.into()).map_err(de::Error::custom);
+    }
+}
+
+/// Refuse layouts this version can't be sure to read, rather than misreading their entries
+fn check_version<E: de::Error>(version: Option<u64>) -> Result<(), E> {
+    return match version {
+        Some(HASH_DATA_VERSION) => Ok(()),
+        Some(version) if version > HASH_DATA_VERSION => Err(E::custom(format!(
+            "format version {version} was written by a newer hashfolder, this one reads up to version {HASH_DATA_VERSION}"
+        ))),
+        Some(version) => Err(E::custom(format!(
+            "unknown format version {version}, expected {HASH_DATA_VERSION}"
+        ))),
+        None => Err(E::custom(format!(
+            "no format version, expected {HASH_DATA_VERSION}"
+        ))),
+    };
+}
+
+struct EntriesSeed<'a, F> {
+    f: &'a mut F,
+}
+
+impl<'de, F> DeserializeSeed<'de> for EntriesSeed<'_, F>
+where
+    F: FnMut(FileEntry) -> Result<(), AppError>,
+{
+    type Value = ();
+
+    fn deserialize<D>(self, deserializer: D) -> Result<(), D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        return deserializer.deserialize_seq(self);
+    }
+}
+
+impl<'de, F> Visitor<'de> for EntriesSeed<'_, F>
+where
+    F: FnMut(FileEntry) -> Result<(), AppError>,
+{
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a list of file entries")
+    }
 
-    let mut hash_data: Vec<FileEntry> = serde_json::from_reader(file).app_err()?;
+    fn visit_seq<A>(self, seq: A) -> Result<(), A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        return visit_entries(seq, self.f);
+    }
+}
 
-    if !hash_data.is_sorted_by_key(|entry| &entry.file_name) {
-        hash_data.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+fn visit_entries<'de, A, F>(mut seq: A, f: &mut F) -> Result<(), A::Error>
+where
+    A: SeqAccess<'de>,
+    F: FnMut(FileEntry) -> Result<(), AppError>,
+{
+    while let Some(entry) = seq.next_element::<FileEntry>()? {
+        f(entry).map_err(de::Error::custom)?;
     }
 
-    return Ok(hash_data);
+    return Ok(());
+}
+
+/// Whether a directory is the root of its own scans, with a state directory or hash data left
+/// there by older versions
+pub fn is_scan_root(directory: &Path) -> bool {
+    return state_directory(directory).is_dir() || directory.join(HASH_DATA_FILENAME).is_file();
+}
+
+/// Folders below `starting_dir` with hash data of their own, found without following symlinks
+pub fn find_nested_roots(starting_dir: &Path) -> Vec<PathBuf> {
+    let mut nested_roots: Vec<PathBuf> = Vec::default();
+    let mut pending_directories: Vec<PathBuf> = vec![starting_dir.into()];
+
+    while let Some(directory) = pending_directories.pop() {
+        let entries = or_else!(read_dir(&directory), _ => continue);
+
+        for entry in entries.flatten() {
+            if !entry.file_type().is_ok_and(|file_type| file_type.is_dir())
+                || entry.file_name() == STATE_DIRECTORY
+            {
+                continue;
+            }
+
+            let path = entry.path();
+
+            if is_scan_root(&path) {
+                nested_roots.push(path.clone());
+            }
+
+            pending_directories.push(path);
+        }
+    }
+
+    nested_roots.sort();
+
+    return nested_roots;
 }
 
 pub fn get_hash_data_file_path(source_path: &Path, create: bool) -> Result<PathBuf, AppError> {
@@ -54,9 +464,46 @@ pub fn get_hash_data_file_path(source_path: &Path, create: bool) -> Result<PathB
     }
 
     if source_path.is_dir() {
-        let data_file_path = source_path.join(HASH_DATA_FILENAME);
+        let data_file_path = state_directory(source_path).join(data_file_name());
 
-        if data_file_path.is_file() || create {
+        if data_file_path.is_file() {
+            return Ok(data_file_path);
+        }
+
+        if DATASET.get().is_some() {
+            if create {
+                return Ok(data_file_path);
+            } else {
+                return Err(AppError::new(format!(
+                    "{} not found",
+                    data_file_path.to_string_lossy()
+                )));
+            }
+        }
+
+        // Written by older versions straight into the root
+        let legacy_file_path = source_path.join(HASH_DATA_FILENAME);
+
+        if legacy_file_path.is_file() {
+            if !create {
+                return Ok(legacy_file_path);
+            }
+
+            let state_directory = state_directory(source_path);
+
+            create_dir_all(&state_directory).app_err_at("Creating", &state_directory)?;
+            rename(&legacy_file_path, &data_file_path).app_err_at("Moving", &legacy_file_path)?;
+
+            println!(
+                "Moved {} to {}",
+                legacy_file_path.to_string_lossy(),
+                data_file_path.to_string_lossy()
+            );
+
+            return Ok(data_file_path);
+        }
+
+        if create {
             return Ok(data_file_path);
         } else {
             return Err(AppError::new(
@@ -71,19 +518,180 @@ pub fn get_hash_data_file_path(source_path: &Path, create: bool) -> Result<PathB
     )));
 }
 
-pub fn save_hash_data(starting_dir: &Path, data_file: &Vec<FileEntry>) -> Result<(), AppError> {
-    let hash_data_filename = starting_dir.join(HASH_DATA_FILENAME);
+pub fn save_hash_data(
+    starting_dir: &Path,
+    info: &DatasetInfo,
+    data_file: &Vec<FileEntry>,
+) -> Result<(), AppError> {
+    let hash_data_file = open_hash_data_for_saving(starting_dir)?;
+
+    let info = DatasetInfo {
+        origin: Some(ScanOrigin::of(starting_dir)),
+        ..info.clone()
+    };
+
+    return save_hash_data_to(&hash_data_file, &info, data_file);
+}
+
+/// Open the data file for writing without truncating it, so it can be saved later even after
+/// the process has lost permission to open it
+pub fn open_hash_data_for_saving(starting_dir: &Path) -> Result<File, AppError> {
+    let hash_data_filename = get_hash_data_file_path(starting_dir, true)?;
 
-    let hash_data_file = OpenOptions::new()
+    let state_directory = state_directory(starting_dir);
+
+    create_dir_all(&state_directory).app_err_at("Creating", &state_directory)?;
+
+    return OpenOptions::new()
         .write(true)
-        .truncate(true)
         .create(true)
-        .open(hash_data_filename)
-        .app_err()?;
+        .truncate(false)
+        .open(&hash_data_filename)
+        .app_err_at("Opening", &hash_data_filename);
+}
+
+/// Take the tree's scan lock, or None while another scan of the tree holds it
+pub fn try_lock_scan(starting_dir: &Path) -> Result<Option<File>, AppError> {
+    let state_directory = state_directory(starting_dir);
 
-    let writer = BufWriter::new(hash_data_file);
+    create_dir_all(&state_directory).app_err_at("Creating", &state_directory)?;
 
-    serde_json::to_writer(writer, &data_file).app_err()?;
+    let lock_path = state_directory.join(SCAN_LOCK_FILENAME);
+
+    let lock_file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&lock_path)
+        .app_err_at("Opening", &lock_path)?;
+
+    return match lock_file.try_lock() {
+        Ok(()) => Ok(Some(lock_file)),
+        Err(TryLockError::WouldBlock) => Ok(None),
+        // Some network file systems can't lock, scan unguarded rather than not at all
+        Err(TryLockError::Error(err)) if err.kind() == ErrorKind::Unsupported => {
+            Ok(Some(lock_file))
+        }
+        Err(TryLockError::Error(err)) => Err(err).app_err_at("Locking", &lock_path),
+    };
+}
+
+pub fn state_directory(starting_dir: &Path) -> PathBuf {
+    return starting_dir.join(STATE_DIRECTORY);
+}
+
+/// Write hash data with its details, stamping when it was created and updated
+pub fn save_hash_data_to(
+    hash_data_file: &File,
+    info: &DatasetInfo,
+    data_file: &Vec<FileEntry>,
+) -> Result<(), AppError> {
+    let mut writer = HashDataWriter::start(hash_data_file, info)?;
+
+    for entry in data_file {
+        writer.write(entry)?;
+    }
+
+    writer.finish()?;
 
     return Ok(());
 }
+
+/// Writes a data file one entry at a time, for hash data too large to hold in memory
+pub struct HashDataWriter<'a> {
+    writer: BufWriter<&'a File>,
+    files: usize,
+    bytes: u64,
+}
+
+impl<'a> HashDataWriter<'a> {
+    /// Replace the contents of the data file with its details, stamping when it was created and
+    /// updated, ready for the entries
+    pub fn start(hash_data_file: &'a File, info: &DatasetInfo) -> Result<Self, AppError> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let info = DatasetInfo {
+            created: info.created.or(Some(now)),
+            updated: Some(now),
+            ..info.clone()
+        };
+
+        hash_data_file.set_len(0).app_err()?;
+
+        let mut writer = BufWriter::new(hash_data_file);
+
+        writer.seek(SeekFrom::Start(0)).app_err()?;
+
+        // The layout with no entries, left open at the end of the list
+        let mut header = serde_json::to_vec(&HashDataFile {
+            version: HASH_DATA_VERSION,
+            info: &info,
+            files: &[],
+        })
+        .app_err()?;
+
+        header.truncate(header.len() - FILES_END.len());
+
+        writer.write_all(&header).app_err()?;
+
+        return Ok(HashDataWriter {
+            writer,
+            files: 0,
+            bytes: 0,
+        });
+    }
+
+    pub fn write(&mut self, entry: &FileEntry) -> Result<(), AppError> {
+        if self.files > 0 {
+            self.writer.write_all(b",").app_err()?;
+        }
+
+        serde_json::to_writer(&mut self.writer, entry).app_err()?;
+
+        self.files += 1;
+        self.bytes += entry.file_size;
+
+        return Ok(());
+    }
+
+    /// Entries written so far
+    pub fn files(&self) -> usize {
+        return self.files;
+    }
+
+    /// Close the data file, returning how many entries were written and their total size
+    pub fn finish(mut self) -> Result<(usize, u64), AppError> {
+        self.writer.write_all(FILES_END).app_err()?;
+        self.writer.flush().app_err()?;
+
+        return Ok((self.files, self.bytes));
+    }
+}
+
+/// UUID of the file system holding a path, from the `/dev/disk/by-uuid` link to its device
+#[cfg(target_os = "linux")]
+fn filesystem_uuid(path: &Path) -> Option<String> {
+    use std::os::unix::fs::MetadataExt;
+
+    let device = path.metadata().ok()?.dev();
+
+    for entry in read_dir("/dev/disk/by-uuid").ok()?.flatten() {
+        if entry
+            .path()
+            .metadata()
+            .is_ok_and(|metadata| metadata.rdev() == device)
+        {
+            return Some(entry.file_name().to_string_lossy().into());
+        }
+    }
+
+    return None;
+}
+
+#[cfg(not(target_os = "linux"))]
+fn filesystem_uuid(_path: &Path) -> Option<String> {
+    return None;
+}
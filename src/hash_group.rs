@@ -0,0 +1,89 @@
+use std::path::PathBuf;
+
+use crate::byte_size::{ByteSize, Count};
+use crate::errors::AppError;
+use crate::hash_data::{FileEntry, for_each_hash_entry};
+use crate::utils::format_timestamp;
+
+/// Hash data to look in, with the name shown for its files
+pub struct GroupSource {
+    /// Name given on the command line, ahead of the label saved with the hash data
+    pub label: Option<String>,
+    /// Name when neither is set
+    pub default_label: String,
+    pub data_path: PathBuf,
+}
+
+/// Print every file with the given content hash in each source's hash data, with its size and
+/// modified time
+pub fn hash_group(hash: &str, sources: &[GroupSource]) -> Result<(), AppError> {
+    let hash = hash.to_ascii_lowercase();
+
+    // Each source is read once, its saved label coming from the same pass as its matches
+    let mut source_matches: Vec<(String, Vec<FileEntry>)> = Vec::default();
+
+    for source in sources {
+        let mut matches = Vec::default();
+
+        let info = for_each_hash_entry(&source.data_path, |entry| {
+            if entry.hash == hash {
+                matches.push(entry);
+            }
+
+            return Ok(());
+        })?;
+
+        let label = source
+            .label
+            .clone()
+            .or(info.label)
+            .unwrap_or_else(|| source.default_label.clone());
+
+        source_matches.push((label, matches));
+    }
+
+    let label_width = source_matches
+        .iter()
+        .map(|(label, _)| label.len())
+        .max()
+        .unwrap_or_default();
+
+    let mut found = 0;
+    let mut found_sources = 0;
+    let mut total_size = 0;
+
+    println!("{hash}");
+
+    for (label, matches) in &source_matches {
+        if !matches.is_empty() {
+            found_sources += 1;
+        }
+
+        for entry in matches {
+            println!(
+                "  {:<label_width$}  {:>9}  {}  {}",
+                label,
+                ByteSize(entry.file_size).to_string(),
+                format_timestamp(entry.modified),
+                entry.file_name
+            );
+
+            found += 1;
+            total_size += entry.file_size;
+        }
+    }
+
+    if found == 0 {
+        println!("No files found with this hash");
+    } else {
+        println!(
+            "Found {} files ({}) in {} of {} sources",
+            Count(found),
+            ByteSize(total_size),
+            Count(found_sources),
+            Count(sources.len())
+        );
+    }
+
+    return Ok(());
+}
@@ -0,0 +1,48 @@
+use std::fs::{File, OpenOptions};
+use std::io::{LineWriter, Write};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::errors::{AppError, AppErrorResult};
+use crate::hash_data::FileEntry;
+use crate::utils::format_timestamp;
+
+/// Append-only record of every file hashed, one tab separated line per file with the time,
+/// path, size, hash and milliseconds spent reading it
+pub struct HashLog {
+    writer: LineWriter<File>,
+}
+
+impl HashLog {
+    pub fn open(log_path: &Path) -> Result<HashLog, AppError> {
+        let log_file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(log_path)
+            .app_err()?;
+
+        return Ok(HashLog {
+            writer: LineWriter::new(log_file),
+        });
+    }
+
+    pub fn record(&mut self, entry: &FileEntry, duration: Duration) -> Result<(), AppError> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        writeln!(
+            self.writer,
+            "{}\t{}\t{}\t{}\t{}",
+            format_timestamp(now),
+            entry.file_name,
+            entry.file_size,
+            entry.hash,
+            duration.as_millis()
+        )
+        .app_err()?;
+
+        return Ok(());
+    }
+}
@@ -0,0 +1,263 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+use md5::Md5;
+use sha2::{Digest, Sha256};
+
+use crate::errors::AppError;
+
+/// Algorithm of the hash every entry is stored and compared by
+pub const MAIN_HASH_ALGORITHM: &str = "sha256";
+
+/// Algorithms `--also-hash` can add alongside the main digest
+pub const ALSO_HASH_ALGORITHMS: [&str; 3] = ["md5", "blake3", "crc32"];
+
+/// Hex encoded digests of one file, the main one plus any extra algorithms by name
+#[derive(Debug, Default)]
+pub struct Digests {
+    pub hash: String,
+    pub also: BTreeMap<String, String>,
+}
+
+/// A digest fed a file's contents in chunks. Implementing it is all a new algorithm, or a mode
+/// such as hashing several digests at once, needs to be used by the scan.
+pub trait Hasher: Send {
+    fn update(&mut self, data: &[u8]);
+
+    /// Hex encoded digests of everything passed to `update`
+    fn finalize(self: Box<Self>) -> Digests;
+
+    /// Algorithm name, e.g. `sha256`
+    fn name(&self) -> &'static str;
+
+    /// Digest length in bytes
+    fn digest_len(&self) -> usize;
+}
+
+pub struct Sha256Hasher(Sha256);
+
+impl Hasher for Sha256Hasher {
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    fn finalize(self: Box<Self>) -> Digests {
+        return Digests {
+            hash: hex::encode(self.0.finalize()),
+            ..Digests::default()
+        };
+    }
+
+    fn name(&self) -> &'static str {
+        return MAIN_HASH_ALGORITHM;
+    }
+
+    fn digest_len(&self) -> usize {
+        return <Sha256 as Digest>::output_size();
+    }
+}
+
+pub struct Md5Hasher(Md5);
+
+impl Hasher for Md5Hasher {
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    fn finalize(self: Box<Self>) -> Digests {
+        return Digests {
+            hash: hex::encode(self.0.finalize()),
+            ..Digests::default()
+        };
+    }
+
+    fn name(&self) -> &'static str {
+        return "md5";
+    }
+
+    fn digest_len(&self) -> usize {
+        return <Md5 as Digest>::output_size();
+    }
+}
+
+pub struct Blake3Hasher(blake3::Hasher);
+
+impl Hasher for Blake3Hasher {
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    fn finalize(self: Box<Self>) -> Digests {
+        return Digests {
+            hash: self.0.finalize().to_hex().to_string(),
+            ..Digests::default()
+        };
+    }
+
+    fn name(&self) -> &'static str {
+        return "blake3";
+    }
+
+    fn digest_len(&self) -> usize {
+        return blake3::OUT_LEN;
+    }
+}
+
+/// CRC32 as used by `.sfv` files, only good for catching transfer errors
+pub struct Crc32Hasher(crc32fast::Hasher);
+
+impl Hasher for Crc32Hasher {
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    fn finalize(self: Box<Self>) -> Digests {
+        return Digests {
+            hash: format!("{:08x}", self.0.finalize()),
+            ..Digests::default()
+        };
+    }
+
+    fn name(&self) -> &'static str {
+        return "crc32";
+    }
+
+    fn digest_len(&self) -> usize {
+        return 4;
+    }
+}
+
+/// Feeds each read to the main hasher and every extra one, so several digests cost one pass
+/// over the file
+struct MultiHasher {
+    main: Box<dyn Hasher>,
+    also: Vec<Box<dyn Hasher>>,
+}
+
+impl Hasher for MultiHasher {
+    fn update(&mut self, data: &[u8]) {
+        self.main.update(data);
+
+        for hasher in &mut self.also {
+            hasher.update(data);
+        }
+    }
+
+    fn finalize(self: Box<Self>) -> Digests {
+        let mut digests = self.main.finalize();
+
+        for hasher in self.also {
+            let name = hasher.name();
+            digests.also.insert(name.into(), hasher.finalize().hash);
+        }
+
+        return digests;
+    }
+
+    fn name(&self) -> &'static str {
+        return self.main.name();
+    }
+
+    fn digest_len(&self) -> usize {
+        return self.main.digest_len();
+    }
+}
+
+fn create_by_name(name: &str) -> Option<Box<dyn Hasher>> {
+    return match name {
+        MAIN_HASH_ALGORITHM => Some(Box::new(Sha256Hasher(Sha256::default()))),
+        "md5" => Some(Box::new(Md5Hasher(Md5::default()))),
+        "blake3" => Some(Box::new(Blake3Hasher(blake3::Hasher::new()))),
+        "crc32" => Some(Box::new(Crc32Hasher(crc32fast::Hasher::new()))),
+        _ => None,
+    };
+}
+
+/// Makes a fresh hasher for each file, shared by the hash workers
+#[derive(Clone)]
+pub struct HasherFactory {
+    create: Arc<dyn Fn() -> Box<dyn Hasher> + Send + Sync>,
+    /// Extra algorithms each hasher also computes
+    also: Vec<String>,
+}
+
+impl HasherFactory {
+    pub fn new(create: impl Fn() -> Box<dyn Hasher> + Send + Sync + 'static) -> Self {
+        return HasherFactory {
+            create: Arc::new(create),
+            also: Vec::default(),
+        };
+    }
+
+    /// Factory for a built in algorithm
+    pub fn by_name(name: &str) -> Option<Self> {
+        create_by_name(name)?;
+
+        let name = name.to_string();
+
+        return Some(HasherFactory::new(move || {
+            create_by_name(&name).expect("algorithm checked when the factory was made")
+        }));
+    }
+
+    /// Factory for the main algorithm that also computes each of `algorithms` in the same pass
+    pub fn with_also(self, algorithms: &[String]) -> Result<Self, AppError> {
+        if algorithms.is_empty() {
+            return Ok(self);
+        }
+
+        for algorithm in algorithms {
+            if !ALSO_HASH_ALGORITHMS.contains(&algorithm.as_str()) {
+                return Err(AppError::new(format!(
+                    "Unknown hash algorithm {algorithm}, expected one of {}",
+                    ALSO_HASH_ALGORITHMS.join(", ")
+                )));
+            }
+        }
+
+        let main = self.create;
+        let also = algorithms.to_vec();
+        let names = also.clone();
+
+        return Ok(HasherFactory {
+            create: Arc::new(move || {
+                Box::new(MultiHasher {
+                    main: main(),
+                    also: names
+                        .iter()
+                        .filter_map(|name| create_by_name(name))
+                        .collect(),
+                })
+            }),
+            also,
+        });
+    }
+
+    pub fn create(&self) -> Box<dyn Hasher> {
+        return (self.create)();
+    }
+
+    /// Extra algorithms computed alongside the main digest
+    pub fn also(&self) -> &[String] {
+        return &self.also;
+    }
+}
+
+impl Default for HasherFactory {
+    fn default() -> Self {
+        return HasherFactory::new(|| Box::new(Sha256Hasher(Sha256::default())));
+    }
+}
+
+impl fmt::Debug for HasherFactory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "HasherFactory({}", self.create().name())?;
+
+        for algorithm in &self.also {
+            write!(f, "+{algorithm}")?;
+        }
+
+        write!(f, ")")
+    }
+}
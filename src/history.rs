@@ -0,0 +1,164 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write, stdout};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::byte_size::{ByteSize, Count};
+use crate::errors::{AppError, AppErrorResult};
+use crate::hash_data::state_directory;
+use crate::utils::{OutputFormat, format_timestamp};
+
+const HISTORY_FILENAME: &str = "history.jsonl";
+
+/// Runs compared against when flagging unusual change
+const TREND_WINDOW: usize = 10;
+/// A run is flagged when it changes this many times more files than the recent average
+const UNUSUAL_CHANGE_FACTOR: f64 = 3.0;
+/// Runs changing fewer files than this are never flagged
+const UNUSUAL_CHANGE_MINIMUM: usize = 10;
+
+/// One scan of the tree, appended to the history log as a JSON line
+#[derive(Serialize, Deserialize)]
+pub struct ScanRecord {
+    /// Start time in seconds since the Unix epoch
+    pub started: u64,
+    pub duration_secs: f64,
+    pub files: usize,
+    pub bytes: u64,
+    pub hashed_bytes: u64,
+    pub new_files: usize,
+    pub changed_files: usize,
+    pub removed_files: usize,
+    #[serde(default)]
+    pub moved_files: usize,
+    pub errors: usize,
+    pub completed: bool,
+}
+
+impl ScanRecord {
+    fn changes(&self) -> usize {
+        return self.new_files + self.changed_files + self.removed_files + self.moved_files;
+    }
+}
+
+#[derive(Serialize)]
+struct HistoryEntry<'a> {
+    #[serde(flatten)]
+    record: &'a ScanRecord,
+    unusual: bool,
+}
+
+pub fn append_scan_record(starting_dir: &Path, record: &ScanRecord) -> Result<(), AppError> {
+    let mut history_file = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(state_directory(starting_dir).join(HISTORY_FILENAME))
+        .app_err()?;
+
+    let mut line = serde_json::to_string(record).app_err()?;
+    line.push('\n');
+
+    history_file.write_all(line.as_bytes()).app_err()?;
+
+    return Ok(());
+}
+
+/// Show the most recent scans, flagging those that changed far more files than usual
+pub fn history_show(
+    starting_dir: &Path,
+    last: usize,
+    format: OutputFormat,
+) -> Result<(), AppError> {
+    let history_path = state_directory(starting_dir).join(HISTORY_FILENAME);
+
+    if !history_path.is_file() {
+        return Err(AppError::new(format!(
+            "No scan history for {}",
+            starting_dir.to_string_lossy()
+        )));
+    }
+
+    let mut records: Vec<ScanRecord> = Vec::default();
+
+    for line in BufReader::new(File::open(history_path).app_err()?).lines() {
+        records.push(serde_json::from_str(&line.app_err()?).app_err()?);
+    }
+
+    let entries: Vec<HistoryEntry> = records
+        .iter()
+        .enumerate()
+        .map(|(index, record)| HistoryEntry {
+            record,
+            unusual: is_unusual(&records[index.saturating_sub(TREND_WINDOW)..index], record),
+        })
+        .collect();
+
+    let shown = &entries[entries.len().saturating_sub(last)..];
+
+    match format {
+        OutputFormat::Text => print_history(shown),
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(stdout(), shown).app_err()?;
+            println!();
+        }
+    }
+
+    return Ok(());
+}
+
+fn is_unusual(previous: &[ScanRecord], record: &ScanRecord) -> bool {
+    let completed: Vec<&ScanRecord> = previous.iter().filter(|record| record.completed).collect();
+
+    if completed.is_empty() || record.changes() < UNUSUAL_CHANGE_MINIMUM {
+        return false;
+    }
+
+    let average = completed
+        .iter()
+        .map(|record| record.changes())
+        .sum::<usize>() as f64
+        / completed.len() as f64;
+
+    return record.changes() as f64 > average.max(1.0) * UNUSUAL_CHANGE_FACTOR;
+}
+
+fn print_history(entries: &[HistoryEntry]) {
+    println!(
+        "{:<19} {:>9} {:>9} {:>9} {:>9} {:>8} {:>8} {:>8} {:>8} {:>6}",
+        "Started",
+        "Duration",
+        "Files",
+        "Size",
+        "Hashed",
+        "New",
+        "Changed",
+        "Moved",
+        "Removed",
+        "Errors"
+    );
+
+    for entry in entries {
+        let record = entry.record;
+
+        println!(
+            "{:<19} {:>8.0}s {:>9} {:>9} {:>9} {:>8} {:>8} {:>8} {:>8} {:>6}{}{}",
+            format_timestamp(record.started),
+            record.duration_secs,
+            Count(record.files),
+            ByteSize(record.bytes),
+            ByteSize(record.hashed_bytes),
+            Count(record.new_files),
+            Count(record.changed_files),
+            Count(record.moved_files),
+            Count(record.removed_files),
+            Count(record.errors),
+            if record.completed { "" } else { "  stopped" },
+            if entry.unusual {
+                "  unusual change"
+            } else {
+                ""
+            },
+        );
+    }
+}
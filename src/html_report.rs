@@ -0,0 +1,56 @@
+use serde::Serialize;
+
+use crate::byte_size::SizeFormat;
+use crate::duplicate_report::{Source, SourceLabels};
+
+const HTML_TEMPLATE: &str = include_str!("report.html");
+const DATA_PLACEHOLDER: &str = "__REPORT_DATA__";
+
+#[derive(Serialize)]
+struct HtmlGroup {
+    size: u64,
+    label: String,
+    files: Vec<(String, String)>,
+}
+
+/// Standalone HTML page listing duplicate groups, sorted and filtered in the browser
+pub struct HtmlReport {
+    size_format: SizeFormat,
+    labels: SourceLabels,
+    groups: Vec<HtmlGroup>,
+}
+
+impl HtmlReport {
+    pub fn new(size_format: SizeFormat, labels: SourceLabels) -> Self {
+        return HtmlReport {
+            size_format,
+            labels,
+            groups: Vec::default(),
+        };
+    }
+
+    pub fn add_group<'a>(
+        &mut self,
+        file_size: u64,
+        files: impl Iterator<Item = (Source, &'a str)>,
+    ) {
+        self.groups.push(HtmlGroup {
+            size: file_size,
+            label: self.size_format.format(file_size),
+            files: files
+                .map(|(source, file_name)| {
+                    (self.labels.label(source).to_string(), file_name.to_string())
+                })
+                .collect(),
+        });
+    }
+
+    pub fn print(self) {
+        let data = serde_json::to_string(&self.groups).unwrap_or_else(|_| "[]".into());
+
+        // Keep file names from closing the script element the data is embedded in
+        let data = data.replace('<', "\\u003c");
+
+        print!("{}", HTML_TEMPLATE.replace(DATA_PLACEHOLDER, &data));
+    }
+}
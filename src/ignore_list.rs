@@ -0,0 +1,128 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fs::{File, create_dir_all};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{AppError, AppErrorResult};
+use crate::hash_data::{load_current_hash_data, state_directory};
+use crate::utils::canonical_path;
+
+const IGNORE_FILENAME: &str = "ignore.json";
+
+/// Duplicates that have been reviewed and kept, left out of later reports so only new ones show,
+/// like a lint baseline. Stored in `.hashfolder/ignore.json`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct IgnoreList {
+    /// Whole duplicate groups, by hash
+    #[serde(default)]
+    hashes: BTreeSet<String>,
+    /// Paths accepted as copies of each other, the smaller path first
+    #[serde(default)]
+    pairs: BTreeSet<(String, String)>,
+}
+
+impl IgnoreList {
+    pub fn load(starting_dir: &Path) -> Result<IgnoreList, AppError> {
+        let ignore_path = state_directory(starting_dir).join(IGNORE_FILENAME);
+
+        if !ignore_path.is_file() {
+            return Ok(IgnoreList::default());
+        }
+
+        let reader = BufReader::new(File::open(&ignore_path).app_err_at("Opening", &ignore_path)?);
+
+        return serde_json::from_reader(reader).app_err_at("Reading", &ignore_path);
+    }
+
+    fn save(&self, starting_dir: &Path) -> Result<(), AppError> {
+        let state_directory = state_directory(starting_dir);
+
+        create_dir_all(&state_directory).app_err_at("Creating", &state_directory)?;
+
+        let ignore_path = state_directory.join(IGNORE_FILENAME);
+
+        let mut writer =
+            BufWriter::new(File::create(&ignore_path).app_err_at("Creating", &ignore_path)?);
+        serde_json::to_writer_pretty(&mut writer, self).app_err_at("Writing", &ignore_path)?;
+        writer.flush().app_err_at("Writing", &ignore_path)?;
+
+        return Ok(());
+    }
+
+    /// Whether a duplicate group has been accepted, either by hash or because every pair of
+    /// files in it has. A new copy turning up makes the group show again.
+    pub fn accepts(&self, hash: &str, files: &[&str]) -> bool {
+        if self.hashes.contains(hash) {
+            return true;
+        }
+
+        if self.pairs.is_empty() {
+            return false;
+        }
+
+        return files.iter().enumerate().all(|(index, first)| {
+            files[index + 1..]
+                .iter()
+                .all(|second| self.pairs.contains(&ordered_pair(first, second)))
+        });
+    }
+}
+
+fn ordered_pair(first: &str, second: &str) -> (String, String) {
+    if first <= second {
+        return (first.into(), second.into());
+    }
+
+    return (second.into(), first.into());
+}
+
+/// Add duplicate groups or path pairs to the base path's ignore list
+pub fn ignore_duplicates(
+    starting_dir: &Path,
+    hashes: &[String],
+    pairs: &[PathBuf],
+    all: bool,
+) -> Result<(), AppError> {
+    let mut ignore_list = IgnoreList::load(starting_dir)?;
+
+    let previous_hashes = ignore_list.hashes.len();
+    let previous_pairs = ignore_list.pairs.len();
+
+    ignore_list.hashes.extend(hashes.iter().cloned());
+
+    for pair in pairs.chunks(2) {
+        if let [first, second] = pair {
+            ignore_list.pairs.insert(ordered_pair(
+                &canonical_path(first)?.to_string_lossy(),
+                &canonical_path(second)?.to_string_lossy(),
+            ));
+        }
+    }
+
+    if all {
+        let mut copies: HashMap<String, usize> = HashMap::default();
+
+        for file in load_current_hash_data(starting_dir, false)? {
+            *copies.entry(file.hash).or_default() += 1;
+        }
+
+        ignore_list.hashes.extend(
+            copies
+                .into_iter()
+                .filter(|(_, copies)| *copies > 1)
+                .map(|(hash, _)| hash),
+        );
+    }
+
+    ignore_list.save(starting_dir)?;
+
+    println!(
+        "Ignoring {} more duplicate groups and {} more path pairs",
+        ignore_list.hashes.len() - previous_hashes,
+        ignore_list.pairs.len() - previous_pairs
+    );
+
+    return Ok(());
+}
@@ -0,0 +1,119 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::fs::TryLockError;
+use std::io::ErrorKind;
+
+/// Finds files another process is writing to, so their half-written content isn't hashed
+pub struct InUseDetector {
+    open_for_writing: HashSet<(u64, u64)>,
+}
+
+impl Default for InUseDetector {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+impl InUseDetector {
+    /// Snapshot of files open for writing when the scan starts, files opened later are only
+    /// caught by the lock check
+    pub fn new() -> Self {
+        return InUseDetector {
+            open_for_writing: files_open_for_writing(),
+        };
+    }
+
+    pub fn is_in_use(&self, file: &File) -> bool {
+        if let Some(identity) = file_identity(file)
+            && self.open_for_writing.contains(&identity)
+        {
+            return true;
+        }
+
+        // A shared lock fails while another process holds an exclusive flock
+        return match file.try_lock_shared() {
+            Ok(()) => {
+                _ = file.unlock();
+                false
+            }
+            Err(TryLockError::WouldBlock) => true,
+            Err(TryLockError::Error(err)) => err.kind() == ErrorKind::WouldBlock,
+        };
+    }
+}
+
+#[cfg(unix)]
+fn file_identity(file: &File) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata = file.metadata().ok()?;
+
+    return Some((metadata.dev(), metadata.ino()));
+}
+
+#[cfg(not(unix))]
+fn file_identity(_file: &File) -> Option<(u64, u64)> {
+    return None;
+}
+
+/// Device and inode of every file some process has open with write access, from /proc
+#[cfg(target_os = "linux")]
+fn files_open_for_writing() -> HashSet<(u64, u64)> {
+    use std::fs::{read_dir, read_to_string};
+    use std::os::unix::fs::MetadataExt;
+    use std::process;
+
+    let mut open_for_writing = HashSet::new();
+
+    let own_pid = process::id().to_string();
+
+    let processes = match read_dir("/proc") {
+        Ok(processes) => processes,
+        Err(_) => return open_for_writing,
+    };
+
+    for process in processes.flatten() {
+        let pid = process.file_name();
+
+        if pid == own_pid.as_str() || !pid.to_string_lossy().bytes().all(|b| b.is_ascii_digit()) {
+            continue;
+        }
+
+        let descriptors = match read_dir(process.path().join("fd")) {
+            Ok(descriptors) => descriptors,
+            Err(_) => continue,
+        };
+
+        for descriptor in descriptors.flatten() {
+            let fd_info = process.path().join("fdinfo").join(descriptor.file_name());
+
+            let flags = read_to_string(fd_info)
+                .ok()
+                .and_then(|fd_info| {
+                    fd_info
+                        .lines()
+                        .find_map(|line| line.strip_prefix("flags:"))
+                        .and_then(|flags| u32::from_str_radix(flags.trim(), 8).ok())
+                })
+                .unwrap_or_default();
+
+            // O_WRONLY or O_RDWR
+            if flags & 0b11 == 0 {
+                continue;
+            }
+
+            if let Ok(metadata) = descriptor.path().metadata()
+                && metadata.is_file()
+            {
+                open_for_writing.insert((metadata.dev(), metadata.ino()));
+            }
+        }
+    }
+
+    return open_for_writing;
+}
+
+#[cfg(not(target_os = "linux"))]
+fn files_open_for_writing() -> HashSet<(u64, u64)> {
+    return HashSet::new();
+}
@@ -0,0 +1,62 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fs::{File, create_dir_all};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{AppError, AppErrorResult};
+use crate::hash_data::state_directory;
+use crate::or_else;
+
+const LAST_REPORT_FILENAME: &str = "last_report.json";
+
+/// Duplicate groups found by the previous report, kept in `.hashfolder/last_report.json` so the
+/// next one can show only what has appeared since
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ReportedGroups {
+    groups: HashMap<String, BTreeSet<String>>,
+}
+
+impl ReportedGroups {
+    pub fn load(starting_dir: &Path) -> Result<ReportedGroups, AppError> {
+        let report_path = state_directory(starting_dir).join(LAST_REPORT_FILENAME);
+
+        if !report_path.is_file() {
+            return Ok(ReportedGroups::default());
+        }
+
+        let reader = BufReader::new(File::open(&report_path).app_err_at("Opening", &report_path)?);
+
+        return serde_json::from_reader(reader).app_err_at("Reading", &report_path);
+    }
+
+    pub fn save(&self, starting_dir: &Path) -> Result<(), AppError> {
+        let state_directory = state_directory(starting_dir);
+
+        create_dir_all(&state_directory).app_err_at("Creating", &state_directory)?;
+
+        let report_path = state_directory.join(LAST_REPORT_FILENAME);
+
+        let mut writer =
+            BufWriter::new(File::create(&report_path).app_err_at("Creating", &report_path)?);
+        serde_json::to_writer(&mut writer, self).app_err_at("Writing", &report_path)?;
+        writer.flush().app_err_at("Writing", &report_path)?;
+
+        return Ok(());
+    }
+
+    pub fn record(&mut self, hash: &str, files: &[&str]) {
+        self.groups.insert(
+            hash.into(),
+            files.iter().map(|file| file.to_string()).collect(),
+        );
+    }
+
+    /// Whether a group wasn't reported last time or has gained a copy since
+    pub fn is_new(&self, hash: &str, files: &[&str]) -> bool {
+        let previous = or_else!(self.groups.get(hash), none => return true);
+
+        return files.iter().any(|file| !previous.contains(*file));
+    }
+}
@@ -0,0 +1,65 @@
+//! Incremental folder hashing and duplicate finding, used by the `hashfolder` command line
+//! and available to other tools that want the same scanning and grouping. The hash data, the
+//! hashers, the scan and the duplicate grouping are public, with the types their options and
+//! entries use re-exported here, everything else belongs to the command line.
+
+mod bench;
+mod byte_size;
+#[doc(hidden)]
+pub mod cli;
+mod compare;
+mod config;
+mod dataset_info;
+mod directory_pairs;
+mod doctor;
+mod duplicate_report;
+mod errors;
+mod export;
+mod extension_summary;
+mod find_copies;
+mod forget;
+mod git;
+mod hash_cache;
+pub mod hash_data;
+mod hash_group;
+mod hash_log;
+pub mod hashing;
+mod history;
+mod html_report;
+mod ignore_list;
+mod in_use;
+mod last_report;
+mod listing;
+mod low_memory;
+mod notify;
+mod pager;
+mod parity;
+mod path_filter;
+mod pointer;
+mod power;
+mod privileges;
+mod progress;
+mod prune;
+mod query;
+mod sandbox;
+pub mod scan_folders;
+mod stats;
+mod status;
+mod template_report;
+mod torrent;
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+mod uring;
+mod utils;
+mod verify;
+#[cfg(windows)]
+mod vss;
+
+pub use byte_size::ByteSize;
+pub use duplicate_report::{DuplicateGroup, DuplicateMember, Source, find_duplicate_groups};
+pub use errors::{AbortError, AppError, CaughtError};
+pub use hash_cache::HashCache;
+/// Progress of `scan_folders::hash_file`, from the indicatif version this crate is built with
+pub use indicatif::ProgressBar;
+pub use path_filter::PathFilter;
+pub use pointer::{Pointer, PointerKind};
+pub use utils::{AbortKey, parse_abort_key};
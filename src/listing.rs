@@ -0,0 +1,266 @@
+use std::collections::HashSet;
+use std::fs::{File, read_to_string};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::byte_size::{ByteSize, Count};
+use crate::errors::{AppError, AppErrorResult};
+use crate::hash_data::{FileEntry, load_current_hash_data};
+
+/// A file from a directory listing, known only by path, size and maybe modified time
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ListingEntry {
+    pub path: String,
+    pub size: u64,
+    /// Seconds since the Unix epoch, when the listing has it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<u64>,
+}
+
+/// Read a listing saved by `import-listing`, or parse one from `find` or `ls`
+pub fn load_listing(listing_path: &Path) -> Result<Vec<ListingEntry>, AppError> {
+    let text = read_to_string(listing_path).app_err_at("Reading", listing_path)?;
+
+    if let Ok(entries) = serde_json::from_str(&text) {
+        return Ok(entries);
+    }
+
+    let entries = parse_listing(&text);
+
+    if entries.is_empty() {
+        return Err(AppError::new(format!(
+            "No files found in {}, expected `find -printf '%s %T@ %p\\n'` or `ls -lR` output",
+            listing_path.to_string_lossy()
+        )));
+    }
+
+    return Ok(entries);
+}
+
+/// Convert a listing into the form `compare --listing` reads, so a dump only has to be parsed
+/// once
+pub fn import_listing(listing_path: &Path, output: &Path) -> Result<(), AppError> {
+    let entries = load_listing(listing_path)?;
+
+    let mut writer = BufWriter::new(File::create(output).app_err_at("Creating", output)?);
+    serde_json::to_writer(&mut writer, &entries).app_err_at("Writing", output)?;
+    writer.flush().app_err_at("Writing", output)?;
+
+    let with_modified = entries
+        .iter()
+        .filter(|entry| entry.modified.is_some())
+        .count();
+
+    println!(
+        "Imported {} files ({}), {} with modified times",
+        Count(entries.len()),
+        ByteSize(entries.iter().map(|entry| entry.size).sum()),
+        Count(with_modified)
+    );
+
+    return Ok(());
+}
+
+/// Lines of `size modified path` or `size path` as written by `find -printf`, or `ls -lR` output
+/// whose directory headers end in `:`. Times in `ls` output lack the year or seconds, so they
+/// aren't used.
+fn parse_listing(text: &str) -> Vec<ListingEntry> {
+    let mut entries = Vec::default();
+    let mut directory: Option<&str> = None;
+
+    for line in text.lines() {
+        if line.trim().is_empty() || line.starts_with("total ") {
+            continue;
+        }
+
+        if let Some(entry) = parse_ls_line(line, directory) {
+            entries.push(entry);
+            continue;
+        }
+
+        if let Some(header) = line.strip_suffix(':')
+            && !line.starts_with(|c: char| c.is_ascii_digit())
+        {
+            directory = Some(header);
+            continue;
+        }
+
+        if let Some(entry) = parse_find_line(line) {
+            entries.push(entry);
+        }
+    }
+
+    return entries;
+}
+
+/// `-rw-r--r-- 1 user group 1234 Jan  1 12:00 name`, regular files only
+fn parse_ls_line(line: &str, directory: Option<&str>) -> Option<ListingEntry> {
+    let mut rest = line;
+    let mut fields = Vec::with_capacity(8);
+
+    for _ in 0..8 {
+        let (field, remainder) = rest.trim_start().split_once(char::is_whitespace)?;
+        fields.push(field);
+        rest = remainder;
+    }
+
+    if !fields[0].starts_with('-') || fields[0].len() < 10 {
+        return None;
+    }
+
+    let size = fields[4].parse().ok()?;
+    // Symlinks aren't listed, so the name runs to the end of the line
+    let name = rest;
+
+    let path = match directory {
+        Some(directory) => format!("{directory}/{name}"),
+        None => name.to_string(),
+    };
+
+    return Some(ListingEntry {
+        path,
+        size,
+        modified: None,
+    });
+}
+
+fn parse_find_line(line: &str) -> Option<ListingEntry> {
+    let (size, rest) = line.split_once(char::is_whitespace)?;
+    let size = size.parse().ok()?;
+
+    let modified = rest
+        .split_once(char::is_whitespace)
+        .and_then(|(time, path)| {
+            let seconds = time.split_once('.').map_or(time, |(seconds, _)| seconds);
+            return Some((seconds.parse::<u64>().ok()?, path));
+        });
+
+    let (modified, path) = match modified {
+        Some((modified, path)) => (Some(modified), path),
+        None => (None, rest),
+    };
+
+    return Some(ListingEntry {
+        path: path.to_string(),
+        size,
+        modified,
+    });
+}
+
+/// List files of the base with no file of the same size and modified time in the listing, and
+/// the other way round
+pub fn compare_listing_unique(starting_dir: &Path, listing_path: &Path) -> Result<(), AppError> {
+    let base = load_current_hash_data(starting_dir, false)?;
+    let listing = load_listing(listing_path)?;
+
+    print_match_note(&listing);
+
+    let listing_keys: HashSet<(u64, Option<u64>)> = listing.iter().map(key).collect();
+    let base_keys: HashSet<(u64, Option<u64>)> = base.iter().flat_map(entry_keys).collect();
+
+    print_unique(
+        "base",
+        base.iter()
+            .filter(|file| {
+                !entry_keys(file)
+                    .iter()
+                    .any(|key| listing_keys.contains(key))
+            })
+            .map(|file| (file.file_name.as_str(), file.file_size)),
+    );
+
+    print_unique(
+        "listing",
+        listing
+            .iter()
+            .filter(|entry| !base_keys.contains(&key(entry)))
+            .map(|entry| (entry.path.as_str(), entry.size)),
+    );
+
+    return Ok(());
+}
+
+/// Check every file in the listing has a file of the same size and modified time in the base
+pub fn compare_listing_subset_check(
+    starting_dir: &Path,
+    listing_path: &Path,
+) -> Result<(), AppError> {
+    let base = load_current_hash_data(starting_dir, false)?;
+    let listing = load_listing(listing_path)?;
+
+    print_match_note(&listing);
+
+    let base_keys: HashSet<(u64, Option<u64>)> = base.iter().flat_map(entry_keys).collect();
+
+    let missing = print_unique(
+        "listing",
+        listing
+            .iter()
+            .filter(|entry| !base_keys.contains(&key(entry)))
+            .map(|entry| (entry.path.as_str(), entry.size)),
+    );
+
+    if missing > 0 {
+        return Err(AppError::new(format!(
+            "{} of {} files in the listing have no match in base",
+            Count(missing),
+            Count(listing.len())
+        )));
+    }
+
+    println!(
+        "All {} files in the listing have a match in base",
+        Count(listing.len())
+    );
+
+    return Ok(());
+}
+
+fn print_match_note(listing: &[ListingEntry]) {
+    if listing.iter().all(|entry| entry.modified.is_some()) {
+        println!("Matched by size and modified time only, contents not verified");
+    } else {
+        println!(
+            "Matched by size, and modified time where the listing has it, contents not verified"
+        );
+    }
+
+    println!();
+}
+
+/// A listing entry matches base files of its size, and modified time if it has one
+fn key(entry: &ListingEntry) -> (u64, Option<u64>) {
+    return (entry.size, entry.modified);
+}
+
+/// Keys a base file matches, with and without its modified time
+fn entry_keys(file: &FileEntry) -> [(u64, Option<u64>); 2] {
+    return [
+        (file.file_size, Some(file.modified)),
+        (file.file_size, None),
+    ];
+}
+
+/// Print files with no match on the other side and return how many there were
+fn print_unique<'a>(label: &str, files: impl Iterator<Item = (&'a str, u64)>) -> usize {
+    let unique: Vec<(&str, u64)> = files.collect();
+
+    if unique.is_empty() {
+        println!("No files only in {label}");
+        return 0;
+    }
+
+    let size = ByteSize(unique.iter().map(|(_, size)| size).sum());
+
+    println!("Only in {label}: {} files ({size})", Count(unique.len()));
+
+    for (file_name, _) in &unique {
+        println!("  {file_name}");
+    }
+
+    println!();
+
+    return unique.len();
+}
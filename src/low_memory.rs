@@ -0,0 +1,264 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::env;
+use std::fs::{File, create_dir_all, remove_dir_all};
+use std::io::{BufRead, BufReader, BufWriter, Lines, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::byte_size::ByteSize;
+use crate::duplicate_report::{ReportOptions, ReportOutput, Source};
+use crate::errors::{AppError, AppErrorResult};
+use crate::hash_data::for_each_hash_entry;
+use crate::last_report::ReportedGroups;
+use crate::or_else;
+
+const SORT_CHUNK_ENTRIES: usize = 200_000;
+
+#[derive(Serialize, Deserialize, PartialEq, Eq)]
+struct SortRecord {
+    file_size: u64,
+    hash: String,
+    source: Source,
+    file_name: String,
+}
+
+impl Ord for SortRecord {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (
+            Reverse(self.file_size),
+            &self.hash,
+            self.source,
+            &self.file_name,
+        )
+            .cmp(&(
+                Reverse(other.file_size),
+                &other.hash,
+                other.source,
+                &other.file_name,
+            ))
+    }
+}
+
+impl PartialOrd for SortRecord {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// A temporary directory for on-disk sorts, removed again when dropped
+pub(crate) struct SortDirectory(PathBuf);
+
+impl SortDirectory {
+    pub(crate) fn create(name: &str) -> Result<SortDirectory, AppError> {
+        let sort_dir = env::temp_dir().join(format!("hashfolder-{}-{name}", process::id()));
+
+        create_dir_all(&sort_dir).app_err_at("Creating", &sort_dir)?;
+
+        return Ok(SortDirectory(sort_dir));
+    }
+}
+
+impl Drop for SortDirectory {
+    fn drop(&mut self) {
+        _ = remove_dir_all(&self.0);
+    }
+}
+
+/// Records sorted in chunks written to disk and merged back in order, so sorting more records
+/// than fit in memory only holds one chunk at a time
+pub(crate) struct ExternalSort<'a, T> {
+    sort_dir: &'a SortDirectory,
+    name: &'static str,
+    chunk: Vec<T>,
+    chunk_paths: Vec<PathBuf>,
+}
+
+impl<'a, T: Serialize + DeserializeOwned + Ord> ExternalSort<'a, T> {
+    pub(crate) fn new(sort_dir: &'a SortDirectory, name: &'static str) -> Self {
+        return ExternalSort {
+            sort_dir,
+            name,
+            chunk: Vec::default(),
+            chunk_paths: Vec::default(),
+        };
+    }
+
+    pub(crate) fn push(&mut self, record: T) -> Result<(), AppError> {
+        self.chunk.push(record);
+
+        if self.chunk.len() >= SORT_CHUNK_ENTRIES {
+            self.write_chunk()?;
+        }
+
+        return Ok(());
+    }
+
+    /// The records pushed, smallest first
+    pub(crate) fn sorted(mut self) -> Result<SortedRecords<T>, AppError> {
+        if !self.chunk.is_empty() {
+            self.write_chunk()?;
+        }
+
+        let mut readers = Vec::with_capacity(self.chunk_paths.len());
+        let mut heap = BinaryHeap::new();
+
+        for (index, chunk_path) in self.chunk_paths.iter().enumerate() {
+            let mut reader =
+                BufReader::new(File::open(chunk_path).app_err_at("Opening", chunk_path)?).lines();
+
+            if let Some(record) = read_record(&mut reader)? {
+                heap.push(Reverse((record, index)));
+            }
+
+            readers.push(reader);
+        }
+
+        return Ok(SortedRecords { readers, heap });
+    }
+
+    fn write_chunk(&mut self) -> Result<(), AppError> {
+        self.chunk.sort_unstable();
+
+        let chunk_path =
+            self.sort_dir
+                .0
+                .join(format!("{}-{}.jsonl", self.name, self.chunk_paths.len()));
+
+        let mut writer =
+            BufWriter::new(File::create(&chunk_path).app_err_at("Creating", &chunk_path)?);
+
+        for record in self.chunk.drain(..) {
+            serde_json::to_writer(&mut writer, &record).app_err()?;
+            writer.write_all(b"\n").app_err()?;
+        }
+
+        writer.flush().app_err()?;
+
+        self.chunk_paths.push(chunk_path);
+
+        return Ok(());
+    }
+}
+
+/// Merges the sorted chunks of an `ExternalSort`, reading one record ahead in each
+pub(crate) struct SortedRecords<T> {
+    readers: Vec<Lines<BufReader<File>>>,
+    heap: BinaryHeap<Reverse<(T, usize)>>,
+}
+
+impl<T: DeserializeOwned + Ord> SortedRecords<T> {
+    pub(crate) fn next(&mut self) -> Result<Option<T>, AppError> {
+        let Reverse((record, index)) = or_else!(self.heap.pop(), none => return Ok(None));
+
+        if let Some(reader) = self.readers.get_mut(index)
+            && let Some(next_record) = read_record(reader)?
+        {
+            self.heap.push(Reverse((next_record, index)));
+        }
+
+        return Ok(Some(record));
+    }
+}
+
+fn read_record<T: DeserializeOwned>(
+    reader: &mut Lines<BufReader<File>>,
+) -> Result<Option<T>, AppError> {
+    return match reader.next() {
+        Some(line) => Ok(Some(serde_json::from_str(&line.app_err()?).app_err()?)),
+        None => Ok(None),
+    };
+}
+
+/// Duplicate report that streams the hash data files from disk and groups them with an
+/// external merge sort, so memory use stays bounded by the sort chunk size
+pub fn low_memory_duplicate_report(
+    data_paths: &[(Source, &Path)],
+    options: &ReportOptions,
+    reported: &mut ReportedGroups,
+) -> Result<(), AppError> {
+    let sort_dir = SortDirectory::create("report")?;
+
+    let mut sort = ExternalSort::new(&sort_dir, "chunk");
+    let mut found_under_root = false;
+
+    for (source, data_path) in data_paths {
+        for_each_hash_entry(data_path, |file| {
+            found_under_root |=
+                *source == Source::Other && options.is_under_other_root(&file.file_name);
+
+            // Small and excluded files are left out only once their groups are recorded, as
+            // `duplicate_report` does
+            return sort.push(SortRecord {
+                file_size: file.content_size(),
+                hash: file.hash,
+                source: *source,
+                file_name: file.file_name,
+            });
+        })?;
+    }
+
+    options.check_other_root(found_under_root)?;
+
+    let mut records = sort.sorted()?;
+
+    let mut group: Vec<SortRecord> = Vec::default();
+    let mut output = ReportOutput::new(options, data_paths.len() > 1);
+
+    while let Some(record) = records.next()? {
+        if let Some(first) = group.first()
+            && (first.file_size != record.file_size || first.hash != record.hash)
+        {
+            flush_group(&mut output, &mut group, options, reported);
+        }
+
+        group.push(record);
+    }
+
+    flush_group(&mut output, &mut group, options, reported);
+
+    output.finish();
+
+    return Ok(());
+}
+
+fn flush_group(
+    output: &mut ReportOutput,
+    group: &mut Vec<SortRecord>,
+    options: &ReportOptions,
+    reported: &mut ReportedGroups,
+) {
+    let minimum: u64 = options.minimum.unwrap_or(ByteSize(1)).into();
+
+    // The same path from a root's hash data and a nested folder's
+    group.dedup_by(|a, b| a.source == b.source && a.file_name == b.file_name);
+
+    if group.len() > 1
+        && let Some(first) = group.first()
+    {
+        let files: Vec<&str> = group
+            .iter()
+            .map(|record| record.file_name.as_str())
+            .collect();
+
+        reported.record(&first.hash, &files);
+
+        let members: Vec<(Source, &str)> = group
+            .iter()
+            .map(|record| (record.source, record.file_name.as_str()))
+            .filter(|(source, file_name)| !options.excludes(*source, file_name))
+            .collect();
+
+        let files: Vec<&str> = members.iter().map(|(_, file_name)| *file_name).collect();
+
+        if members.len() > 1 && first.file_size >= minimum && options.shows(&first.hash, &files)
+        {
+            output.add_group(first.file_size, members.into_iter());
+        }
+    }
+
+    group.clear();
+}
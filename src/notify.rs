@@ -0,0 +1,160 @@
+use std::fmt::Write;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::byte_size::Count;
+use crate::errors::AppError;
+use crate::scan_folders::ScanSummary;
+
+/// SMTP settings from the `email` section of the config file
+#[derive(Deserialize)]
+#[cfg_attr(not(feature = "email"), allow(dead_code))]
+pub struct EmailConfig {
+    pub smtp_server: String,
+    /// Defaults to 587 with STARTTLS, or 465 with `implicit_tls`
+    #[serde(default)]
+    pub port: Option<u16>,
+    /// Connect with TLS from the start instead of upgrading with STARTTLS
+    #[serde(default)]
+    pub implicit_tls: bool,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+/// Subject and body summarising a scan, with files that failed to read listed first since they
+/// may point to failing storage
+pub fn scan_summary_message(
+    starting_dir: &Path,
+    summary: &ScanSummary,
+    scan_err: Option<&AppError>,
+) -> (String, String) {
+    let problems = summary.failed_files.len() + summary.unreadable_paths.len();
+
+    let outcome = match (scan_err, problems) {
+        (Some(_), _) => "stopped early".into(),
+        (None, 0) => "finished".into(),
+        (None, problems) => format!("finished with {} problems", Count(problems)),
+    };
+
+    let subject = format!(
+        "hashfolder: scan of {} {outcome}",
+        starting_dir.to_string_lossy()
+    );
+
+    let mut body = format!("Scan of {} {outcome}\n", starting_dir.to_string_lossy());
+
+    if let Some(scan_err) = scan_err {
+        _ = writeln!(body, "\n{scan_err}");
+    }
+
+    if !summary.failed_files.is_empty() {
+        _ = writeln!(
+            body,
+            "\n{} files could not be read:",
+            Count(summary.failed_files.len())
+        );
+
+        for (path, err) in &summary.failed_files {
+            _ = writeln!(body, "  {path}: {err}");
+        }
+    }
+
+    if !summary.unreadable_paths.is_empty() {
+        _ = writeln!(
+            body,
+            "\n{} paths skipped for lack of permission:",
+            Count(summary.unreadable_paths.len())
+        );
+
+        for path in &summary.unreadable_paths {
+            _ = writeln!(body, "  {path}");
+        }
+    }
+
+    if !summary.in_use_files.is_empty() {
+        _ = writeln!(
+            body,
+            "\n{} files in use were skipped until the next run",
+            Count(summary.in_use_files.len())
+        );
+    }
+
+    return (subject, body);
+}
+
+/// Email a plain text message to every address in the config
+#[cfg(feature = "email")]
+pub fn send_email(config: &EmailConfig, subject: &str, body: String) -> Result<(), AppError> {
+    use lettre::message::Mailbox;
+    use lettre::transport::smtp::authentication::Credentials;
+    use lettre::{Message, SmtpTransport, Transport};
+
+    use crate::errors::AppErrorResult;
+
+    let mut message = Message::builder()
+        .from(config.from.parse::<Mailbox>().app_err()?)
+        .subject(subject);
+
+    for to in &config.to {
+        message = message.to(to.parse::<Mailbox>().app_err()?);
+    }
+
+    let message = message.body(body).app_err()?;
+
+    let mut transport = if config.implicit_tls {
+        SmtpTransport::relay(&config.smtp_server).app_err()?
+    } else {
+        SmtpTransport::starttls_relay(&config.smtp_server).app_err()?
+    };
+
+    if let Some(port) = config.port {
+        transport = transport.port(port);
+    }
+
+    if let Some(username) = &config.username {
+        transport = transport.credentials(Credentials::new(
+            username.clone(),
+            config.password.clone().unwrap_or_default(),
+        ));
+    }
+
+    transport.build().send(&message).app_err()?;
+
+    return Ok(());
+}
+
+/// Show a desktop notification, for when the terminal is out of sight
+#[cfg(feature = "desktop-notification")]
+pub fn send_desktop_notification(summary: &str, body: &str) -> Result<(), AppError> {
+    use notify_rust::Notification;
+
+    use crate::errors::AppErrorResult;
+
+    Notification::new()
+        .appname("hashfolder")
+        .summary(summary)
+        .body(body)
+        .show()
+        .app_err()?;
+
+    return Ok(());
+}
+
+#[cfg(not(feature = "desktop-notification"))]
+pub fn send_desktop_notification(_summary: &str, _body: &str) -> Result<(), AppError> {
+    return Err(AppError::new(
+        "Desktop notifications need hashfolder built with the desktop-notification feature".into(),
+    ));
+}
+
+#[cfg(not(feature = "email"))]
+pub fn send_email(_config: &EmailConfig, _subject: &str, _body: String) -> Result<(), AppError> {
+    return Err(AppError::new(
+        "Sending email needs hashfolder built with the email feature".into(),
+    ));
+}
@@ -0,0 +1,134 @@
+use std::io::{IsTerminal, Write, stdin, stdout};
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::execute;
+use crossterm::style::{Attribute, Print, PrintStyledContent, Stylize};
+use crossterm::terminal::{self, Clear, ClearType};
+
+const PROMPT: &str = "-- More -- Space for the next page, Enter for the next group, Q to stop";
+
+/// Pauses report output once a page is full, like `git log` does, until a key is pressed
+pub struct Pager {
+    /// Groups per page, otherwise a page is a screenful of lines
+    page_size: Option<usize>,
+    lines: usize,
+    groups: usize,
+    /// Pause again after the next group instead of a whole page
+    single_step: bool,
+    stopped: bool,
+}
+
+impl Pager {
+    /// Only pages when both stdin and stdout are terminals, so piped output is never held up
+    pub fn new(page_size: Option<usize>) -> Option<Self> {
+        if !stdout().is_terminal() || !stdin().is_terminal() {
+            return None;
+        }
+
+        return Some(Pager {
+            page_size,
+            // The report header
+            lines: 1,
+            groups: 0,
+            single_step: false,
+            stopped: false,
+        });
+    }
+
+    /// Called before printing a group of `lines` lines, waits for a key if the page is full.
+    /// Returns false once the reader has asked to stop.
+    pub fn next_group(&mut self, lines: usize) -> bool {
+        if self.stopped {
+            return false;
+        }
+
+        let page_full = if self.single_step {
+            self.groups > 0
+        } else {
+            match self.page_size {
+                Some(page_size) => self.groups >= page_size,
+                None => {
+                    let height = terminal::size().map_or(24, |(_, rows)| rows as usize);
+                    // One line is kept for the prompt
+                    self.groups > 0 && self.lines + lines >= height
+                }
+            }
+        };
+
+        if page_full {
+            match wait_for_key() {
+                PagerKey::Page => self.single_step = false,
+                PagerKey::Group => self.single_step = true,
+                PagerKey::Stop => {
+                    self.stopped = true;
+                    return false;
+                }
+            }
+
+            self.lines = 0;
+            self.groups = 0;
+        }
+
+        self.lines += lines;
+        self.groups += 1;
+
+        return true;
+    }
+}
+
+enum PagerKey {
+    Page,
+    Group,
+    Stop,
+}
+
+fn wait_for_key() -> PagerKey {
+    let mut out = stdout();
+
+    _ = execute!(
+        out,
+        PrintStyledContent(PROMPT.attribute(Attribute::Reverse))
+    );
+
+    let was_raw = terminal::is_raw_mode_enabled().unwrap_or(false);
+    if !was_raw && terminal::enable_raw_mode().is_err() {
+        return PagerKey::Stop;
+    }
+
+    let key = loop {
+        let key_event = match event::read() {
+            Ok(Event::Key(key_event)) => key_event,
+            Ok(_) => continue,
+            Err(_) => break PagerKey::Stop,
+        };
+
+        if let Some(key) = pager_key(&key_event) {
+            break key;
+        }
+    };
+
+    if !was_raw {
+        _ = terminal::disable_raw_mode();
+    }
+
+    _ = execute!(out, Clear(ClearType::CurrentLine), Print("\r"));
+    _ = out.flush();
+
+    return key;
+}
+
+fn pager_key(key_event: &KeyEvent) -> Option<PagerKey> {
+    if key_event.kind != KeyEventKind::Press {
+        return None;
+    }
+
+    return match key_event.code {
+        KeyCode::Char('c') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+            Some(PagerKey::Stop)
+        }
+        KeyCode::Char(' ') | KeyCode::PageDown => Some(PagerKey::Page),
+        KeyCode::Enter | KeyCode::Down | KeyCode::Char('j') => Some(PagerKey::Group),
+        KeyCode::Char('q') | KeyCode::Char('Q') | KeyCode::Esc => Some(PagerKey::Stop),
+        _ => None,
+    };
+}
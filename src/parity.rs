@@ -0,0 +1,182 @@
+use std::collections::BTreeMap;
+use std::fs::{File, create_dir_all, read_dir, remove_file};
+use std::io::{BufReader, BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{AppError, AppErrorResult};
+use crate::hash_data::{load_current_hash_data, state_directory};
+
+const PARITY_FILENAME: &str = "parity.json";
+const PARITY_DIRECTORY: &str = "parity";
+const PAR2_COMMAND: &str = "par2";
+const RECOVERY_SET_NAME: &str = "recovery";
+
+/// PAR2 recovery files made for each directory, kept in `.hashfolder/parity.json` so `verify`
+/// can check they are still there
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ParityRecord {
+    sets: BTreeMap<String, ParitySet>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ParitySet {
+    files: Vec<String>,
+    created: u64,
+}
+
+impl ParityRecord {
+    pub fn load(starting_dir: &Path) -> Result<ParityRecord, AppError> {
+        let parity_path = state_directory(starting_dir).join(PARITY_FILENAME);
+
+        if !parity_path.is_file() {
+            return Ok(ParityRecord::default());
+        }
+
+        let reader = BufReader::new(File::open(&parity_path).app_err_at("Opening", &parity_path)?);
+
+        return serde_json::from_reader(reader).app_err_at("Reading", &parity_path);
+    }
+
+    fn save(&self, starting_dir: &Path) -> Result<(), AppError> {
+        let parity_path = state_directory(starting_dir).join(PARITY_FILENAME);
+
+        let mut writer =
+            BufWriter::new(File::create(&parity_path).app_err_at("Creating", &parity_path)?);
+        serde_json::to_writer_pretty(&mut writer, self).app_err_at("Writing", &parity_path)?;
+        writer.flush().app_err_at("Writing", &parity_path)?;
+
+        return Ok(());
+    }
+
+    /// Recorded recovery files that no longer exist
+    pub fn missing_files(&self) -> Vec<&str> {
+        return self
+            .sets
+            .values()
+            .flat_map(|set| &set.files)
+            .filter(|file| !Path::new(file).is_file())
+            .map(|file| file.as_str())
+            .collect();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.sets.is_empty();
+    }
+}
+
+/// Make PAR2 recovery files for each directory in the hash data with `par2` from par2cmdline,
+/// written under `.hashfolder/parity/` so the scanned tree itself is left as it was
+pub fn create_parity(starting_dir: &Path, redundancy: u8) -> Result<(), AppError> {
+    let hash_data = load_current_hash_data(starting_dir, false)?;
+
+    let mut directories: BTreeMap<PathBuf, Vec<&str>> = BTreeMap::default();
+
+    for entry in &hash_data {
+        let path = Path::new(&entry.file_name);
+
+        if let Some(parent) = path.parent() {
+            directories
+                .entry(parent.to_path_buf())
+                .or_default()
+                .push(&entry.file_name);
+        }
+    }
+
+    let mut record = ParityRecord::load(starting_dir)?;
+
+    let created = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .app_err()?
+        .as_secs();
+
+    for (directory, files) in directories {
+        let relative = directory.strip_prefix(starting_dir).app_err()?;
+        let target = state_directory(starting_dir)
+            .join(PARITY_DIRECTORY)
+            .join(relative);
+
+        create_dir_all(&target).app_err_at("Creating", &target)?;
+
+        // par2 won't overwrite a previous set
+        for file in recovery_files(&target)? {
+            remove_file(&file).app_err_at("Removing", &file)?;
+        }
+
+        let status = Command::new(PAR2_COMMAND)
+            .arg("create")
+            .arg("-q")
+            .arg(format!("-r{redundancy}"))
+            .arg(format!("-B{}", directory.to_string_lossy()))
+            .arg(target.join(format!("{RECOVERY_SET_NAME}.par2")))
+            .arg("--")
+            .args(&files)
+            .status();
+
+        let status = match status {
+            Ok(status) => status,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                return Err(AppError::new(
+                    "Creating recovery files needs par2cmdline installed as par2".into(),
+                ));
+            }
+            Err(err) => return Err(err).app_err_at("Running par2 for", &directory),
+        };
+
+        if !status.success() {
+            return Err(AppError::new(format!(
+                "par2 failed for {} with {status}",
+                directory.to_string_lossy()
+            )));
+        }
+
+        let parity_files: Vec<String> = recovery_files(&target)?
+            .iter()
+            .map(|file| file.to_string_lossy().to_string())
+            .collect();
+
+        println!(
+            "Created {} recovery files for {}",
+            parity_files.len(),
+            directory.to_string_lossy()
+        );
+
+        record.sets.insert(
+            directory.to_string_lossy().to_string(),
+            ParitySet {
+                files: parity_files,
+                created,
+            },
+        );
+    }
+
+    record.save(starting_dir)?;
+
+    return Ok(());
+}
+
+fn recovery_files(target: &Path) -> Result<Vec<PathBuf>, AppError> {
+    let mut files: Vec<PathBuf> = Vec::default();
+
+    for file in read_dir(target).app_err_at("Reading", target)? {
+        let path = file.app_err_at("Reading", target)?.path();
+
+        let is_recovery_file = path
+            .extension()
+            .is_some_and(|extension| extension == "par2")
+            && path
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with(RECOVERY_SET_NAME));
+
+        if is_recovery_file {
+            files.push(path);
+        }
+    }
+
+    files.sort();
+
+    return Ok(files);
+}
@@ -0,0 +1,181 @@
+use std::path::{MAIN_SEPARATOR, Path};
+use std::sync::Arc;
+
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use regex::Regex;
+
+use crate::errors::{AppError, AppErrorResult};
+use crate::or_else;
+
+/// System and application files that are never worth hashing, matched ignoring case and left out
+/// unless `--no-default-excludes` is given
+const DEFAULT_EXCLUDES: [&str; 16] = [
+    "**/Thumbs.db",
+    "**/ehthumbs.db",
+    "**/desktop.ini",
+    "**/$RECYCLE.BIN",
+    "**/System Volume Information",
+    "**/.DS_Store",
+    "**/._*",
+    "**/.Spotlight-V100",
+    "**/.Trashes",
+    "**/.fseventsd",
+    "**/~$*",
+    "**/.*.swp",
+    // Browser cache shards
+    "**/Cache/Cache_Data",
+    "**/Code Cache",
+    "**/GPUCache",
+    "**/cache2/entries",
+];
+
+/// Paths left out of a scan, matched against the path relative to the scan root
+#[derive(Debug, Default)]
+pub struct PathFilter {
+    globs: GlobSet,
+    regexes: Vec<Regex>,
+    /// When set, only files matching one of these are scanned
+    includes: Option<GlobSet>,
+}
+
+impl PathFilter {
+    pub fn new(
+        globs: &[Glob],
+        regexes: &[Regex],
+        includes: &[Glob],
+        default_excludes: bool,
+    ) -> Result<PathFilter, AppError> {
+        let includes = if includes.is_empty() {
+            None
+        } else {
+            Some(build_glob_set(includes)?)
+        };
+
+        let mut globs = globs.to_vec();
+
+        if default_excludes {
+            for pattern in DEFAULT_EXCLUDES {
+                globs.push(
+                    GlobBuilder::new(pattern)
+                        .case_insensitive(true)
+                        .build()
+                        .app_err()?,
+                );
+            }
+        }
+
+        return Ok(PathFilter {
+            globs: build_glob_set(&globs)?,
+            regexes: regexes.to_vec(),
+            includes,
+        });
+    }
+
+    /// Whether a file under `root` is one of those scanned, directories are always walked so
+    /// matching files deeper down are found
+    pub fn is_included(&self, root: &Path, path: &Path) -> bool {
+        let includes = or_else!(&self.includes, none => return true);
+        let relative = or_else!(path.strip_prefix(root), _ => return true);
+
+        return includes.is_match(relative);
+    }
+
+    /// Whether the path under `root` is excluded. Regexes see `/` separators on every platform,
+    /// with a trailing `/` on directories so a pattern can pick out directories only.
+    pub fn is_excluded(&self, root: &Path, path: &Path, is_dir: bool) -> bool {
+        let relative = or_else!(path.strip_prefix(root), _ => return false);
+
+        if self.globs.is_match(relative) {
+            return true;
+        }
+
+        if self.regexes.is_empty() {
+            return false;
+        }
+
+        let mut relative = relative.to_string_lossy().replace(MAIN_SEPARATOR, "/");
+
+        if is_dir {
+            relative.push('/');
+        }
+
+        return self.regexes.iter().any(|regex| regex.is_match(&relative));
+    }
+
+    /// Whether a file listed in hash data is excluded itself or lies in an excluded directory
+    pub fn excludes_file(&self, root: &Path, path: &Path) -> bool {
+        return path
+            .ancestors()
+            .take_while(|ancestor| *ancestor != root && ancestor.starts_with(root))
+            .enumerate()
+            .any(|(depth, ancestor)| self.is_excluded(root, ancestor, depth > 0));
+    }
+}
+
+/// File of gitignore rules for the directory it's in and everything below it
+pub const IGNORE_FILENAME: &str = ".hashfolderignore";
+
+/// The `.hashfolderignore` rules that apply in a directory, its own first, then those of each
+/// directory above it
+#[derive(Debug)]
+pub struct IgnoreFiles {
+    rules: Gitignore,
+    parent: Option<Arc<IgnoreFiles>>,
+}
+
+impl IgnoreFiles {
+    /// The rules for `directory`, adding its own ignore file, if it has one, to its parent's.
+    /// Problems reading the file are returned alongside whatever rules could be read from it.
+    pub fn for_directory(
+        parent: Option<Arc<IgnoreFiles>>,
+        directory: &Path,
+    ) -> (Option<Arc<IgnoreFiles>>, Option<ignore::Error>) {
+        let ignore_path = directory.join(IGNORE_FILENAME);
+
+        if !ignore_path.is_file() {
+            return (parent, None);
+        }
+
+        let mut builder = GitignoreBuilder::new(directory);
+
+        let add_err = builder.add(&ignore_path);
+
+        return match builder.build() {
+            Ok(rules) => (Some(Arc::new(IgnoreFiles { rules, parent })), add_err),
+            Err(err) => (parent, Some(err)),
+        };
+    }
+
+    /// Whether the closest rule matching the path ignores it, a `!` rule in a deeper file can
+    /// bring back what one above ignores
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let mut current = Some(self);
+
+        while let Some(ignore_files) = current {
+            let matched = ignore_files.rules.matched(path, is_dir);
+
+            if matched.is_ignore() {
+                return true;
+            }
+
+            if matched.is_whitelist() {
+                return false;
+            }
+
+            current = ignore_files.parent.as_deref();
+        }
+
+        return false;
+    }
+}
+
+fn build_glob_set(globs: &[Glob]) -> Result<GlobSet, AppError> {
+    let mut glob_set = GlobSetBuilder::new();
+
+    for glob in globs {
+        glob_set.add(glob.clone());
+    }
+
+    return glob_set.build().app_err();
+}
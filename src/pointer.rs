@@ -0,0 +1,109 @@
+use std::fs::read_link;
+use std::io::Read;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+/// Git LFS pointer files are well under this, larger files aren't checked
+pub const LFS_POINTER_MAX_SIZE: u64 = 1024;
+const LFS_VERSION_LINE: &str = "version https://git-lfs.github.com/spec/v1";
+const ANNEX_OBJECTS: &str = "annex/objects";
+const SHA256_HEX_LENGTH: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PointerKind {
+    Lfs,
+    Annex,
+}
+
+/// A stand-in for a large file kept elsewhere, stored with the SHA-256 of the file it refers to
+/// so it matches the real file in another tree
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Pointer {
+    pub kind: PointerKind,
+    /// Size of the referenced file
+    pub size: u64,
+}
+
+/// Read a Git LFS pointer, returning the referenced file's SHA-256 if `file` is one. At most
+/// `LFS_POINTER_MAX_SIZE` bytes are read.
+pub fn read_lfs_pointer(file: impl Read) -> Option<(String, Pointer)> {
+    let mut content = String::default();
+
+    // Fails on anything that isn't text
+    file.take(LFS_POINTER_MAX_SIZE)
+        .read_to_string(&mut content)
+        .ok()?;
+
+    let mut lines = content.lines();
+
+    if lines.next() != Some(LFS_VERSION_LINE) {
+        return None;
+    }
+
+    let mut oid: Option<&str> = None;
+    let mut size: Option<u64> = None;
+
+    for line in lines {
+        if let Some(value) = line.strip_prefix("oid sha256:") {
+            oid = Some(value);
+        } else if let Some(value) = line.strip_prefix("size ") {
+            size = value.parse().ok();
+        }
+    }
+
+    return match (oid, size) {
+        (Some(oid), Some(size)) if is_sha256_hex(oid) => Some((
+            oid.to_string(),
+            Pointer {
+                kind: PointerKind::Lfs,
+                size,
+            },
+        )),
+        _ => None,
+    };
+}
+
+/// The SHA-256 of the file a git-annex symlink points to, read from the annex key in the link
+/// target so content that isn't present still gets a hash. Keys from other backends give `None`.
+pub fn read_annex_link(path: &Path) -> Option<(String, Pointer)> {
+    let target = read_link(path).ok()?;
+
+    if !target.to_string_lossy().contains(ANNEX_OBJECTS) {
+        return None;
+    }
+
+    let key = target.file_name()?.to_string_lossy().to_string();
+
+    // BACKEND-sSIZE[-mMTIME...]--HASH[.ext]
+    let (fields, name) = key.split_once("--")?;
+    let mut fields = fields.split('-');
+
+    if !matches!(fields.next(), Some("SHA256" | "SHA256E")) {
+        return None;
+    }
+
+    let size: u64 = fields
+        .find_map(|field| field.strip_prefix('s'))?
+        .parse()
+        .ok()?;
+
+    let hash = name.split('.').next()?.to_lowercase();
+
+    if !is_sha256_hex(&hash) {
+        return None;
+    }
+
+    return Some((
+        hash,
+        Pointer {
+            kind: PointerKind::Annex,
+            size,
+        },
+    ));
+}
+
+fn is_sha256_hex(value: &str) -> bool {
+    return value.len() == SHA256_HEX_LENGTH && value.bytes().all(|byte| byte.is_ascii_hexdigit());
+}
@@ -0,0 +1,198 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle, sleep};
+use std::time::{Duration, Instant};
+
+use indicatif::ProgressBar;
+
+use crate::errors::AppError;
+#[cfg(target_os = "linux")]
+use crate::or_else;
+use crate::utils::check_exit_key_pressed;
+
+const POWER_POLL_INTERVAL: Duration = Duration::from_secs(5);
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(200);
+#[cfg(target_os = "linux")]
+const POWER_SUPPLY_DIRECTORY: &str = "/sys/class/power_supply";
+#[cfg(target_os = "linux")]
+const THERMAL_DIRECTORY: &str = "/sys/class/thermal";
+
+static PAUSED: AtomicBool = AtomicBool::new(false);
+
+pub struct PowerMonitor {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Drop for PowerMonitor {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+
+        if let Some(handle) = self.handle.take() {
+            _ = handle.join();
+        }
+
+        PAUSED.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Check the power source and CPU temperature on a background thread until the monitor is
+/// dropped, pausing hashing on battery with `only_on_ac` and while the CPU is hot with
+/// `pause_when_hot`
+pub fn start_power_monitor(
+    only_on_ac: bool,
+    pause_when_hot: bool,
+    progress: ProgressBar,
+) -> PowerMonitor {
+    PAUSED.store(false, Ordering::Relaxed);
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let thread_stop = stop.clone();
+
+    let handle = thread::spawn(move || {
+        let print = |message: String| {
+            if progress.is_hidden() {
+                println!("{message}");
+            } else {
+                progress.println(message);
+            }
+        };
+
+        let mut checked: Option<Instant> = None;
+
+        while !thread_stop.load(Ordering::Relaxed) {
+            if checked.is_some_and(|checked| checked.elapsed() < POWER_POLL_INTERVAL) {
+                sleep(STOP_POLL_INTERVAL);
+                continue;
+            }
+
+            checked = Some(Instant::now());
+
+            let paused = PAUSED.load(Ordering::Relaxed);
+
+            let reason = if only_on_ac && on_battery() {
+                Some("running on battery".to_string())
+            } else if pause_when_hot {
+                overheated(paused).map(|celsius| format!("CPU at {celsius}°C"))
+            } else {
+                None
+            };
+
+            match reason {
+                Some(reason) if !paused => {
+                    PAUSED.store(true, Ordering::Relaxed);
+                    print(format!("Paused, {reason}"));
+                }
+                None if paused => {
+                    PAUSED.store(false, Ordering::Relaxed);
+                    print("Resumed".into());
+                }
+                _ => {}
+            }
+        }
+    });
+
+    return PowerMonitor {
+        stop,
+        handle: Some(handle),
+    };
+}
+
+/// Block while the power monitor has the scan paused, the abort key still stops it
+pub fn wait_while_paused() -> Result<(), AppError> {
+    while PAUSED.load(Ordering::Relaxed) {
+        check_exit_key_pressed()?;
+        sleep(PAUSE_POLL_INTERVAL);
+    }
+
+    return Ok(());
+}
+
+#[cfg(target_os = "linux")]
+fn on_battery() -> bool {
+    use std::fs::{read_dir, read_to_string};
+
+    let supplies = or_else!(read_dir(POWER_SUPPLY_DIRECTORY), _ => return false);
+
+    return supplies.flatten().any(|supply| {
+        let path = supply.path();
+
+        let read = |name: &str| {
+            read_to_string(path.join(name))
+                .map(|value| value.trim().to_string())
+                .unwrap_or_default()
+        };
+
+        read("type") == "Battery" && read("status") == "Discharging"
+    });
+}
+
+#[cfg(not(target_os = "linux"))]
+fn on_battery() -> bool {
+    return false;
+}
+
+/// Temperature in degrees of the hottest thermal zone at its limit, its lowest hot or critical
+/// trip point. Once paused it has to cool a little below it before resuming, so the scan doesn't
+/// flap around the limit.
+#[cfg(target_os = "linux")]
+fn overheated(paused: bool) -> Option<i64> {
+    use std::fs::{read_dir, read_to_string};
+
+    /// Limit for zones without a hot or critical trip point
+    const DEFAULT_LIMIT: i64 = 90_000;
+    const RESUME_MARGIN: i64 = 5_000;
+
+    let zones = read_dir(THERMAL_DIRECTORY).ok()?;
+
+    let read = |path: std::path::PathBuf| -> Option<String> {
+        return read_to_string(path)
+            .ok()
+            .map(|value| value.trim().to_string());
+    };
+
+    let mut hottest: Option<i64> = None;
+
+    for zone in zones.flatten() {
+        let path = zone.path();
+
+        if !zone
+            .file_name()
+            .to_string_lossy()
+            .starts_with("thermal_zone")
+        {
+            continue;
+        }
+
+        let temperature: i64 =
+            or_else!(read(path.join("temp")).and_then(|temp| temp.parse().ok()), none => continue);
+
+        let trip_point = (0..)
+            .map_while(|index| {
+                let kind = read(path.join(format!("trip_point_{index}_type")))?;
+                let temp = read(path.join(format!("trip_point_{index}_temp")))?;
+                return Some((kind, temp.parse::<i64>().unwrap_or(i64::MAX)));
+            })
+            .filter(|(kind, temp)| (kind == "hot" || kind == "critical") && *temp > 0)
+            .map(|(_, temp)| temp)
+            .min();
+
+        let mut limit = trip_point.unwrap_or(DEFAULT_LIMIT);
+
+        if paused {
+            limit -= RESUME_MARGIN;
+        }
+
+        if temperature >= limit {
+            hottest = hottest.max(Some(temperature));
+        }
+    }
+
+    return hottest.map(|millidegrees| millidegrees / 1000);
+}
+
+#[cfg(not(target_os = "linux"))]
+fn overheated(_paused: bool) -> Option<i64> {
+    return None;
+}
@@ -0,0 +1,166 @@
+use crate::errors::AppError;
+
+/// Switch to the user that ran sudo (or nobody), keeping only the capability to read any
+/// file so protected folders can still be scanned
+#[cfg(target_os = "linux")]
+pub fn drop_privileges() -> Result<(), AppError> {
+    use std::env::var;
+    use std::io::Error;
+
+    const LINUX_CAPABILITY_VERSION_3: u32 = 0x2008_0522;
+    const CAP_DAC_READ_SEARCH: u32 = 2;
+    const NOBODY: u32 = 65534;
+
+    #[repr(C)]
+    struct CapabilityHeader {
+        version: u32,
+        pid: i32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Default)]
+    struct CapabilityData {
+        effective: u32,
+        permitted: u32,
+        inheritable: u32,
+    }
+
+    // SAFETY: geteuid has no preconditions
+    if unsafe { libc::geteuid() } != 0 {
+        return Err(AppError::new(
+            "Dropping privileges needs the scan to be started as root".into(),
+        ));
+    }
+
+    let uid: u32 = var("SUDO_UID")
+        .ok()
+        .and_then(|uid| uid.parse().ok())
+        .unwrap_or(NOBODY);
+    let gid: u32 = var("SUDO_GID")
+        .ok()
+        .and_then(|gid| gid.parse().ok())
+        .unwrap_or(NOBODY);
+
+    let last_error = |action: &str| {
+        AppError::new(format!(
+            "Failed to {action} while dropping privileges: {}",
+            Error::last_os_error()
+        ))
+    };
+
+    // SAFETY: plain syscalls on the current process with valid arguments, the capability
+    // structs match the kernel's v3 layout of one header and two data entries
+    unsafe {
+        if libc::prctl(libc::PR_SET_KEEPCAPS, 1, 0, 0, 0) != 0 {
+            return Err(last_error("keep capabilities"));
+        }
+
+        if libc::setgroups(0, std::ptr::null()) != 0 {
+            return Err(last_error("clear groups"));
+        }
+
+        if libc::setresgid(gid, gid, gid) != 0 {
+            return Err(last_error("set group"));
+        }
+
+        if libc::setresuid(uid, uid, uid) != 0 {
+            return Err(last_error("set user"));
+        }
+
+        let mut header = CapabilityHeader {
+            version: LINUX_CAPABILITY_VERSION_3,
+            pid: 0,
+        };
+        let mut data = [CapabilityData::default(); 2];
+        data[0].effective = 1 << CAP_DAC_READ_SEARCH;
+        data[0].permitted = 1 << CAP_DAC_READ_SEARCH;
+
+        if libc::syscall(
+            libc::SYS_capset,
+            &mut header as *mut CapabilityHeader,
+            data.as_mut_ptr(),
+        ) != 0
+        {
+            return Err(last_error("restrict capabilities"));
+        }
+
+        if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+            return Err(last_error("disable privilege escalation"));
+        }
+    }
+
+    return Ok(());
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn drop_privileges() -> Result<(), AppError> {
+    return Err(AppError::new(
+        "Dropping privileges is only supported on Linux".into(),
+    ));
+}
+
+/// The user id of a user given by name or id
+#[cfg(target_os = "linux")]
+pub fn user_id(user: &str) -> Result<u32, AppError> {
+    use std::ffi::CString;
+
+    const BUFFER_SIZE: usize = 16 * 1024;
+
+    if let Ok(uid) = user.parse() {
+        return Ok(uid);
+    }
+
+    let unknown_user = || AppError::new(format!("Unknown user {user}"));
+
+    let name = CString::new(user).map_err(|_| unknown_user())?;
+
+    // SAFETY: passwd is plain data that getpwnam_r fills in, pointing its strings into buffer
+    let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut buffer = vec![0 as libc::c_char; BUFFER_SIZE];
+    let mut result = std::ptr::null_mut();
+
+    // SAFETY: every pointer is valid for the duration of the call and buffer's length is given
+    let status = unsafe {
+        libc::getpwnam_r(
+            name.as_ptr(),
+            &mut passwd,
+            buffer.as_mut_ptr(),
+            buffer.len(),
+            &mut result,
+        )
+    };
+
+    if status != 0 || result.is_null() {
+        return Err(unknown_user());
+    }
+
+    return Ok(passwd.pw_uid);
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn user_id(_user: &str) -> Result<u32, AppError> {
+    return Err(AppError::new(
+        "Filtering by owner is only supported on Linux".into(),
+    ));
+}
+
+/// The user that ran sudo, or the user running hashfolder
+#[cfg(target_os = "linux")]
+pub fn current_user_id() -> Result<u32, AppError> {
+    if let Some(uid) = std::env::var("SUDO_UID")
+        .ok()
+        .and_then(|uid| uid.parse().ok())
+    {
+        return Ok(uid);
+    }
+
+    // SAFETY: geteuid has no preconditions
+    return Ok(unsafe { libc::geteuid() });
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn current_user_id() -> Result<u32, AppError> {
+    return Err(AppError::new(
+        "Filtering by owner is only supported on Linux".into(),
+    ));
+}
@@ -0,0 +1,242 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{IsTerminal, stdout};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crossterm::execute;
+use crossterm::terminal::SetTitle;
+use indicatif::style::ProgressTracker;
+use indicatif::{
+    BinaryBytes, MultiProgress, ProgressBar, ProgressDrawTarget, ProgressState, ProgressStyle,
+};
+
+use crate::byte_size::Count;
+use crate::or_else;
+use crate::utils::{AbortKey, abort_pending, format_duration};
+
+const OVERALL_TEMPLATE: &str =
+    "{bar:30} {bytes}/{total_bytes} {window_rate} ETA {window_eta}{abort_hint} {msg}";
+const STATUS_LINE_TEMPLATE: &str =
+    "{percent:>3}% {prefix} {window_rate} ETA {window_eta}{abort_hint} {wide_msg}";
+const WORKER_TEMPLATE: &str = "{spinner} {wide_msg}";
+const TITLE_INTERVAL: Duration = Duration::from_millis(500);
+/// Span of recent progress the throughput and ETA are averaged over
+const RATE_WINDOW: Duration = Duration::from_secs(30);
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Clone, Copy)]
+enum WindowValue {
+    Rate,
+    Eta,
+}
+
+/// Throughput or time remaining from the bytes hashed over the last `RATE_WINDOW`, which
+/// follows changes in speed, such as moving from small files to large ones, without
+/// jumping around on every read
+#[derive(Clone)]
+struct WindowedRate {
+    value: WindowValue,
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl WindowedRate {
+    fn new(value: WindowValue) -> Self {
+        return WindowedRate {
+            value,
+            samples: VecDeque::default(),
+        };
+    }
+
+    /// Bytes per second across the window, once it spans long enough to mean something
+    fn bytes_per_second(&self) -> Option<f64> {
+        let (first_time, first_position) = self.samples.front()?;
+        let (last_time, last_position) = self.samples.back()?;
+
+        let seconds = last_time.duration_since(*first_time).as_secs_f64();
+
+        if seconds < 1.0 {
+            return None;
+        }
+
+        return Some(last_position.saturating_sub(*first_position) as f64 / seconds);
+    }
+}
+
+impl ProgressTracker for WindowedRate {
+    fn clone_box(&self) -> Box<dyn ProgressTracker> {
+        Box::new(self.clone())
+    }
+
+    fn tick(&mut self, state: &ProgressState, now: Instant) {
+        if let Some((last_time, _)) = self.samples.back()
+            && now.duration_since(*last_time) < SAMPLE_INTERVAL
+        {
+            return;
+        }
+
+        self.samples.push_back((now, state.pos()));
+
+        while let Some((first_time, _)) = self.samples.front()
+            && now.duration_since(*first_time) > RATE_WINDOW
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    fn reset(&mut self, _state: &ProgressState, _now: Instant) {
+        self.samples.clear();
+    }
+
+    fn write(&self, state: &ProgressState, w: &mut dyn fmt::Write) {
+        let bytes_per_second = self.bytes_per_second();
+
+        _ = match (self.value, bytes_per_second) {
+            (WindowValue::Rate, Some(rate)) => write!(w, "{}/s", BinaryBytes(rate as u64)),
+            (WindowValue::Eta, Some(rate)) if rate > 0.0 => {
+                let remaining = state.len().unwrap_or_default().saturating_sub(state.pos());
+
+                write!(
+                    w,
+                    "{}",
+                    format_duration(Duration::from_secs_f64(remaining as f64 / rate))
+                )
+            }
+            _ => write!(w, "-"),
+        };
+    }
+}
+
+/// Scan progress display: an overall bar for hashed bytes plus one line per hash worker, or a
+/// single status line with the file being hashed
+pub struct ScanProgress {
+    multi: MultiProgress,
+    overall: ProgressBar,
+    status_line: bool,
+    /// When the terminal title was last set, None when stdout isn't a terminal
+    title_updated: Option<Mutex<Instant>>,
+}
+
+impl ScanProgress {
+    /// Redraws are capped at `refresh_rate` per second, which keeps slow terminals such as
+    /// SSH sessions from being flooded
+    pub fn new(refresh_rate: u8, status_line: bool, abort_key: AbortKey) -> Self {
+        let multi = MultiProgress::with_draw_target(ProgressDrawTarget::stderr_with_hz(
+            refresh_rate.max(1),
+        ));
+
+        let template = if status_line {
+            STATUS_LINE_TEMPLATE
+        } else {
+            OVERALL_TEMPLATE
+        };
+
+        let overall = multi.add(ProgressBar::new(0));
+        overall.set_style(
+            ProgressStyle::with_template(template)
+                .map(|style| {
+                    style
+                        .with_key("window_rate", WindowedRate::new(WindowValue::Rate))
+                        .with_key("window_eta", WindowedRate::new(WindowValue::Eta))
+                        .with_key(
+                            "abort_hint",
+                            move |_: &ProgressState, w: &mut dyn fmt::Write| {
+                                if abort_pending() {
+                                    _ = write!(w, " - press {abort_key} again to stop");
+                                }
+                            },
+                        )
+                })
+                .unwrap_or_else(|_| ProgressStyle::default_bar()),
+        );
+        // Reads only bump an atomic position, ticking keeps the rate samples coming
+        overall.enable_steady_tick(SAMPLE_INTERVAL);
+
+        let title_updated = stdout()
+            .is_terminal()
+            .then(|| Mutex::new(Instant::now() - TITLE_INTERVAL));
+
+        return ScanProgress {
+            multi,
+            overall,
+            status_line,
+            title_updated,
+        };
+    }
+
+    pub fn overall(&self) -> &ProgressBar {
+        &self.overall
+    }
+
+    pub fn add_worker(&self) -> ProgressBar {
+        if self.status_line {
+            return ProgressBar::hidden();
+        }
+
+        let worker = self.multi.add(ProgressBar::new_spinner());
+        worker.set_style(
+            ProgressStyle::with_template(WORKER_TEMPLATE)
+                .unwrap_or_else(|_| ProgressStyle::default_spinner()),
+        );
+
+        return worker;
+    }
+
+    /// Show what a hash worker is reading
+    pub fn show_file(&self, worker: &ProgressBar, message: String) {
+        if self.status_line {
+            self.overall.set_message(message);
+        } else {
+            worker.set_message(message);
+        }
+    }
+
+    /// Show file counts beside the overall bar, and the progress in the terminal title so it
+    /// can be followed from another window or tab
+    pub fn set_file_counts(&self, completed: usize, discovered: usize) {
+        let file_counts = format!("{}/{} files", Count(completed), Count(discovered));
+
+        if self.status_line {
+            self.overall.set_prefix(file_counts);
+        } else {
+            self.overall.set_message(file_counts);
+        }
+
+        let title_updated = or_else!(&self.title_updated, none => return);
+        let mut title_updated = or_else!(title_updated.lock(), _ => return);
+
+        if title_updated.elapsed() < TITLE_INTERVAL {
+            return;
+        }
+
+        *title_updated = Instant::now();
+
+        let length = self.overall.length().unwrap_or_default().max(1);
+        let percent = self.overall.position() * 100 / length;
+
+        _ = execute!(
+            stdout(),
+            SetTitle(format!(
+                "hashfolder {percent}% ({completed}/{discovered} files)"
+            ))
+        );
+    }
+
+    /// Print a message above the progress bars without garbling them
+    pub fn println(&self, message: impl AsRef<str>) {
+        if self.multi.is_hidden() {
+            println!("{}", message.as_ref());
+        } else {
+            _ = self.multi.println(message);
+        }
+    }
+
+    pub fn finish(&self) {
+        self.overall.finish_and_clear();
+        _ = self.multi.clear();
+
+        if self.title_updated.is_some() {
+            _ = execute!(stdout(), SetTitle("hashfolder"));
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::byte_size::{ByteSize, Count};
+use crate::errors::AppError;
+use crate::hash_data::{FileEntry, load_hash_data_with_info, save_hash_data};
+use crate::utils::{canonical_path, unix_now};
+
+/// Drop entries no scan has found and `verify` hasn't checked for longer than `older_than` and
+/// those under any of `under`, only entries matching both when both are given, only counting
+/// them with `dry_run`. Entries from before scans recorded when they found a file are never
+/// older than `older_than`.
+pub fn prune(
+    starting_dir: &Path,
+    older_than: Option<Duration>,
+    under: &[PathBuf],
+    dry_run: bool,
+) -> Result<(), AppError> {
+    let under = under
+        .iter()
+        .map(|path| canonical_path(path))
+        .collect::<Result<Vec<PathBuf>, AppError>>()?;
+
+    let cutoff = older_than.map(|older_than| unix_now().saturating_sub(older_than.as_secs()));
+
+    let (data_file, info) = load_hash_data_with_info(starting_dir, false)?;
+
+    let total = data_file.len();
+
+    let (pruned, kept): (Vec<FileEntry>, Vec<FileEntry>) =
+        data_file.into_iter().partition(|file| {
+            cutoff.is_none_or(|cutoff| {
+                file.last_seen
+                    .max(file.last_verified)
+                    .is_some_and(|last_seen| last_seen < cutoff)
+            }) && (under.is_empty()
+                || under
+                    .iter()
+                    .any(|path| Path::new(&file.file_name).starts_with(path)))
+        });
+
+    let pruned_size: u64 = pruned.iter().map(|file| file.file_size).sum();
+
+    if dry_run {
+        println!(
+            "Would prune {} of {} entries ({}), {} kept",
+            Count(pruned.len()),
+            Count(total),
+            ByteSize(pruned_size),
+            Count(kept.len())
+        );
+
+        return Ok(());
+    }
+
+    if pruned.is_empty() {
+        println!("No entries to prune out of {}", Count(total));
+        return Ok(());
+    }
+
+    save_hash_data(starting_dir, &info, &kept)?;
+
+    println!(
+        "Pruned {} of {} entries ({}), {} kept",
+        Count(pruned.len()),
+        Count(total),
+        ByteSize(pruned_size),
+        Count(kept.len())
+    );
+
+    return Ok(());
+}
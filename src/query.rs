@@ -0,0 +1,488 @@
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use globset::{GlobBuilder, GlobMatcher};
+
+use crate::byte_size::ByteSize;
+use crate::hash_data::FileEntry;
+use crate::or_else;
+use crate::utils::parse_interval;
+
+/// A filter on hash data entries such as `size > 1GiB and ext in (mkv, iso)`, combining
+/// comparisons with `and`, `or`, `not` and parentheses
+#[derive(Clone, Debug)]
+pub enum Query {
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+    Not(Box<Query>),
+    Compare(Field, Comparison, Value),
+    In(Field, Vec<Value>),
+    Matches(Field, GlobMatcher),
+}
+
+/// What of an entry a query compares
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Field {
+    /// Size in bytes, compared with sizes such as `1GiB`
+    Size,
+    /// Time since the file was modified, compared with intervals such as `30d`
+    Age,
+    /// Extension without the dot, ignoring case
+    Ext,
+    /// File name without its folder
+    Name,
+    /// Full path as stored in the hash data
+    Path,
+    Hash,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Value {
+    Number(u64),
+    Text(String),
+}
+
+impl Field {
+    fn parse(name: &str) -> Result<Field, String> {
+        return match name.to_ascii_lowercase().as_str() {
+            "size" => Ok(Field::Size),
+            "age" => Ok(Field::Age),
+            "ext" => Ok(Field::Ext),
+            "name" => Ok(Field::Name),
+            "path" => Ok(Field::Path),
+            "hash" => Ok(Field::Hash),
+            _ => Err(format!(
+                "Unknown field {name}, expected size, age, ext, name, path or hash"
+            )),
+        };
+    }
+
+    fn is_numeric(self) -> bool {
+        return matches!(self, Field::Size | Field::Age);
+    }
+
+    fn parse_value(self, value: &str) -> Result<Value, String> {
+        return match self {
+            Field::Size => Ok(Value::Number(value.parse::<ByteSize>()?.into())),
+            Field::Age => Ok(Value::Number(parse_interval(value)?.as_secs())),
+            Field::Ext => Ok(Value::Text(value.trim_start_matches('.').to_lowercase())),
+            Field::Hash => Ok(Value::Text(value.to_lowercase())),
+            Field::Name | Field::Path => Ok(Value::Text(value.into())),
+        };
+    }
+
+    fn value_of(self, entry: &FileEntry, now: u64) -> Value {
+        let path = Path::new(&entry.file_name);
+
+        return match self {
+            Field::Size => Value::Number(entry.file_size),
+            Field::Age => Value::Number(now.saturating_sub(entry.modified)),
+            Field::Ext => Value::Text(
+                path.extension()
+                    .map(|extension| extension.to_string_lossy().to_lowercase())
+                    .unwrap_or_default(),
+            ),
+            Field::Name => Value::Text(
+                path.file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+            ),
+            Field::Path => Value::Text(entry.file_name.clone()),
+            Field::Hash => Value::Text(entry.hash.clone()),
+        };
+    }
+}
+
+impl Query {
+    /// Whether an entry passes the filter, with ages measured back from `now` in seconds since
+    /// the Unix epoch
+    pub fn matches_at(&self, entry: &FileEntry, now: u64) -> bool {
+        return match self {
+            Query::And(left, right) => left.matches_at(entry, now) && right.matches_at(entry, now),
+            Query::Or(left, right) => left.matches_at(entry, now) || right.matches_at(entry, now),
+            Query::Not(query) => !query.matches_at(entry, now),
+            Query::Compare(field, comparison, value) => {
+                let actual = field.value_of(entry, now);
+
+                match comparison {
+                    Comparison::Equal => actual == *value,
+                    Comparison::NotEqual => actual != *value,
+                    Comparison::Less => compare_numbers(&actual, value, |a, b| a < b),
+                    Comparison::LessOrEqual => compare_numbers(&actual, value, |a, b| a <= b),
+                    Comparison::Greater => compare_numbers(&actual, value, |a, b| a > b),
+                    Comparison::GreaterOrEqual => compare_numbers(&actual, value, |a, b| a >= b),
+                }
+            }
+            Query::In(field, values) => values.contains(&field.value_of(entry, now)),
+            Query::Matches(field, glob) => match field.value_of(entry, now) {
+                Value::Text(text) => glob.is_match(text),
+                Value::Number(_) => false,
+            },
+        };
+    }
+}
+
+fn compare_numbers(actual: &Value, expected: &Value, compare: fn(u64, u64) -> bool) -> bool {
+    return match (actual, expected) {
+        (Value::Number(actual), Value::Number(expected)) => compare(*actual, *expected),
+        _ => false,
+    };
+}
+
+impl FromStr for Query {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(text)?;
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+        };
+
+        let query = parser.parse_or()?;
+
+        if let Some(token) = parser.peek() {
+            return Err(format!("Unexpected {token} in query"));
+        }
+
+        return Ok(query);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Symbol(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            Token::Word(word) => write!(f, "{word}"),
+            Token::Quoted(text) => write!(f, "'{text}'"),
+            Token::Symbol(symbol) => write!(f, "{symbol}"),
+        };
+    }
+}
+
+/// Symbols in the order they're tried, longer ones before their prefixes
+const SYMBOLS: [&str; 10] = [">=", "<=", "!=", "==", ">", "<", "=", "(", ")", ","];
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::default();
+    let mut rest = text.trim_start();
+
+    while let Some(first) = rest.chars().next() {
+        if let Some(symbol) = SYMBOLS.iter().find(|symbol| rest.starts_with(**symbol)) {
+            tokens.push(Token::Symbol(symbol));
+            rest = &rest[symbol.len()..];
+        } else if first == '\'' || first == '"' {
+            let end = or_else!(rest[1..].find(first), none => {
+                return Err(format!("Unterminated {first} in query"));
+            });
+
+            tokens.push(Token::Quoted(rest[1..=end].into()));
+            rest = &rest[end + 2..];
+        } else {
+            let end = rest
+                .find(|c: char| c.is_whitespace() || "<>=!(),'\"".contains(c))
+                .unwrap_or(rest.len());
+
+            if end == 0 {
+                return Err(format!("Unexpected {first} in query"));
+            }
+
+            tokens.push(Token::Word(rest[..end].into()));
+            rest = &rest[end..];
+        }
+
+        rest = rest.trim_start();
+    }
+
+    return Ok(tokens);
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        return self.tokens.get(self.position);
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        return token;
+    }
+
+    fn next_is_keyword(&self, keyword: &str) -> bool {
+        return matches!(self.peek(), Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword));
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), String> {
+        return match self.next() {
+            Some(Token::Symbol(found)) if found == symbol => Ok(()),
+            Some(token) => Err(format!("Expected {symbol} but found {token} in query")),
+            None => Err(format!("Expected {symbol} at the end of the query")),
+        };
+    }
+
+    fn parse_or(&mut self) -> Result<Query, String> {
+        let mut query = self.parse_and()?;
+
+        while self.next_is_keyword("or") {
+            self.next();
+            query = Query::Or(Box::new(query), Box::new(self.parse_and()?));
+        }
+
+        return Ok(query);
+    }
+
+    fn parse_and(&mut self) -> Result<Query, String> {
+        let mut query = self.parse_unary()?;
+
+        while self.next_is_keyword("and") {
+            self.next();
+            query = Query::And(Box::new(query), Box::new(self.parse_unary()?));
+        }
+
+        return Ok(query);
+    }
+
+    fn parse_unary(&mut self) -> Result<Query, String> {
+        if self.next_is_keyword("not") {
+            self.next();
+            return Ok(Query::Not(Box::new(self.parse_unary()?)));
+        }
+
+        if self.peek() == Some(&Token::Symbol("(")) {
+            self.next();
+            let query = self.parse_or()?;
+            self.expect(")")?;
+            return Ok(query);
+        }
+
+        return self.parse_comparison();
+    }
+
+    fn parse_comparison(&mut self) -> Result<Query, String> {
+        let field = match self.next() {
+            Some(Token::Word(name)) => Field::parse(&name)?,
+            Some(token) => return Err(format!("Expected a field but found {token} in query")),
+            None => return Err("Expected a field at the end of the query".into()),
+        };
+
+        if self.next_is_keyword("in") {
+            self.next();
+            self.expect("(")?;
+
+            let mut values = vec![field.parse_value(&self.parse_text()?)?];
+
+            while self.peek() == Some(&Token::Symbol(",")) {
+                self.next();
+                values.push(field.parse_value(&self.parse_text()?)?);
+            }
+
+            self.expect(")")?;
+
+            return Ok(Query::In(field, values));
+        }
+
+        if self.next_is_keyword("matches") {
+            self.next();
+
+            if field.is_numeric() {
+                return Err("Only ext, name, path and hash can be matched with a glob".into());
+            }
+
+            let pattern = self.parse_text()?;
+            let glob = GlobBuilder::new(&pattern)
+                .case_insensitive(field == Field::Ext)
+                .build()
+                .map_err(|err| format!("Invalid glob {pattern}: {err}"))?;
+
+            return Ok(Query::Matches(field, glob.compile_matcher()));
+        }
+
+        let comparison = match self.next() {
+            Some(Token::Symbol("=" | "==")) => Comparison::Equal,
+            Some(Token::Symbol("!=")) => Comparison::NotEqual,
+            Some(Token::Symbol("<")) => Comparison::Less,
+            Some(Token::Symbol("<=")) => Comparison::LessOrEqual,
+            Some(Token::Symbol(">")) => Comparison::Greater,
+            Some(Token::Symbol(">=")) => Comparison::GreaterOrEqual,
+            Some(token) => {
+                return Err(format!(
+                    "Expected a comparison, in or matches but found {token} in query"
+                ));
+            }
+            None => return Err("Expected a comparison at the end of the query".into()),
+        };
+
+        let ordered = !matches!(comparison, Comparison::Equal | Comparison::NotEqual);
+
+        if ordered && !field.is_numeric() {
+            return Err("Only size and age can be compared with < and >".into());
+        }
+
+        let value = field.parse_value(&self.parse_text()?)?;
+
+        return Ok(Query::Compare(field, comparison, value));
+    }
+
+    fn parse_text(&mut self) -> Result<String, String> {
+        return match self.next() {
+            Some(Token::Word(text) | Token::Quoted(text)) => Ok(text),
+            Some(token) => Err(format!("Expected a value but found {token} in query")),
+            None => Err("Expected a value at the end of the query".into()),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+    const GIB: u64 = 1024 * 1024 * 1024;
+
+    fn entry(file_name: &str, file_size: u64) -> FileEntry {
+        return FileEntry {
+            file_name: file_name.into(),
+            file_size,
+            hash: "3b1c1662".into(),
+            modified: NOW - 60,
+            ..FileEntry::default()
+        };
+    }
+
+    fn matches(query: &str, entry: &FileEntry) -> bool {
+        return query.parse::<Query>().unwrap().matches_at(entry, NOW);
+    }
+
+    fn parse_error(query: &str) -> String {
+        return query.parse::<Query>().unwrap_err();
+    }
+
+    #[test]
+    fn size_and_extension_list() {
+        let query = "size > 1GiB and ext in (mkv,iso)";
+
+        assert!(matches(query, &entry("/data/film.MKV", 2 * GIB)));
+        assert!(matches(query, &entry("/data/disc.iso", 2 * GIB)));
+        assert!(!matches(query, &entry("/data/film.mkv", GIB)));
+        assert!(!matches(query, &entry("/data/notes.txt", 2 * GIB)));
+    }
+
+    #[test]
+    fn quoted_values_keep_spaces_and_other_quotes() {
+        assert!(matches(
+            "name = 'my file.txt'",
+            &entry("/data/my file.txt", 1)
+        ));
+        assert!(matches(
+            "path == \"/data/a b/c.txt\"",
+            &entry("/data/a b/c.txt", 1)
+        ));
+        assert!(matches("name = \"it's.txt\"", &entry("/data/it's.txt", 1)));
+        assert!(!matches(
+            "name != 'my file.txt'",
+            &entry("/data/my file.txt", 1)
+        ));
+    }
+
+    #[test]
+    fn not_binds_tighter_than_and_and_and_than_or() {
+        let query = "not ext = txt and size < 10";
+
+        assert!(!matches(query, &entry("a.txt", 5)));
+        assert!(matches(query, &entry("b.bin", 5)));
+        assert!(!matches(query, &entry("b.bin", 50)));
+
+        let query = "ext = txt or ext = md and size > 100";
+
+        assert!(matches(query, &entry("a.txt", 5)));
+        assert!(!matches(query, &entry("a.md", 5)));
+        assert!(matches(query, &entry("a.md", 500)));
+    }
+
+    #[test]
+    fn parentheses_override_precedence() {
+        let query = "(ext = txt or ext = md) and size > 100";
+
+        assert!(!matches(query, &entry("a.txt", 5)));
+        assert!(matches(query, &entry("a.txt", 500)));
+        assert!(matches("not (ext = txt or size > 100)", &entry("a.md", 5)));
+    }
+
+    #[test]
+    fn matches_globs() {
+        assert!(matches(
+            "name matches '*.tar.*'",
+            &entry("/data/backup.tar.gz", 1)
+        ));
+        assert!(matches(
+            "path matches '/data/**/cache/*'",
+            &entry("/data/a/b/cache/x", 1)
+        ));
+
+        // Extensions ignore case like `ext =` does, names don't
+        assert!(matches("ext matches 'M*'", &entry("film.mkv", 1)));
+        assert!(!matches("name matches 'F*'", &entry("film.mkv", 1)));
+    }
+
+    #[test]
+    fn age_is_measured_from_now() {
+        assert!(matches("age < 30d", &entry("a.txt", 1)));
+        assert!(!matches("age > 1h", &entry("a.txt", 1)));
+    }
+
+    #[test]
+    fn errors_name_the_problem() {
+        assert_eq!(
+            parse_error("colour = red"),
+            "Unknown field colour, expected size, age, ext, name, path or hash"
+        );
+        assert_eq!(
+            parse_error("name > a"),
+            "Only size and age can be compared with < and >"
+        );
+        assert_eq!(
+            parse_error("size matches '*'"),
+            "Only ext, name, path and hash can be matched with a glob"
+        );
+        assert_eq!(parse_error("name = 'open"), "Unterminated ' in query");
+        assert_eq!(
+            parse_error("(size > 1"),
+            "Expected ) at the end of the query"
+        );
+        assert_eq!(
+            parse_error("size >"),
+            "Expected a value at the end of the query"
+        );
+        assert_eq!(
+            parse_error("size 5"),
+            "Expected a comparison, in or matches but found 5 in query"
+        );
+        assert_eq!(
+            parse_error("ext in (mkv iso)"),
+            "Expected ) but found iso in query"
+        );
+        assert_eq!(parse_error("size > 1 size"), "Unexpected size in query");
+    }
+}
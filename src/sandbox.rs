@@ -0,0 +1,164 @@
+use std::path::Path;
+
+use crate::errors::AppError;
+
+/// Restrict the process with Landlock so it can only read beneath `read_paths` and read and
+/// write beneath `read_write_paths`. Files opened before the call stay usable.
+#[cfg(target_os = "linux")]
+pub fn restrict_filesystem(
+    read_paths: &[&Path],
+    read_write_paths: &[&Path],
+) -> Result<(), AppError> {
+    use std::fs::File;
+    use std::io::Error;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::os::unix::fs::OpenOptionsExt;
+
+    use crate::or_else;
+
+    const CREATE_RULESET_VERSION: u32 = 1 << 0;
+    const RULE_PATH_BENEATH: u32 = 1;
+
+    const ACCESS_FS_EXECUTE: u64 = 1 << 0;
+    const ACCESS_FS_WRITE_FILE: u64 = 1 << 1;
+    const ACCESS_FS_READ_FILE: u64 = 1 << 2;
+    const ACCESS_FS_READ_DIR: u64 = 1 << 3;
+    const ACCESS_FS_TRUNCATE: u64 = 1 << 14;
+    // Everything from EXECUTE through MAKE_SYM, the rights of the first Landlock ABI
+    const ACCESS_FS_ABI_1: u64 = (1 << 13) - 1;
+    const ACCESS_FS_REFER: u64 = 1 << 13;
+
+    #[repr(C)]
+    struct RulesetAttr {
+        handled_access_fs: u64,
+    }
+
+    #[repr(C, packed)]
+    struct PathBeneathAttr {
+        allowed_access: u64,
+        parent_fd: i32,
+    }
+
+    let sandbox_error = |action: &str| {
+        AppError::new(format!(
+            "Failed to {action} for the sandbox: {}",
+            Error::last_os_error()
+        ))
+    };
+
+    // SAFETY: querying the ABI version takes no attribute
+    let abi = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_create_ruleset,
+            std::ptr::null::<RulesetAttr>(),
+            0,
+            CREATE_RULESET_VERSION,
+        )
+    };
+
+    if abi < 1 {
+        return Err(sandbox_error("find Landlock support"));
+    }
+
+    let mut handled_access = ACCESS_FS_ABI_1;
+    if abi >= 2 {
+        handled_access |= ACCESS_FS_REFER;
+    }
+    if abi >= 3 {
+        handled_access |= ACCESS_FS_TRUNCATE;
+    }
+
+    let file_access = ACCESS_FS_EXECUTE | ACCESS_FS_WRITE_FILE | ACCESS_FS_READ_FILE;
+    let read_access = ACCESS_FS_READ_FILE | ACCESS_FS_READ_DIR;
+
+    let ruleset_attr = RulesetAttr {
+        handled_access_fs: handled_access,
+    };
+
+    // SAFETY: the attribute struct matches the kernel layout and outlives the call
+    let ruleset_fd = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_create_ruleset,
+            &ruleset_attr as *const RulesetAttr,
+            size_of::<RulesetAttr>(),
+            0,
+        )
+    };
+
+    if ruleset_fd < 0 {
+        return Err(sandbox_error("create the ruleset"));
+    }
+
+    // SAFETY: the syscall returned a new descriptor that nothing else owns
+    let ruleset_fd = unsafe { OwnedFd::from_raw_fd(ruleset_fd as i32) };
+
+    let rules = read_paths.iter().map(|path| (path, read_access)).chain(
+        read_write_paths
+            .iter()
+            .map(|path| (path, handled_access & !ACCESS_FS_EXECUTE)),
+    );
+
+    for (path, access) in rules {
+        let parent = or_else!(
+            File::options()
+                .read(true)
+                .custom_flags(libc::O_PATH | libc::O_CLOEXEC)
+                .open(path),
+            _ => continue
+        );
+
+        // Rules on a file may only grant rights that apply to files
+        let is_dir = parent.metadata().is_ok_and(|metadata| metadata.is_dir());
+        let access = if is_dir {
+            access & handled_access
+        } else {
+            access & handled_access & (file_access | ACCESS_FS_TRUNCATE)
+        };
+
+        let path_beneath = PathBeneathAttr {
+            allowed_access: access,
+            parent_fd: parent.as_raw_fd(),
+        };
+
+        // SAFETY: both descriptors are open and the attribute matches the kernel layout
+        let result = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_add_rule,
+                ruleset_fd.as_raw_fd(),
+                RULE_PATH_BENEATH,
+                &path_beneath as *const PathBeneathAttr,
+                0,
+            )
+        };
+
+        if result != 0 {
+            return Err(sandbox_error(&format!(
+                "allow access to {}",
+                path.to_string_lossy()
+            )));
+        }
+    }
+
+    // SAFETY: plain syscalls on the current process
+    unsafe {
+        if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+            return Err(sandbox_error("disable privilege escalation"));
+        }
+
+        if libc::syscall(libc::SYS_landlock_restrict_self, ruleset_fd.as_raw_fd(), 0) != 0 {
+            return Err(sandbox_error("restrict the process"));
+        }
+    }
+
+    return Ok(());
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn restrict_filesystem(
+    _read_paths: &[&Path],
+    _read_write_paths: &[&Path],
+) -> Result<(), AppError> {
+    return Err(AppError::new(
+        "The sandbox is only supported on Linux".into(),
+    ));
+}
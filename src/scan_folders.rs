@@ -1,200 +1,2162 @@
-use std::fs::{File, OpenOptions, read_dir};
-use std::io::{BufReader, Read, Stdout, stdout};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ffi::OsString;
+use std::fs::{DirEntry, File, FileType, Metadata, OpenOptions, read_dir};
+use std::io::{self, ErrorKind, IsTerminal, Read, Seek, stdin};
 use std::path::{Path, PathBuf};
-use std::time::UNIX_EPOCH;
+use std::sync::mpsc::{Receiver, Sender, SyncSender, channel, sync_channel};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, Scope};
+use std::time::{Duration, Instant, UNIX_EPOCH};
 
-use crossterm::{cursor, execute, terminal};
-use sha2::{Digest, Sha256};
+use clap::ValueEnum;
+use crossterm::terminal;
+use indicatif::ProgressBar;
+use memmap2::Mmap;
+use serde::{Deserialize, Serialize};
 
-use crate::errors::{AppError, AppErrorResult};
-use crate::hash_data::FileEntry;
+use crate::byte_size::Count;
+use crate::errors::{AppError, AppErrorResult, CaughtError};
+use crate::git::{GIT_DIRECTORY, head_commit, tracked_files};
+use crate::hash_cache::HashCache;
+use crate::hash_data::{
+    DatasetInfo, FileEntry, HashDataWriter, STATE_DIRECTORY, for_each_hash_entry,
+    get_hash_data_file_path, is_scan_root,
+};
+use crate::hash_log::HashLog;
+use crate::hashing::{Digests, Hasher, HasherFactory};
+use crate::in_use::InUseDetector;
+use crate::low_memory::{ExternalSort, SortDirectory, SortedRecords};
 use crate::or_else;
-use crate::utils::check_exit_key_pressed;
+use crate::path_filter::{IGNORE_FILENAME, IgnoreFiles, PathFilter};
+use crate::pointer::{LFS_POINTER_MAX_SIZE, Pointer, read_annex_link, read_lfs_pointer};
+use crate::power::{start_power_monitor, wait_while_paused};
+use crate::progress::ScanProgress;
+use crate::utils::{AbortKey, check_exit_key_pressed, listening_for_abort, unix_now};
+
+const MMAP_MIN_SIZE: u64 = 16 * 1024 * 1024;
+const MMAP_CHUNK_SIZE: usize = 1024 * 1024;
+const MIN_READ_BUFFER_SIZE: usize = 8 * 1024;
+const MAX_READ_BUFFER_SIZE: usize = 1024 * 1024;
+const MAX_ROTATIONAL_READ_BUFFER_SIZE: usize = 4 * 1024 * 1024;
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+const IO_URING_BATCH_SIZE: usize = 32;
+const WALK_QUEUE_SIZE: usize = 1024;
+const OPEN_QUEUE_SIZE: usize = 256;
+const HASH_QUEUE_SIZE: usize = 64;
+/// Seconds a scan lets an unchanged file's `last_seen` fall behind before moving it on, so
+/// scans of an unchanged tree write the same data file
+const LAST_SEEN_STEP: u64 = 24 * 60 * 60;
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub enum ChangeDetection {
+    /// Rehash when size or modified time change
+    Relaxed,
+    /// Also rehash when ctime or inode change
+    #[default]
+    Strict,
+}
+
+#[derive(Debug, Default)]
+pub struct ScanOptions {
+    pub change_detection: ChangeDetection,
+    pub mmap: bool,
+    pub buffer_size: Option<u64>,
+    pub hdd_mode: bool,
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    pub io_uring: bool,
+    pub threads: usize,
+    pub live_duplicates: bool,
+    pub retries: u32,
+    pub skip_in_use: bool,
+    /// Directory walked in place of the starting directory, such as a snapshot of it, with
+    /// paths still stored under the starting directory
+    pub walk_root: Option<PathBuf>,
+    /// Machine-wide hashes consulted before reading a file
+    pub shared_cache: Option<HashCache>,
+    /// Progress redraws per second
+    pub refresh_rate: u8,
+    /// Show progress on one line instead of a line per hash worker
+    pub status_line: bool,
+    /// File to append a line to for every file hashed
+    pub log_file: Option<PathBuf>,
+    pub abort_key: AbortKey,
+    /// Files smaller than this are neither hashed nor stored
+    pub scan_minimum: u64,
+    /// Files and directories left out of the scan
+    pub path_filter: PathFilter,
+    /// Leave out dotfiles and dot-directories
+    pub skip_hidden: bool,
+    /// Digest used for file contents
+    pub hasher: HasherFactory,
+    /// Leave out `.git` directories and note the repositories found
+    pub git_aware: bool,
+    /// Also leave out files tracked by those repositories
+    pub skip_git_tracked: bool,
+    /// Pause hashing while running on battery
+    pub only_on_ac: bool,
+    /// Pause hashing while the CPU is at its thermal trip point
+    pub pause_when_hot: bool,
+    /// Leave out subdirectories with hash data of their own
+    pub skip_nested_roots: bool,
+    /// Hash only these files instead of walking the tree, keeping the entries of all others
+    pub files_from: Option<Vec<PathBuf>>,
+    /// Entries under these paths are kept when their files aren't found, such as on a network
+    /// share that is offline
+    pub preserve_missing: Vec<PathBuf>,
+    /// When more than this percentage of the known files are missing, no entries are dropped and
+    /// the scan fails, since a disk or share is more likely unavailable than the files deleted
+    pub purge_limit: Option<u8>,
+    /// Only scan files owned by this user id, leaving out those of other users and directories
+    /// of theirs that can't be read
+    pub owner: Option<u32>,
+}
+
+enum ScanItem {
+    File(PathBuf),
+    Stat(PathBuf, Metadata),
+    DirectoryError(PathBuf, io::Error),
+    EntryError(io::Error),
+    StatFailed(PathBuf, io::Error),
+    EmptyDirectory(PathBuf),
+    SpecialFile(PathBuf, &'static str),
+    Hidden,
+    OtherOwner,
+    GitRepository(PathBuf, Option<String>),
+    GitError(PathBuf, io::Error),
+    GitTracked,
+    /// A git-annex symlink, with its own metadata and the key's hash
+    AnnexLink(PathBuf, Metadata, String, Pointer),
+    IgnoreFileError(PathBuf, ignore::Error),
+    NestedRoot(PathBuf),
+}
+
+/// Things noticed during a scan that aren't part of the hash data
+#[derive(Debug, Default)]
+pub struct ScanSummary {
+    pub empty_directories: Vec<PathBuf>,
+    pub special_files: Vec<(PathBuf, &'static str)>,
+    pub failed_files: Vec<(String, String)>,
+    pub in_use_files: Vec<String>,
+    pub unreadable_paths: Vec<String>,
+    pub new_files: usize,
+    pub changed_files: usize,
+    pub removed_files: usize,
+    pub moved_files: usize,
+    pub hashed_bytes: u64,
+    /// Dotfiles and dot-directories left out with `skip_hidden`
+    pub hidden_skipped: usize,
+    /// Files and unreadable directories of other users left out with `owner`
+    pub other_owner_skipped: usize,
+    /// Repositories found with `git_aware` and the commit each had checked out
+    pub git_repositories: Vec<(PathBuf, Option<String>)>,
+    /// Files left out with `skip_git_tracked`
+    pub git_tracked_skipped: usize,
+    /// Subdirectories left out with `skip_nested_roots`
+    pub nested_roots: Vec<PathBuf>,
+    /// Entries of missing files kept because they are under a `preserve_missing` path
+    pub preserved_missing: usize,
+}
+
+/// Signature and hash of a known file, matched against files found under new paths
+struct MovedHash {
+    file_size: u64,
+    modified: u64,
+    modified_nanos: Option<u32>,
+    hash: String,
+    also_hashes: BTreeMap<String, String>,
+    pointer: Option<Pointer>,
+}
+
+struct HashJob {
+    path: PathBuf,
+    entry: FileEntry,
+}
+
+enum HashOutcome {
+    Hashed(FileEntry, Duration),
+    OpenFailed(PathBuf, io::Error),
+    ReadFailed(FileEntry, CaughtError),
+    InUse(FileEntry),
+}
 
 pub fn scan_folder_tree(
     mut data_file: Vec<FileEntry>,
     starting_dir: &Path,
+    options: &ScanOptions,
+    summary: &mut ScanSummary,
 ) -> (Option<Vec<FileEntry>>, Option<AppError>) {
-    println!("Press Q to stop and save progress");
+    let max_buffer_size = max_buffer_size(starting_dir, options);
 
-    let mut out: Stdout = stdout();
+    let mut scan_result = listening_for_abort(options.abort_key, || {
+        scan_for_new_and_updated(
+            starting_dir,
+            &mut data_file,
+            summary,
+            options,
+            max_buffer_size,
+        )
+    });
 
-    data_file = or_else!(
-        scan_for_deleted(data_file),
-        err => return (None, Some(err))
-    );
+    if scan_result.is_ok() && options.files_from.is_none() {
+        scan_result = purge_missing(&mut data_file, options, summary);
+    }
 
-    let scan_result = scan_for_new_and_updated(&mut out, starting_dir, &mut data_file);
+    return (Some(data_file), scan_result.err());
+}
 
-    _ = terminal::disable_raw_mode();
-    println!();
+fn max_buffer_size(starting_dir: &Path, options: &ScanOptions) -> usize {
+    if options.hdd_mode {
+        return MAX_ROTATIONAL_READ_BUFFER_SIZE;
+    }
 
-    return (Some(data_file), scan_result.err());
+    return max_read_buffer_size(starting_dir);
 }
 
-fn scan_for_deleted(hash_data: Vec<FileEntry>) -> Result<Vec<FileEntry>, AppError> {
-    let mut result: Vec<FileEntry> = Vec::new();
+/// Drop the entries of files the scan didn't find, unless they are under a path to preserve.
+/// Fails without dropping any when more are missing than `purge_limit` allows.
+fn purge_missing(
+    data_file: &mut Vec<FileEntry>,
+    options: &ScanOptions,
+    summary: &mut ScanSummary,
+) -> Result<(), AppError> {
+    let is_preserved = |file: &FileEntry| {
+        options
+            .preserve_missing
+            .iter()
+            .any(|prefix| Path::new(&file.file_name).starts_with(prefix))
+    };
 
-    for file in hash_data.into_iter() {
-        check_exit_key_pressed()?;
+    let missing = data_file.iter().filter(|file| !file.seen).count();
+    let preserved = data_file
+        .iter()
+        .filter(|file| !file.seen && is_preserved(file))
+        .count();
 
-        if PathBuf::from(&file.file_name).is_file() {
-            result.push(file);
-        }
+    summary.preserved_missing = preserved;
+
+    check_purge_limit(options, missing - preserved, data_file.len())?;
+
+    let previous_len = data_file.len();
+    data_file.retain(|file| file.seen || is_preserved(file));
+    summary.removed_files = previous_len - data_file.len();
+
+    return Ok(());
+}
+
+/// Fails when more of the known files are missing than `purge_limit` allows
+fn check_purge_limit(options: &ScanOptions, missing: usize, total: usize) -> Result<(), AppError> {
+    if let Some(purge_limit) = options.purge_limit
+        && missing * 100 > total * purge_limit as usize
+    {
+        return Err(AppError::new(format!(
+            "{} of {} known files are missing, more than the {purge_limit}% purge limit. Their \
+             entries were kept, check the disk is mounted and the path is right, or run with \
+             --force-purge to drop them",
+            Count(missing),
+            Count(total)
+        )));
     }
 
-    return Ok(result);
+    return Ok(());
 }
 
+/// Runs the scan as a pipeline: a walker thread enumerates directories, a stat thread reads
+/// metadata, this thread checks the hash data, an opener thread opens files that need hashing
+/// and the hash workers read them. Queues between stages are bounded so the walker can't run
+/// arbitrarily far ahead and the number of open files stays limited.
 fn scan_for_new_and_updated(
-    out: &mut Stdout,
     starting_dir: &Path,
-    data_file: &mut Vec<FileEntry>,
+    hash_data: &mut Vec<FileEntry>,
+    summary: &mut ScanSummary,
+    options: &ScanOptions,
+    max_buffer_size: usize,
 ) -> Result<(), AppError> {
-    terminal::enable_raw_mode().app_err()?;
+    if stdin().is_terminal() {
+        terminal::enable_raw_mode().app_err()?;
+    }
+
+    let progress = ScanProgress::new(options.refresh_rate, options.status_line, options.abort_key);
 
-    let mut pending_directories_list: Vec<PathBuf> = Vec::default();
+    let power_monitor = start_power_monitor(
+        options.only_on_ac,
+        options.pause_when_hot,
+        progress.overall().clone(),
+    );
 
-    pending_directories_list.push(starting_dir.into());
+    let in_use_detector = options.skip_in_use.then(InUseDetector::new);
 
-    loop {
-        let current_directory = or_else!(pending_directories_list.pop(), none => return Ok(()));
+    let mut new_entries: Vec<FileEntry> = Vec::default();
+
+    let scan_result = thread::scope(|scope| {
+        let (walk_sender, walk_receiver) = sync_channel(WALK_QUEUE_SIZE);
+        let (stat_sender, stat_receiver) = sync_channel(WALK_QUEUE_SIZE);
+
+        let walk_root = options.walk_root.as_deref().unwrap_or(starting_dir);
+
+        match &options.files_from {
+            Some(files) => scope.spawn(move || send_listed_files(files, walk_sender)),
+            None => scope.spawn(move || walk_folders(walk_root, options, walk_sender)),
+        };
+        scope.spawn(move || stat_files(walk_receiver, stat_sender, options.retries));
+
+        let (job_sender, result_receiver) = spawn_hash_stages(
+            scope,
+            options,
+            in_use_detector.as_ref(),
+            &progress,
+            max_buffer_size,
+        );
+
+        return process_scan_items(
+            &progress,
+            starting_dir,
+            hash_data,
+            &mut new_entries,
+            summary,
+            options,
+            stat_receiver,
+            job_sender,
+            result_receiver,
+        );
+    });
+
+    drop(power_monitor);
+    progress.finish();
+
+    // Also after an abort, so the files hashed so far are saved
+    merge_new_entries(hash_data, new_entries);
+
+    return scan_result;
+}
+
+/// A scan that sorts the hash data and the files it finds by path on disk and merges them,
+/// writing the new hash data to `hash_data_file` as each entry is settled, so memory use
+/// doesn't grow with the tree. Unlike `scan_folder_tree` a file found under a new path is
+/// hashed again rather than matched to its old entry by inode, and `files_from`,
+/// `live_duplicates` and `shared_cache` aren't supported. Returns how many entries were saved and their total
+/// size, or None when the hash data was left as it was.
+pub fn scan_folder_tree_low_memory(
+    starting_dir: &Path,
+    hash_data_file: &File,
+    info: &DatasetInfo,
+    options: &ScanOptions,
+    summary: &mut ScanSummary,
+) -> (Option<(usize, u64)>, Option<AppError>) {
+    let max_buffer_size = max_buffer_size(starting_dir, options);
+
+    return listening_for_abort(options.abort_key, || {
+        if stdin().is_terminal()
+            && let Err(err) = terminal::enable_raw_mode().app_err()
+        {
+            return (None, Some(err));
+        }
+
+        let sort_dir = or_else!(SortDirectory::create("scan"), err => return (None, Some(err)));
+
+        let progress =
+            ScanProgress::new(options.refresh_rate, options.status_line, options.abort_key);
+
+        // Nothing is written until the walk is done, so stopping before then leaves the hash
+        // data as it was
+        let sorted = sort_previous_and_found(&sort_dir, starting_dir, options, summary, &progress);
+
+        let (previous, found, unreadable) = or_else!(sorted, err => {
+            progress.finish();
+            return (None, Some(err));
+        });
+
+        let mut writer = or_else!(HashDataWriter::start(hash_data_file, info), err => {
+            progress.finish();
+            return (None, Some(err));
+        });
+
+        let power_monitor = start_power_monitor(
+            options.only_on_ac,
+            options.pause_when_hot,
+            progress.overall().clone(),
+        );
+
+        let scan_result = merge_and_hash(
+            &progress,
+            &sort_dir,
+            previous,
+            found,
+            &unreadable,
+            &mut writer,
+            summary,
+            options,
+            max_buffer_size,
+        );
+
+        drop(power_monitor);
+        progress.finish();
+
+        return match (writer.finish(), scan_result) {
+            (Ok(saved), scan_result) => (Some(saved), scan_result.err()),
+            (Err(err), _) => (None, Some(err)),
+        };
+    });
+}
 
-        let mut subdirectory_list = process_folder(out, current_directory, data_file)?;
+/// A previous entry, sorted by path with the newest entry for a path first so it's the one kept
+#[derive(Serialize, Deserialize)]
+#[serde(transparent)]
+struct PreviousEntry(FileEntry);
 
-        pending_directories_list.append(&mut subdirectory_list);
+impl Ord for PreviousEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        return self
+            .0
+            .file_name
+            .cmp(&other.0.file_name)
+            .then(other.0.modified.cmp(&self.0.modified))
+            .then(other.0.modified_nanos.cmp(&self.0.modified_nanos));
     }
 }
 
-fn process_folder(
-    out: &mut Stdout,
-    current_path: PathBuf,
-    hash_data: &mut Vec<FileEntry>,
-) -> Result<Vec<PathBuf>, AppError> {
-    let mut file_list: Vec<PathBuf> = Vec::default();
-    let mut subdirectory_list: Vec<PathBuf> = Vec::default();
+impl PartialOrd for PreviousEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        return Some(self.cmp(other));
+    }
+}
 
-    let dir_reader = or_else!(
-        read_dir(&current_path),
-        err => {
-            println!(
-                "Error reading directory {}: {}",
-                current_path.to_string_lossy(),
-                err
-            );
-            execute!(out, cursor::MoveToNextLine(1)).app_err()?;
-            return Ok(subdirectory_list);
+impl PartialEq for PreviousEntry {
+    fn eq(&self, other: &Self) -> bool {
+        return self.cmp(other) == Ordering::Equal;
+    }
+}
+
+impl Eq for PreviousEntry {}
+
+/// A file found by the walk of a low-memory scan, sorted by the path it's stored under
+#[derive(Serialize, Deserialize)]
+struct FoundFile {
+    /// Without its hash, unless it's a git-annex link
+    entry: FileEntry,
+    /// Where to read the file when that isn't the path it's stored under, such as in a
+    /// snapshot or with a name that isn't valid UTF-8
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    path: Option<OsString>,
+    /// Couldn't be checked, so its previous entry is kept
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    failed: bool,
+}
+
+impl Ord for FoundFile {
+    fn cmp(&self, other: &Self) -> Ordering {
+        return self.entry.file_name.cmp(&other.entry.file_name);
+    }
+}
+
+impl PartialOrd for FoundFile {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        return Some(self.cmp(other));
+    }
+}
+
+impl PartialEq for FoundFile {
+    fn eq(&self, other: &Self) -> bool {
+        return self.cmp(other) == Ordering::Equal;
+    }
+}
+
+impl Eq for FoundFile {}
+
+type SortedScan = (
+    SortedRecords<PreviousEntry>,
+    SortedRecords<FoundFile>,
+    Vec<PathBuf>,
+);
+
+/// Sort the saved entries and the files the walk finds by path on disk, returning them with the
+/// directories that couldn't be read
+fn sort_previous_and_found(
+    sort_dir: &SortDirectory,
+    starting_dir: &Path,
+    options: &ScanOptions,
+    summary: &mut ScanSummary,
+    progress: &ScanProgress,
+) -> Result<SortedScan, AppError> {
+    let mut previous = ExternalSort::new(sort_dir, "previous");
+
+    // The data file is created empty when it's opened for saving up front
+    if get_hash_data_file_path(starting_dir, true)?
+        .metadata()
+        .is_ok_and(|metadata| metadata.len() > 0)
+    {
+        for_each_hash_entry(starting_dir, |entry| previous.push(PreviousEntry(entry)))?;
+    }
+
+    let mut found = ExternalSort::new(sort_dir, "found");
+    let mut unreadable = Vec::default();
+    let mut discovered: usize = 0;
+
+    thread::scope(|scope| {
+        let (walk_sender, walk_receiver) = sync_channel(WALK_QUEUE_SIZE);
+        let (stat_sender, stat_receiver) = sync_channel(WALK_QUEUE_SIZE);
+
+        let walk_root = options.walk_root.as_deref().unwrap_or(starting_dir);
+
+        scope.spawn(move || walk_folders(walk_root, options, walk_sender));
+        scope.spawn(move || stat_files(walk_receiver, stat_sender, options.retries));
+
+        for item in stat_receiver {
+            let (path, metadata, annex_link) =
+                match note_scan_item(progress, summary, starting_dir, options, item?) {
+                    WalkOutcome::Found(path, metadata, annex_link) => (path, metadata, annex_link),
+                    WalkOutcome::Failed(file_name) => {
+                        found.push(FoundFile {
+                            entry: FileEntry {
+                                file_name,
+                                ..FileEntry::default()
+                            },
+                            path: None,
+                            failed: true,
+                        })?;
+                        continue;
+                    }
+                    WalkOutcome::Unreadable(path) => {
+                        unreadable.push(path);
+                        continue;
+                    }
+                    WalkOutcome::Noted => continue,
+                };
+
+            discovered += 1;
+
+            progress.set_file_counts(0, discovered);
+
+            let file_name = stored_path(starting_dir, options, path.clone())
+                .to_string_lossy()
+                .to_string();
+
+            // Skipped with a warning, the previous hash is kept until the time can be read again
+            let modified = or_else!(modified_time(&metadata), err => {
+                record_failure(progress, summary, file_name.clone(), &err);
+                found.push(FoundFile {
+                    entry: FileEntry {
+                        file_name,
+                        ..FileEntry::default()
+                    },
+                    path: None,
+                    failed: true,
+                })?;
+                continue;
+            });
+
+            let mut entry = new_entry(file_name, &metadata, modified);
+
+            if let Some((hash, pointer)) = annex_link {
+                entry.hash = hash;
+                entry.pointer = Some(pointer);
+            }
+
+            let path = (path.to_str() != Some(entry.file_name.as_str())).then(|| path.into());
+
+            found.push(FoundFile {
+                entry,
+                path,
+                failed: false,
+            })?;
         }
-    );
 
-    for current_entry in dir_reader {
-        check_exit_key_pressed()?;
+        return Ok(());
+    })?;
 
-        match current_entry {
-            Err(err) => {
-                print!("Error reading directory entry: {err:?}");
-                execute!(out, cursor::MoveToNextLine(1)).app_err()?;
+    return Ok((previous.sorted()?, found.sorted()?, unreadable));
+}
+
+/// Go through the saved entries and the files found together in path order, keeping the entries
+/// of unchanged files and sending the others to be hashed, writing each entry once it's settled.
+/// After an error or the abort key every remaining file keeps its previous entry.
+#[allow(clippy::too_many_arguments)]
+fn merge_and_hash(
+    progress: &ScanProgress,
+    sort_dir: &SortDirectory,
+    mut previous: SortedRecords<PreviousEntry>,
+    mut found: SortedRecords<FoundFile>,
+    unreadable: &[PathBuf],
+    writer: &mut HashDataWriter,
+    summary: &mut ScanSummary,
+    options: &ScanOptions,
+    max_buffer_size: usize,
+) -> Result<(), AppError> {
+    let in_use_detector = options.skip_in_use.then(InUseDetector::new);
+
+    let mut hash_log = options.log_file.as_deref().map(HashLog::open).transpose()?;
+
+    // Entries of files not found, dropped at the end unless there are too many of them
+    let mut missing = ExternalSort::new(sort_dir, "missing");
+    let mut missing_count: usize = 0;
+    let mut preserved: usize = 0;
+
+    // Previous entries of files being hashed, kept if they can't be read
+    let mut in_flight: HashMap<String, Option<FileEntry>> = HashMap::default();
+
+    let mut stopped: Option<AppError> = None;
+
+    thread::scope(|scope| {
+        let (job_sender, result_receiver) = spawn_hash_stages(
+            scope,
+            options,
+            in_use_detector.as_ref(),
+            progress,
+            max_buffer_size,
+        );
+
+        let mut discovered: usize = 0;
+        let mut completed: usize = 0;
+
+        let mut next_previous = next_previous_entry(&mut previous, None)?;
+        let mut next_found = found.next()?;
+
+        loop {
+            while let Ok(result) = result_receiver.try_recv() {
+                match result {
+                    Ok(outcome) => settle_hash_outcome(
+                        progress,
+                        writer,
+                        summary,
+                        &mut in_flight,
+                        &mut hash_log,
+                        outcome,
+                    )?,
+                    Err(err) => _ = stopped.get_or_insert(err),
+                }
+
+                completed += 1;
             }
-            Ok(entry) => {
-                let path = entry.path();
 
-                if path.is_dir() {
-                    subdirectory_list.push(path);
-                } else if path.is_file() {
-                    file_list.push(path);
+            let order = match (&next_found, &next_previous) {
+                (Some(found_file), Some(previous_entry)) => {
+                    found_file.entry.file_name.cmp(&previous_entry.file_name)
+                }
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => break,
+            };
+
+            let previous_entry = if order.is_ge() {
+                let previous_entry = next_previous.take();
+                next_previous = next_previous_entry(&mut previous, previous_entry.as_ref())?;
+                previous_entry
+            } else {
+                None
+            };
+
+            let found_file = if order.is_le() {
+                let found_file = next_found.take();
+                next_found = found.next()?;
+                found_file
+            } else {
+                None
+            };
+
+            let found_file = or_else!(found_file, none => {
+                let previous_entry = or_else!(previous_entry, none => continue);
+
+                let is_under = |directory: &PathBuf| {
+                    Path::new(&previous_entry.file_name).starts_with(directory)
+                };
+
+                if stopped.is_some() || unreadable.iter().any(is_under) {
+                    writer.write(&previous_entry)?;
+                } else if options.preserve_missing.iter().any(is_under) {
+                    preserved += 1;
+                    writer.write(&previous_entry)?;
+                } else {
+                    missing_count += 1;
+                    missing.push(PreviousEntry(previous_entry))?;
+                }
+
+                continue;
+            });
+
+            if stopped.is_some() || found_file.failed {
+                if let Some(previous_entry) = previous_entry {
+                    writer.write(&previous_entry)?;
                 }
+
+                continue;
             }
-        }
-    }
 
-    let terminal_width: usize = terminal::size().map(|size| size.0).unwrap_or(75).into();
+            discovered += 1;
 
-    for (index, current_file) in file_list.iter().enumerate() {
-        let progress = (index + 1) * 100 / file_list.len();
+            progress.set_file_counts(completed, discovered);
 
-        println!(
-            "{progress}% {:1$.1$}",
-            current_path.to_string_lossy(),
-            terminal_width - 5
-        );
-        execute!(out, cursor::MoveToPreviousLine(1)).app_err()?;
+            let entry = found_file.entry;
 
-        let file_name = current_file.to_string_lossy().to_string();
+            let previous_entry = match previous_entry {
+                Some(mut previous_entry) if is_unchanged(&previous_entry, &entry, options) => {
+                    refresh_entry(&mut previous_entry, &entry);
+                    writer.write(&previous_entry)?;
+                    completed += 1;
+                    continue;
+                }
+                previous_entry => previous_entry,
+            };
 
-        let file = or_else!(
-            OpenOptions::new().read(true).open(current_file),
-            err => {
-                println!(
-                    "Error reading file {}: {}",
-                    current_path.to_string_lossy(),
-                    err
-                );
-                execute!(out, cursor::MoveToNextLine(1)).app_err()?;
+            // Git-annex links already have their hash
+            if entry.pointer.is_some() {
+                if previous_entry.is_some() {
+                    summary.changed_files += 1;
+                } else {
+                    summary.new_files += 1;
+                }
+
+                writer.write(&entry)?;
+                completed += 1;
                 continue;
             }
-        );
 
-        let metadata = file.metadata().app_err()?;
+            progress.overall().inc_length(entry.file_size);
 
-        let modified = metadata
-            .modified()
-            .app_err()?
-            .duration_since(UNIX_EPOCH)
-            .app_err()?
-            .as_secs();
+            let path = found_file
+                .path
+                .map_or_else(|| PathBuf::from(&entry.file_name), PathBuf::from);
+            let file_name = entry.file_name.clone();
 
-        let file_size = metadata.len();
+            if job_sender.send(HashJob { path, entry }).is_ok() {
+                in_flight.insert(file_name, previous_entry);
+            } else {
+                stopped.get_or_insert_with(|| AppError::new("The hash workers stopped".into()));
 
-        let entry_position = hash_data.binary_search_by_key(&&file_name, |entry| &entry.file_name);
+                if let Some(previous_entry) = previous_entry {
+                    writer.write(&previous_entry)?;
+                }
+            }
+        }
 
-        if let Ok(entry_position) = entry_position
-            && let Some(entry) = hash_data.get(entry_position)
-            && entry.file_size == file_size
-            && entry.modified == modified
-        {
-            continue;
+        drop(job_sender);
+
+        for result in result_receiver {
+            match result {
+                Ok(outcome) => settle_hash_outcome(
+                    progress,
+                    writer,
+                    summary,
+                    &mut in_flight,
+                    &mut hash_log,
+                    outcome,
+                )?,
+                Err(err) => _ = stopped.get_or_insert(err),
+            }
+
+            completed += 1;
+
+            progress.set_file_counts(completed, discovered);
+        }
+
+        return Ok(());
+    })?;
+
+    // Files whose outcome never arrived after hashing stopped
+    for previous_entry in in_flight.into_values().flatten() {
+        writer.write(&previous_entry)?;
+    }
+
+    summary.preserved_missing = preserved;
+
+    let purge_result = match &stopped {
+        Some(_) => Ok(()),
+        None => check_purge_limit(options, missing_count, writer.files() + missing_count),
+    };
+
+    if stopped.is_some() || purge_result.is_err() {
+        let mut missing = missing.sorted()?;
+
+        while let Some(PreviousEntry(previous_entry)) = missing.next()? {
+            writer.write(&previous_entry)?;
+        }
+    } else {
+        summary.removed_files = missing_count;
+    }
+
+    if let Some(err) = stopped {
+        return Err(err);
+    }
+
+    return purge_result;
+}
+
+/// The next saved entry, skipping older entries for the path of the last one
+fn next_previous_entry(
+    previous: &mut SortedRecords<PreviousEntry>,
+    last: Option<&FileEntry>,
+) -> Result<Option<FileEntry>, AppError> {
+    while let Some(PreviousEntry(entry)) = previous.next()? {
+        if last.is_none_or(|last| last.file_name != entry.file_name) {
+            return Ok(Some(entry));
+        }
+    }
+
+    return Ok(None);
+}
+
+/// Write the entry of a hashed file, or its previous entry when it couldn't be read
+fn settle_hash_outcome(
+    progress: &ScanProgress,
+    writer: &mut HashDataWriter,
+    summary: &mut ScanSummary,
+    in_flight: &mut HashMap<String, Option<FileEntry>>,
+    hash_log: &mut Option<HashLog>,
+    outcome: HashOutcome,
+) -> Result<(), AppError> {
+    let previous_entry = match &outcome {
+        HashOutcome::Hashed(entry, _)
+        | HashOutcome::ReadFailed(entry, _)
+        | HashOutcome::InUse(entry) => in_flight.remove(&entry.file_name),
+        HashOutcome::OpenFailed(path, _) => in_flight.remove(path.to_string_lossy().as_ref()),
+    }
+    .flatten();
+
+    if let HashOutcome::Hashed(entry, duration) = &outcome {
+        note_hashed(progress, summary, hash_log, entry, *duration);
+
+        if previous_entry.is_some() {
+            summary.changed_files += 1;
+        } else {
+            summary.new_files += 1;
+        }
+
+        return writer.write(entry);
+    }
+
+    note_hash_failure(progress, summary, outcome);
+
+    if let Some(previous_entry) = previous_entry {
+        writer.write(&previous_entry)?;
+    }
+
+    return Ok(());
+}
+
+/// Start the thread opening files to hash and the hash workers reading them, returning where
+/// to send the files and where their outcomes arrive
+fn spawn_hash_stages<'scope>(
+    scope: &'scope Scope<'scope, '_>,
+    options: &'scope ScanOptions,
+    in_use_detector: Option<&'scope InUseDetector>,
+    progress: &'scope ScanProgress,
+    max_buffer_size: usize,
+) -> (SyncSender<HashJob>, Receiver<Result<HashOutcome, AppError>>) {
+    // Rotational disks thrash when several files are read at once
+    let hash_workers = if options.hdd_mode {
+        1
+    } else {
+        options.threads.max(1)
+    };
+
+    let (job_sender, job_receiver) = sync_channel(OPEN_QUEUE_SIZE);
+    let (open_sender, open_receiver) = sync_channel(HASH_QUEUE_SIZE);
+    let (result_sender, result_receiver) = channel();
+
+    scope.spawn({
+        let result_sender = result_sender.clone();
+        move || {
+            open_files(
+                job_receiver,
+                open_sender,
+                result_sender,
+                options.retries,
+                in_use_detector,
+            )
         }
+    });
+
+    let open_receiver = Arc::new(Mutex::new(open_receiver));
+
+    for _ in 0..hash_workers {
+        let open_receiver = open_receiver.clone();
+        let result_sender = result_sender.clone();
+        let worker_progress = progress.add_worker();
+        scope.spawn(move || {
+            hash_files(
+                open_receiver,
+                result_sender,
+                options,
+                max_buffer_size,
+                progress,
+                &worker_progress,
+            );
+            worker_progress.finish_and_clear();
+        });
+    }
+
+    return (job_sender, result_receiver);
+}
+
+/// Files and directories `list_files` couldn't read, with the reason
+pub type UnreadablePaths = Vec<(PathBuf, io::Error)>;
 
-        let hash = hash_file(file)?;
+/// Every file a scan with these options would consider, with its metadata, without reading
+/// any of them, and the files and directories that couldn't be read
+pub fn list_files(
+    starting_dir: &Path,
+    options: &ScanOptions,
+) -> Result<(Vec<(PathBuf, Metadata)>, UnreadablePaths), AppError> {
+    let (sender, receiver) = sync_channel(WALK_QUEUE_SIZE);
+
+    return thread::scope(|scope| {
+        scope.spawn(move || walk_folders(starting_dir, options, sender));
 
-        match entry_position {
-            Ok(entry_position) => {
-                if let Some(entry) = hash_data.get_mut(entry_position) {
-                    entry.hash = hash
+        let mut files: Vec<(PathBuf, Metadata)> = Vec::default();
+        let mut unreadable: UnreadablePaths = Vec::default();
+
+        for item in receiver {
+            let (path, metadata) = match item? {
+                ScanItem::File(path) => match with_retries(options.retries, || path.metadata()) {
+                    Ok(metadata) => (path, metadata),
+                    Err(err) => {
+                        unreadable.push((path, err));
+                        continue;
+                    }
+                },
+                ScanItem::AnnexLink(path, metadata, _, _) => (path, metadata),
+                ScanItem::StatFailed(path, err) | ScanItem::DirectoryError(path, err) => {
+                    unreadable.push((path, err));
+                    continue;
                 }
+                _ => continue,
+            };
+
+            if metadata.len() >= options.scan_minimum {
+                files.push((path, metadata));
             }
-            Err(entry_position) => {
-                hash_data.insert(
-                    entry_position,
-                    FileEntry {
-                        file_name,
-                        file_size,
-                        modified,
-                        hash,
-                    },
+        }
+
+        return Ok((files, unreadable));
+    });
+}
+
+fn walk_folders(
+    starting_dir: &Path,
+    options: &ScanOptions,
+    sender: SyncSender<Result<ScanItem, AppError>>,
+) {
+    let mut pending_directories_list: Vec<(PathBuf, Option<Arc<IgnoreFiles>>)> =
+        vec![(starting_dir.into(), None)];
+    let mut git_tracked: HashSet<PathBuf> = HashSet::default();
+
+    while let Some((current_directory, ignore_files)) = pending_directories_list.pop() {
+        let (ignore_files, ignore_err) =
+            IgnoreFiles::for_directory(ignore_files, &current_directory);
+
+        if let Some(err) = ignore_err
+            && sender
+                .send(Ok(ScanItem::IgnoreFileError(
+                    current_directory.join(IGNORE_FILENAME),
+                    err,
+                )))
+                .is_err()
+        {
+            return;
+        }
+
+        if options.git_aware
+            && current_directory.join(GIT_DIRECTORY).exists()
+            && sender
+                .send(Ok(read_git_repository(
+                    &current_directory,
+                    options,
+                    &mut git_tracked,
+                )))
+                .is_err()
+        {
+            return;
+        }
+
+        let items = match read_folder(
+            starting_dir,
+            &current_directory,
+            options,
+            &git_tracked,
+            ignore_files.as_deref(),
+        ) {
+            Ok((items, subdirectory_list)) => {
+                pending_directories_list.extend(
+                    subdirectory_list
+                        .into_iter()
+                        .map(|subdirectory| (subdirectory, ignore_files.clone())),
                 );
+                items
+            }
+            Err(err) => vec![Err(err)],
+        };
+
+        for item in items {
+            let is_err = item.is_err();
+
+            if sender.send(item).is_err() || is_err {
+                return;
             }
         }
     }
+}
+
+fn send_listed_files(files: &[PathBuf], sender: SyncSender<Result<ScanItem, AppError>>) {
+    for file in files {
+        if sender.send(Ok(ScanItem::File(file.clone()))).is_err() {
+            return;
+        }
+    }
+}
+
+/// Note a repository found by the walk, adding the files it tracks to those left out with
+/// `skip_git_tracked`
+fn read_git_repository(
+    repository: &Path,
+    options: &ScanOptions,
+    git_tracked: &mut HashSet<PathBuf>,
+) -> ScanItem {
+    if options.skip_git_tracked {
+        match tracked_files(repository) {
+            Ok(files) => git_tracked.extend(files),
+            Err(err) => return ScanItem::GitError(repository.to_owned(), err),
+        }
+    }
 
-    return Ok(subdirectory_list);
+    return ScanItem::GitRepository(repository.to_owned(), head_commit(repository));
 }
 
-fn hash_file(file: File) -> Result<String, AppError> {
-    let mut reader = BufReader::new(file);
+/// Whether a file belongs to `owner`, always true without one
+#[cfg(unix)]
+fn is_owned_by(metadata: &Metadata, owner: Option<u32>) -> bool {
+    use std::os::unix::fs::MetadataExt;
 
-    let mut hasher = Sha256::default();
+    return owner.is_none_or(|owner| metadata.uid() == owner);
+}
 
-    let mut buffer = [0u8; 8192];
-    loop {
-        check_exit_key_pressed()?;
+#[cfg(not(unix))]
+fn is_owned_by(_metadata: &Metadata, _owner: Option<u32>) -> bool {
+    return true;
+}
 
-        let n = reader.read(&mut buffer).app_err()?;
-        if n == 0 {
-            break;
+type FolderItems = (Vec<Result<ScanItem, AppError>>, Vec<PathBuf>);
+
+fn read_folder(
+    starting_dir: &Path,
+    current_path: &Path,
+    options: &ScanOptions,
+    git_tracked: &HashSet<PathBuf>,
+    ignore_files: Option<&IgnoreFiles>,
+) -> Result<FolderItems, AppError> {
+    let mut items: Vec<Result<ScanItem, AppError>> = Vec::default();
+    let mut file_list: Vec<(PathBuf, u64)> = Vec::default();
+    let mut subdirectory_list: Vec<PathBuf> = Vec::default();
+    let mut is_empty = true;
+
+    let dir_reader = or_else!(
+        read_dir(current_path),
+        err => {
+            let other_owner = err.kind() == ErrorKind::PermissionDenied
+                && current_path
+                    .metadata()
+                    .is_ok_and(|metadata| !is_owned_by(&metadata, options.owner));
+
+            if other_owner {
+                items.push(Ok(ScanItem::OtherOwner));
+            } else {
+                items.push(Ok(ScanItem::DirectoryError(current_path.to_owned(), err)));
+            }
+
+            return Ok((items, subdirectory_list));
         }
-        hasher.update(&buffer[..n]);
+    );
+
+    for current_entry in dir_reader {
+        check_exit_key_pressed()?;
+
+        is_empty = false;
+
+        match current_entry {
+            Err(err) => items.push(Ok(ScanItem::EntryError(err))),
+            Ok(entry) => {
+                let path = entry.path();
+
+                let annex_link = if entry
+                    .file_type()
+                    .is_ok_and(|file_type| file_type.is_symlink())
+                {
+                    read_annex_link(&path)
+                } else {
+                    None
+                };
+
+                // Follows symlinks like is_dir and is_file, broken links other than annex links
+                // are skipped
+                let metadata = match annex_link {
+                    Some(_) => with_retries(options.retries, || path.symlink_metadata()),
+                    None => with_retries(options.retries, || path.metadata()),
+                };
+
+                // Anything else that can't be read is reported, keeping its previous entries
+                // rather than purging them as missing
+                let metadata = match metadata {
+                    Ok(metadata) => metadata,
+                    Err(err) if err.kind() == ErrorKind::NotFound => continue,
+                    Err(err) => {
+                        let is_dir = entry
+                            .file_type()
+                            .is_ok_and(|file_type| file_type.is_dir());
+
+                        if !options.path_filter.is_excluded(starting_dir, &path, is_dir) {
+                            items.push(Ok(if is_dir {
+                                ScanItem::DirectoryError(path, err)
+                            } else {
+                                ScanItem::StatFailed(path, err)
+                            }));
+                        }
+
+                        continue;
+                    }
+                };
+                let file_type = annex_link.is_none().then(|| metadata.file_type());
+                let is_dir = file_type.is_some_and(|file_type| file_type.is_dir());
+
+                if options.path_filter.is_excluded(starting_dir, &path, is_dir) {
+                    continue;
+                }
+
+                if ignore_files.is_some_and(|ignore_files| ignore_files.is_ignored(&path, is_dir)) {
+                    continue;
+                }
+
+                if is_dir && entry.file_name() == STATE_DIRECTORY {
+                    continue;
+                }
+
+                // A file in worktrees and submodules
+                if options.git_aware && entry.file_name() == GIT_DIRECTORY {
+                    continue;
+                }
+
+                if options.skip_hidden && entry.file_name().as_encoded_bytes().starts_with(b".") {
+                    items.push(Ok(ScanItem::Hidden));
+                    continue;
+                }
+
+                match file_type {
+                    Some(file_type) if file_type.is_dir() => {
+                        if options.skip_nested_roots && is_scan_root(&path) {
+                            items.push(Ok(ScanItem::NestedRoot(path)));
+                        } else {
+                            subdirectory_list.push(path);
+                        }
+                    }
+                    Some(file_type) if !file_type.is_file() => {
+                        items.push(Ok(ScanItem::SpecialFile(
+                            path,
+                            special_file_kind(&file_type),
+                        )));
+                    }
+                    _ => {
+                        if !options.path_filter.is_included(starting_dir, &path) {
+                            continue;
+                        }
+
+                        if git_tracked.contains(&path) {
+                            items.push(Ok(ScanItem::GitTracked));
+                            continue;
+                        }
+
+                        if !is_owned_by(&metadata, options.owner) {
+                            items.push(Ok(ScanItem::OtherOwner));
+                            continue;
+                        }
+
+                        match annex_link {
+                            Some((hash, pointer)) => {
+                                items.push(Ok(ScanItem::AnnexLink(path, metadata, hash, pointer)));
+                            }
+                            None => file_list.push((path, physical_order_hint(&entry))),
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    if is_empty {
+        items.push(Ok(ScanItem::EmptyDirectory(current_path.to_owned())));
+    }
+
+    if options.hdd_mode {
+        file_list.sort_unstable_by_key(|(_, physical_order)| *physical_order);
+    } else {
+        file_list.sort_unstable();
+    }
+    subdirectory_list.sort_unstable_by(|a, b| b.cmp(a));
+
+    items.extend(
+        file_list
+            .into_iter()
+            .map(|(path, _)| Ok(ScanItem::File(path))),
+    );
+
+    return Ok((items, subdirectory_list));
+}
+
+fn stat_files(
+    receiver: Receiver<Result<ScanItem, AppError>>,
+    sender: SyncSender<Result<ScanItem, AppError>>,
+    retries: u32,
+) {
+    for item in receiver {
+        let item = match item {
+            Ok(ScanItem::File(path)) => match with_retries(retries, || path.metadata()) {
+                Ok(metadata) => Ok(ScanItem::Stat(path, metadata)),
+                Err(err) => Ok(ScanItem::StatFailed(path, err)),
+            },
+            item => item,
+        };
+
+        if sender.send(item).is_err() {
+            return;
+        }
+    }
+}
+
+fn open_files(
+    receiver: Receiver<HashJob>,
+    sender: SyncSender<(File, FileEntry)>,
+    result_sender: Sender<Result<HashOutcome, AppError>>,
+    retries: u32,
+    in_use_detector: Option<&InUseDetector>,
+) {
+    for HashJob { path, entry } in receiver {
+        match with_retries(retries, || OpenOptions::new().read(true).open(&path)) {
+            Ok(file) if in_use_detector.is_some_and(|detector| detector.is_in_use(&file)) => {
+                if result_sender.send(Ok(HashOutcome::InUse(entry))).is_err() {
+                    return;
+                }
+            }
+            Ok(file) => {
+                if sender.send((file, entry)).is_err() {
+                    return;
+                }
+            }
+            Err(err) => {
+                if result_sender
+                    .send(Ok(HashOutcome::OpenFailed(entry.file_name.into(), err)))
+                    .is_err()
+                {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+fn hash_files(
+    receiver: Arc<Mutex<Receiver<(File, FileEntry)>>>,
+    sender: Sender<Result<HashOutcome, AppError>>,
+    options: &ScanOptions,
+    max_buffer_size: usize,
+    progress: &ScanProgress,
+    worker_progress: &ProgressBar,
+) {
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    let batch_size = if options.io_uring {
+        IO_URING_BATCH_SIZE
+    } else {
+        1
+    };
+
+    #[cfg(not(all(target_os = "linux", feature = "io-uring")))]
+    let batch_size = 1;
+
+    loop {
+        let mut batch: Vec<(File, FileEntry)> = Vec::with_capacity(batch_size);
+
+        {
+            let receiver = or_else!(receiver.lock(), _ => return);
+
+            match receiver.recv() {
+                Ok(opened) => batch.push(opened),
+                Err(_) => return,
+            }
+
+            while batch.len() < batch_size
+                && let Ok(opened) = receiver.try_recv()
+            {
+                batch.push(opened);
+            }
+        }
+
+        match batch.as_slice() {
+            [(_, entry)] => progress.show_file(worker_progress, entry.file_name.clone()),
+            batch => progress.show_file(worker_progress, format!("{} files", batch.len())),
+        }
+
+        match hash_batch(batch, options, max_buffer_size, progress.overall()) {
+            Ok(outcomes) => {
+                for outcome in outcomes {
+                    if sender.send(Ok(outcome)).is_err() {
+                        return;
+                    }
+                }
+            }
+            Err(err) => {
+                _ = sender.send(Err(err));
+                return;
+            }
+        }
+    }
+}
+
+fn hash_batch(
+    batch: Vec<(File, FileEntry)>,
+    options: &ScanOptions,
+    max_buffer_size: usize,
+    progress: &ProgressBar,
+) -> Result<Vec<HashOutcome>, AppError> {
+    let mut outcomes: Vec<HashOutcome> = Vec::with_capacity(batch.len());
+
+    let mut files: Vec<(File, FileEntry)> = Vec::with_capacity(batch.len());
+
+    for (mut file, mut entry) in batch {
+        match read_pointer(&mut file, &mut entry) {
+            Ok(true) => outcomes.push(HashOutcome::Hashed(entry, Duration::ZERO)),
+            Ok(false) => files.push((file, entry)),
+            Err(AppError::Caught(err)) => outcomes.push(HashOutcome::ReadFailed(entry, err)),
+            Err(err) => return Err(err),
+        }
+    }
+
+    let batch = files;
+
+    let buffer_size = |file_size: u64| {
+        options
+            .buffer_size
+            .map(|buffer_size| usize::try_from(buffer_size).unwrap_or(usize::MAX))
+            .unwrap_or_else(|| read_buffer_size(file_size, max_buffer_size))
+    };
+
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    if options.io_uring && batch.len() > 1 {
+        let files: Vec<(&File, usize)> = batch
+            .iter()
+            .map(|(file, entry)| (file, buffer_size(entry.file_size)))
+            .collect();
+
+        let started = Instant::now();
+
+        match crate::uring::hash_files(&files, &options.hasher, progress) {
+            Ok(hashes) => {
+                let duration = started.elapsed();
+
+                outcomes.extend(
+                    batch
+                        .into_iter()
+                        .zip(hashes)
+                        .map(|((_, mut entry), digests)| {
+                            entry.hash = digests.hash;
+                            entry.also_hashes = digests.also;
+                            HashOutcome::Hashed(entry, duration)
+                        }),
+                );
+
+                return Ok(outcomes);
+            }
+            // Reads use explicit offsets, so each file can be hashed again on its own with retries
+            Err(AppError::Caught(_)) => {}
+            Err(err) => return Err(err),
+        }
+    }
+
+    for (file, mut entry) in batch {
+        let started = Instant::now();
+
+        let digests = if options.mmap && entry.file_size >= MMAP_MIN_SIZE {
+            hash_file_mapped(&file, options.hasher.create(), progress)
+        } else {
+            hash_file(
+                file,
+                options.hasher.create(),
+                buffer_size(entry.file_size),
+                options.retries,
+                progress,
+            )
+        };
+
+        // Caught errors come from the file itself, anything else is an abort request
+        match digests.map_err(|err| err.with_subject("Reading", Path::new(&entry.file_name))) {
+            Ok(digests) => {
+                entry.hash = digests.hash;
+                entry.also_hashes = digests.also;
+                outcomes.push(HashOutcome::Hashed(entry, started.elapsed()));
+            }
+            Err(AppError::Caught(err)) => outcomes.push(HashOutcome::ReadFailed(entry, err)),
+            Err(err) => return Err(err),
+        }
+    }
+
+    return Ok(outcomes);
+}
+
+#[allow(clippy::too_many_arguments)]
+fn process_scan_items(
+    progress: &ScanProgress,
+    starting_dir: &Path,
+    hash_data: &mut [FileEntry],
+    new_entries: &mut Vec<FileEntry>,
+    summary: &mut ScanSummary,
+    options: &ScanOptions,
+    receiver: Receiver<Result<ScanItem, AppError>>,
+    job_sender: SyncSender<HashJob>,
+    result_receiver: Receiver<Result<HashOutcome, AppError>>,
+) -> Result<(), AppError> {
+    let mut discovered: usize = 0;
+    let mut completed: usize = 0;
+
+    let mut hash_log = options.log_file.as_deref().map(HashLog::open).transpose()?;
+
+    // First path seen for each hash, so new files with known content can be reported right away
+    let mut known_hashes: Option<HashMap<String, String>> = options.live_duplicates.then(|| {
+        hash_data
+            .iter()
+            .map(|entry| (entry.hash.clone(), entry.file_name.clone()))
+            .collect()
+    });
+
+    // Known hashes by device and inode, so a file found under a new path after a rename or a
+    // move to another directory on the same file system keeps its hash instead of being read
+    // again. Entries saved before devices were recorded have device 0.
+    let moved_hashes: HashMap<(u64, u64), MovedHash> = hash_data
+        .iter()
+        .filter(|entry| entry.inode != 0)
+        .map(|entry| {
+            (
+                (entry.device, entry.inode),
+                MovedHash {
+                    file_size: entry.file_size,
+                    modified: entry.modified,
+                    modified_nanos: entry.modified_nanos,
+                    hash: entry.hash.clone(),
+                    also_hashes: entry.also_hashes.clone(),
+                    pointer: entry.pointer.clone(),
+                },
+            )
+        })
+        .collect();
+
+    for item in receiver {
+        while let Ok(result) = result_receiver.try_recv() {
+            process_hash_outcome(
+                progress,
+                hash_data,
+                new_entries,
+                summary,
+                &mut known_hashes,
+                &mut hash_log,
+                result?,
+            );
+            completed += 1;
+        }
+
+        let (path, metadata, annex_link) =
+            match note_scan_item(progress, summary, starting_dir, options, item?) {
+                WalkOutcome::Found(path, metadata, annex_link) => (path, metadata, annex_link),
+                WalkOutcome::Failed(file_name) => {
+                    keep_previous_entry(hash_data, &file_name);
+                    continue;
+                }
+                WalkOutcome::Unreadable(path) => {
+                    for entry in hash_data.iter_mut() {
+                        if Path::new(&entry.file_name).starts_with(&path) {
+                            entry.seen = true;
+                        }
+                    }
+
+                    continue;
+                }
+                WalkOutcome::Noted => continue,
+            };
+
+        discovered += 1;
+
+        progress.set_file_counts(completed, discovered);
+
+        let file_name = stored_path(starting_dir, options, path.clone())
+            .to_string_lossy()
+            .to_string();
+
+        let entry_position = hash_data.binary_search_by_key(&&file_name, |entry| &entry.file_name);
+
+        if let Ok(entry_position) = entry_position
+            && let Some(entry) = hash_data.get_mut(entry_position)
+        {
+            entry.seen = true;
+        }
+
+        // Skipped with a warning, the previous hash is kept until the time can be read again
+        let modified = or_else!(modified_time(&metadata), err => {
+            record_failure(progress, summary, file_name, &err);
+            completed += 1;
+            continue;
+        });
+
+        let mut entry = new_entry(file_name, &metadata, modified);
+
+        if let Ok(entry_position) = entry_position
+            && let Some(stored) = hash_data.get_mut(entry_position)
+            && is_unchanged(stored, &entry, options)
+        {
+            refresh_entry(stored, &entry);
+            completed += 1;
+            continue;
+        }
+
+        if let Some((hash, pointer)) = annex_link {
+            entry.hash = hash;
+            entry.pointer = Some(pointer);
+
+            if store_entry(hash_data, new_entries, entry) {
+                summary.changed_files += 1;
+            } else {
+                summary.new_files += 1;
+            }
+
+            completed += 1;
+            continue;
+        }
+
+        if entry_position.is_err()
+            && entry.inode != 0
+            && let Some(moved) = moved_hashes
+                .get(&(entry.device, entry.inode))
+                .or_else(|| moved_hashes.get(&(0, entry.inode)))
+            && moved.file_size == entry.file_size
+            && moved.modified == entry.modified
+            && moved
+                .modified_nanos
+                .is_none_or(|nanos| Some(nanos) == entry.modified_nanos)
+            && has_also_hashes(&moved.also_hashes, &options.hasher)
+        {
+            entry.hash = moved.hash.clone();
+            entry.also_hashes = moved.also_hashes.clone();
+            entry.pointer = moved.pointer.clone();
+            store_entry(hash_data, new_entries, entry);
+            summary.moved_files += 1;
+            completed += 1;
+            continue;
+        }
+
+        if let Some(hash) = shared_cache_hash(&entry, options) {
+            entry.hash = hash;
+
+            if store_entry(hash_data, new_entries, entry) {
+                summary.changed_files += 1;
+            } else {
+                summary.new_files += 1;
+            }
+
+            completed += 1;
+            continue;
+        }
+
+        progress.overall().inc_length(entry.file_size);
+
+        let job = HashJob { path, entry };
+
+        if job_sender.send(job).is_err() {
+            break;
+        }
+    }
+
+    drop(job_sender);
+
+    for result in result_receiver {
+        process_hash_outcome(
+            progress,
+            hash_data,
+            new_entries,
+            summary,
+            &mut known_hashes,
+            &mut hash_log,
+            result?,
+        );
+        completed += 1;
+
+        progress.set_file_counts(completed, discovered);
+    }
+
+    return Ok(());
+}
+
+/// What a scan does with an item from the walk once it's noted in the summary
+#[allow(clippy::large_enum_variant)]
+enum WalkOutcome {
+    /// A file to check against the hash data, with the hash and pointer of a git-annex link
+    Found(PathBuf, Metadata, Option<(String, Pointer)>),
+    /// A file that couldn't be checked, whose previous entry is kept
+    Failed(String),
+    /// A directory that couldn't be read, whose previous entries are kept
+    Unreadable(PathBuf),
+    Noted,
+}
+
+/// Count or list an item from the walk in the summary, passing on the files to check
+fn note_scan_item(
+    progress: &ScanProgress,
+    summary: &mut ScanSummary,
+    starting_dir: &Path,
+    options: &ScanOptions,
+    item: ScanItem,
+) -> WalkOutcome {
+    let (path, metadata, annex_link) = match item {
+        ScanItem::DirectoryError(path, err) => {
+            let path = stored_path(starting_dir, options, path);
+
+            if err.kind() == ErrorKind::PermissionDenied {
+                summary
+                    .unreadable_paths
+                    .push(path.to_string_lossy().to_string());
+            } else {
+                progress.println(format!(
+                    "Error reading directory {}: {}",
+                    path.to_string_lossy(),
+                    err
+                ));
+            }
+
+            return WalkOutcome::Unreadable(path);
+        }
+        ScanItem::EntryError(err) => {
+            progress.println(format!("Error reading directory entry: {err:?}"));
+            return WalkOutcome::Noted;
+        }
+        ScanItem::StatFailed(path, err) => {
+            let file_name = stored_path(starting_dir, options, path)
+                .to_string_lossy()
+                .to_string();
+
+            record_failure(progress, summary, file_name.clone(), &err);
+            return WalkOutcome::Failed(file_name);
+        }
+        ScanItem::EmptyDirectory(path) => {
+            summary
+                .empty_directories
+                .push(stored_path(starting_dir, options, path));
+            return WalkOutcome::Noted;
+        }
+        ScanItem::SpecialFile(path, kind) => {
+            summary
+                .special_files
+                .push((stored_path(starting_dir, options, path), kind));
+            return WalkOutcome::Noted;
+        }
+        ScanItem::Hidden => {
+            summary.hidden_skipped += 1;
+            return WalkOutcome::Noted;
+        }
+        ScanItem::OtherOwner => {
+            summary.other_owner_skipped += 1;
+            return WalkOutcome::Noted;
+        }
+        ScanItem::GitRepository(path, head) => {
+            summary
+                .git_repositories
+                .push((stored_path(starting_dir, options, path), head));
+            return WalkOutcome::Noted;
+        }
+        ScanItem::GitError(path, err) => {
+            progress.println(format!(
+                "Could not list the files git tracks in {}, scanning them all: {err}",
+                path.to_string_lossy()
+            ));
+            return WalkOutcome::Noted;
+        }
+        ScanItem::GitTracked => {
+            summary.git_tracked_skipped += 1;
+            return WalkOutcome::Noted;
+        }
+        ScanItem::NestedRoot(path) => {
+            summary
+                .nested_roots
+                .push(stored_path(starting_dir, options, path));
+            return WalkOutcome::Noted;
+        }
+        ScanItem::IgnoreFileError(path, err) => {
+            progress.println(format!("Error reading {}: {err}", path.to_string_lossy()));
+            return WalkOutcome::Noted;
+        }
+        ScanItem::File(path) => match path.metadata() {
+            Ok(metadata) => (path, metadata, None),
+            Err(err) => {
+                let file_name = stored_path(starting_dir, options, path)
+                    .to_string_lossy()
+                    .to_string();
+
+                record_failure(progress, summary, file_name.clone(), &err);
+                return WalkOutcome::Failed(file_name);
+            }
+        },
+        ScanItem::Stat(path, metadata) => (path, metadata, None),
+        ScanItem::AnnexLink(path, metadata, hash, pointer) => {
+            (path, metadata, Some((hash, pointer)))
+        }
+    };
+
+    // Replaced by a FIFO or device since the directory was read, opening it could block
+    if annex_link.is_none() && !metadata.is_file() {
+        summary.special_files.push((
+            stored_path(starting_dir, options, path),
+            special_file_kind(&metadata.file_type()),
+        ));
+        return WalkOutcome::Noted;
+    }
+
+    if metadata.len() < options.scan_minimum {
+        return WalkOutcome::Noted;
+    }
+
+    return WalkOutcome::Found(path, metadata, annex_link);
+}
+
+/// The path a walked file is stored under, which differs from where it was found when walking
+/// a snapshot
+fn stored_path(starting_dir: &Path, options: &ScanOptions, path: PathBuf) -> PathBuf {
+    return match &options.walk_root {
+        Some(walk_root) => match path.strip_prefix(walk_root) {
+            Ok(relative) => starting_dir.join(relative),
+            Err(_) => path,
+        },
+        None => path,
+    };
+}
+
+fn modified_time(metadata: &Metadata) -> io::Result<Duration> {
+    return metadata.modified().and_then(|modified| {
+        modified
+            .duration_since(UNIX_EPOCH)
+            .map_err(|_| io::Error::other("modified time is before 1970"))
+    });
+}
+
+/// An entry for a file found by the walk, yet to be hashed
+fn new_entry(file_name: String, metadata: &Metadata, modified: Duration) -> FileEntry {
+    let (ctime, inode, device) = file_identity(metadata);
+
+    return FileEntry {
+        file_name,
+        file_size: metadata.len(),
+        hash: String::default(),
+        modified: modified.as_secs(),
+        modified_nanos: Some(modified.subsec_nanos()),
+        ctime,
+        inode,
+        device,
+        also_hashes: BTreeMap::default(),
+        pointer: None,
+        last_verified: None,
+        last_seen: Some(unix_now()),
+        seen: true,
+    };
+}
+
+/// Whether a stored entry still holds for the file found at its path, so it needn't be hashed
+fn is_unchanged(stored: &FileEntry, found: &FileEntry, options: &ScanOptions) -> bool {
+    return stored.file_size == found.file_size
+        && stored.modified == found.modified
+        && stored
+            .modified_nanos
+            .is_none_or(|nanos| Some(nanos) == found.modified_nanos)
+        && is_identity_unchanged(stored, found.ctime, found.inode, options.change_detection)
+        && has_also_hashes(&stored.also_hashes, &options.hasher);
+}
+
+/// Bring a stored entry of an unchanged file up to date with the file found
+fn refresh_entry(stored: &mut FileEntry, found: &FileEntry) {
+    if found.last_seen.unwrap_or_default() >= stored.last_seen.unwrap_or_default() + LAST_SEEN_STEP
+    {
+        stored.last_seen = found.last_seen;
+    }
+
+    stored.modified_nanos = found.modified_nanos;
+    stored.ctime = found.ctime;
+    stored.inode = found.inode;
+    stored.device = found.device;
+}
+
+/// The hash another scan on this machine stored for the same device, inode, size and modified
+/// time. The shared cache only holds the main digest.
+fn shared_cache_hash(entry: &FileEntry, options: &ScanOptions) -> Option<String> {
+    let shared_cache = options.shared_cache.as_ref()?;
+
+    if entry.inode == 0 || !options.hasher.also().is_empty() {
+        return None;
+    }
+
+    return shared_cache
+        .lookup(
+            entry.device,
+            entry.inode,
+            entry.file_size,
+            entry.modified,
+            entry.modified_nanos.unwrap_or_default(),
+        )
+        .map(String::from);
+}
+
+fn process_hash_outcome(
+    progress: &ScanProgress,
+    hash_data: &mut [FileEntry],
+    new_entries: &mut Vec<FileEntry>,
+    summary: &mut ScanSummary,
+    known_hashes: &mut Option<HashMap<String, String>>,
+    hash_log: &mut Option<HashLog>,
+    outcome: HashOutcome,
+) {
+    match outcome {
+        HashOutcome::Hashed(entry, duration) => {
+            note_hashed(progress, summary, hash_log, &entry, duration);
+
+            // Empty files all share one hash and aren't worth flagging
+            if let Some(known_hashes) = known_hashes
+                && entry.file_size > 0
+            {
+                match known_hashes.get(&entry.hash) {
+                    Some(existing) if *existing != entry.file_name => {
+                        progress.println(format!(
+                            "Duplicate: {} matches {}",
+                            entry.file_name, existing
+                        ));
+                    }
+                    Some(_) => {}
+                    None => {
+                        known_hashes.insert(entry.hash.clone(), entry.file_name.clone());
+                    }
+                }
+            }
+
+            if store_entry(hash_data, new_entries, entry) {
+                summary.changed_files += 1;
+            } else {
+                summary.new_files += 1;
+            }
+        }
+        outcome => note_hash_failure(progress, summary, outcome),
+    }
+}
+
+fn note_hashed(
+    progress: &ScanProgress,
+    summary: &mut ScanSummary,
+    hash_log: &mut Option<HashLog>,
+    entry: &FileEntry,
+    duration: Duration,
+) {
+    if let Some(log) = hash_log.as_mut()
+        && let Err(err) = log.record(entry, duration)
+    {
+        progress.println(format!("Stopped writing the log file: {err}"));
+        *hash_log = None;
+    }
+
+    summary.hashed_bytes += entry.file_size;
+}
+
+fn note_hash_failure(progress: &ScanProgress, summary: &mut ScanSummary, outcome: HashOutcome) {
+    match outcome {
+        HashOutcome::Hashed(..) => {}
+        HashOutcome::OpenFailed(path, err) => {
+            record_failure(progress, summary, path.to_string_lossy().to_string(), &err);
+        }
+        HashOutcome::ReadFailed(entry, err) => {
+            progress.println(format!(
+                "Error reading file {}: {}",
+                entry.file_name, err.error
+            ));
+
+            summary
+                .failed_files
+                .push((entry.file_name, err.error.to_string()));
+        }
+        HashOutcome::InUse(entry) => summary.in_use_files.push(entry.file_name),
+    }
+}
+
+/// Permission problems are expected when scanning system folders, they are only counted and
+/// listed at the end instead of printed over the progress display
+fn record_failure(
+    progress: &ScanProgress,
+    summary: &mut ScanSummary,
+    file_name: String,
+    err: &io::Error,
+) {
+    if err.kind() == ErrorKind::PermissionDenied {
+        summary.unreadable_paths.push(file_name);
+        return;
+    }
+
+    progress.println(format!("Error reading file {file_name}: {err}"));
+
+    summary.failed_files.push((file_name, err.to_string()));
+}
+
+/// Record a Git LFS pointer with the hash of the file it refers to in place of its own,
+/// returning false for any other file, which is left to be hashed
+fn read_pointer(file: &mut File, entry: &mut FileEntry) -> Result<bool, AppError> {
+    if entry.file_size > LFS_POINTER_MAX_SIZE {
+        return Ok(false);
+    }
+
+    let pointer = read_lfs_pointer(&mut *file);
+
+    file.rewind()
+        .app_err_at("Reading", Path::new(&entry.file_name))?;
+
+    let (hash, pointer) = or_else!(pointer, none => return Ok(false));
+
+    entry.hash = hash;
+    entry.pointer = Some(pointer);
+
+    return Ok(true);
+}
+
+/// Whether a stored entry has every digest `--also-hash` asks for, so turning it on rehashes
+/// files scanned without it
+fn has_also_hashes(also_hashes: &BTreeMap<String, String>, hasher: &HasherFactory) -> bool {
+    return hasher
+        .also()
+        .iter()
+        .all(|algorithm| also_hashes.contains_key(algorithm));
+}
+
+/// Keep the previous hash of a file that couldn't be checked rather than purging a file that is
+/// still there
+fn keep_previous_entry(hash_data: &mut [FileEntry], file_name: &str) {
+    if let Ok(entry_position) =
+        hash_data.binary_search_by_key(&file_name, |entry| entry.file_name.as_str())
+        && let Some(entry) = hash_data.get_mut(entry_position)
+    {
+        entry.seen = true;
+    }
+}
+
+/// Replace the entry for a path, or set a new path aside to be merged in after the scan,
+/// returning whether it replaced one
+fn store_entry(
+    hash_data: &mut [FileEntry],
+    new_entries: &mut Vec<FileEntry>,
+    new_entry: FileEntry,
+) -> bool {
+    match hash_data.binary_search_by_key(&&new_entry.file_name, |entry| &entry.file_name) {
+        Ok(entry_position) => {
+            if let Some(entry) = hash_data.get_mut(entry_position) {
+                *entry = new_entry;
+            }
+
+            return true;
+        }
+        Err(_) => {
+            new_entries.push(new_entry);
+
+            return false;
+        }
+    }
+}
+
+/// Merge the paths found by a scan into the sorted hash data. Inserting each one in place
+/// shifts everything after it, which is quadratic on trees with millions of new files, while
+/// the stable sort merges the two sorted runs in linear time.
+fn merge_new_entries(hash_data: &mut Vec<FileEntry>, mut new_entries: Vec<FileEntry>) {
+    if new_entries.is_empty() {
+        return;
+    }
+
+    new_entries.sort_unstable_by(|a, b| a.file_name.cmp(&b.file_name));
+
+    hash_data.append(&mut new_entries);
+    hash_data.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+}
+
+/// Retry an I/O operation with exponential backoff, for flaky disks that fail intermittently
+fn with_retries<T>(retries: u32, mut operation: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+    let mut attempt = 0;
+
+    loop {
+        match operation() {
+            Err(err) if attempt < retries && is_transient(&err) => {
+                thread::sleep(RETRY_BASE_DELAY * 2u32.pow(attempt));
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn is_transient(err: &io::Error) -> bool {
+    return !matches!(
+        err.kind(),
+        ErrorKind::NotFound
+            | ErrorKind::PermissionDenied
+            | ErrorKind::InvalidInput
+            | ErrorKind::Unsupported
+    );
+}
+
+fn is_identity_unchanged(
+    entry: &FileEntry,
+    ctime: u64,
+    inode: u64,
+    change_detection: ChangeDetection,
+) -> bool {
+    match change_detection {
+        ChangeDetection::Relaxed => true,
+        ChangeDetection::Strict => {
+            (entry.ctime == 0 || entry.ctime == ctime) && (entry.inode == 0 || entry.inode == inode)
+        }
+    }
+}
+
+#[cfg(unix)]
+fn physical_order_hint(entry: &DirEntry) -> u64 {
+    use std::os::unix::fs::DirEntryExt;
+
+    return entry.ino();
+}
+
+#[cfg(not(unix))]
+fn physical_order_hint(_entry: &DirEntry) -> u64 {
+    return 0;
+}
+
+#[cfg(unix)]
+fn special_file_kind(file_type: &FileType) -> &'static str {
+    use std::os::unix::fs::FileTypeExt;
+
+    if file_type.is_fifo() {
+        return "fifo";
+    }
+
+    if file_type.is_socket() {
+        return "socket";
+    }
+
+    if file_type.is_block_device() {
+        return "block device";
+    }
+
+    if file_type.is_char_device() {
+        return "character device";
+    }
+
+    return "special file";
+}
+
+#[cfg(not(unix))]
+fn special_file_kind(_file_type: &FileType) -> &'static str {
+    return "special file";
+}
+
+/// Change time, inode and device of a file
+#[cfg(unix)]
+fn file_identity(metadata: &Metadata) -> (u64, u64, u64) {
+    use std::os::unix::fs::MetadataExt;
+
+    return (
+        metadata.ctime().try_into().unwrap_or_default(),
+        metadata.ino(),
+        metadata.dev(),
+    );
+}
+
+#[cfg(not(unix))]
+fn file_identity(_metadata: &Metadata) -> (u64, u64, u64) {
+    return (0, 0, 0);
+}
+
+pub fn hash_file(
+    mut file: File,
+    mut hasher: Box<dyn Hasher>,
+    buffer_size: usize,
+    retries: u32,
+    progress: &ProgressBar,
+) -> Result<Digests, AppError> {
+    let mut buffer = vec![0u8; buffer_size.max(1)];
+    loop {
+        check_exit_key_pressed()?;
+        wait_while_paused()?;
+
+        let n = with_retries(retries, || file.read(&mut buffer)).app_err()?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+        progress.inc(n as u64);
+    }
+
+    Ok(hasher.finalize())
+}
+
+pub fn read_buffer_size(file_size: u64, max_buffer_size: usize) -> usize {
+    let file_size = usize::try_from(file_size).unwrap_or(usize::MAX);
+
+    return file_size
+        .clamp(MIN_READ_BUFFER_SIZE, max_buffer_size)
+        .next_power_of_two()
+        .min(max_buffer_size);
+}
+
+pub fn max_read_buffer_size(path: &Path) -> usize {
+    if is_rotational(path) {
+        return MAX_ROTATIONAL_READ_BUFFER_SIZE;
+    }
+
+    return MAX_READ_BUFFER_SIZE;
+}
+
+#[cfg(target_os = "linux")]
+fn is_rotational(path: &Path) -> bool {
+    use std::fs::read_to_string;
+    use std::os::unix::fs::MetadataExt;
+
+    let dev = or_else!(path.metadata(), _ => return false).dev();
+
+    let major = ((dev >> 32) & 0xffff_f000) | ((dev >> 8) & 0x0000_0fff);
+    let minor = ((dev >> 12) & 0xffff_ff00) | (dev & 0x0000_00ff);
+
+    let device_path = PathBuf::from(format!("/sys/dev/block/{major}:{minor}"));
+
+    // Partitions have no queue of their own, the parent device holds it
+    return [
+        device_path.join("queue/rotational"),
+        device_path.join("../queue/rotational"),
+    ]
+    .iter()
+    .find_map(|rotational_path| read_to_string(rotational_path).ok())
+    .is_some_and(|rotational| rotational.trim() == "1");
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_rotational(_path: &Path) -> bool {
+    return false;
+}
+
+fn hash_file_mapped(
+    file: &File,
+    mut hasher: Box<dyn Hasher>,
+    progress: &ProgressBar,
+) -> Result<Digests, AppError> {
+    // SAFETY: the mapping is read only and dropped before returning. Files truncated by
+    // another process while mapped can still fault, which is why this path is opt-in.
+    let map = unsafe { Mmap::map(file) }.app_err()?;
+
+    #[cfg(unix)]
+    map.advise(memmap2::Advice::Sequential).app_err()?;
+
+    for chunk in map.chunks(MMAP_CHUNK_SIZE) {
+        check_exit_key_pressed()?;
+        wait_while_paused()?;
+
+        hasher.update(chunk);
+        progress.inc(chunk.len() as u64);
     }
 
-    Ok(hex::encode(hasher.finalize()))
+    Ok(hasher.finalize())
 }
@@ -0,0 +1,224 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::io::stdout;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::byte_size::{ByteSize, Count};
+use crate::errors::{AppError, AppErrorResult};
+use crate::hash_data::{FileEntry, load_current_hash_data};
+use crate::utils::OutputFormat;
+
+const HISTOGRAM_BUCKETS: [(u64, &str); 9] = [
+    (1, "0B"),
+    (1_000, "<1KB"),
+    (10_000, "<10KB"),
+    (100_000, "<100KB"),
+    (1_000_000, "<1MB"),
+    (10_000_000, "<10MB"),
+    (100_000_000, "<100MB"),
+    (1_000_000_000, "<1GB"),
+    (10_000_000_000, "<10GB"),
+];
+
+#[derive(Serialize)]
+struct Stats {
+    total_files: usize,
+    total_bytes: u64,
+    histogram: Vec<SizeBucket>,
+    extensions: Vec<ExtensionStats>,
+    largest_files: Vec<LargestFile>,
+    most_duplicated: Vec<DuplicatedHash>,
+}
+
+#[derive(Serialize)]
+struct SizeBucket {
+    label: &'static str,
+    files: usize,
+    bytes: u64,
+}
+
+#[derive(Serialize)]
+struct ExtensionStats {
+    extension: String,
+    files: usize,
+    bytes: u64,
+}
+
+#[derive(Serialize)]
+struct LargestFile {
+    file_name: String,
+    file_size: u64,
+}
+
+#[derive(Serialize)]
+struct DuplicatedHash {
+    hash: String,
+    copies: usize,
+    file_size: u64,
+}
+
+pub fn stats(data_path: &Path, top: usize, format: OutputFormat) -> Result<(), AppError> {
+    let data_file = load_current_hash_data(data_path, false)?;
+
+    let stats = collect_stats(&data_file, top);
+
+    match format {
+        OutputFormat::Text => print_stats(&stats),
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(stdout(), &stats).app_err()?;
+            println!();
+        }
+    }
+
+    return Ok(());
+}
+
+fn collect_stats(data_file: &[FileEntry], top: usize) -> Stats {
+    let mut histogram: Vec<SizeBucket> = HISTOGRAM_BUCKETS
+        .iter()
+        .map(|(_, label)| SizeBucket {
+            label,
+            files: 0,
+            bytes: 0,
+        })
+        .chain([SizeBucket {
+            label: ">=10GB",
+            files: 0,
+            bytes: 0,
+        }])
+        .collect();
+
+    let mut extension_index: HashMap<String, ExtensionStats> = HashMap::new();
+    let mut hash_index: HashMap<&str, (usize, u64)> = HashMap::with_capacity(data_file.len());
+
+    for file in data_file {
+        let bucket = HISTOGRAM_BUCKETS
+            .iter()
+            .position(|(limit, _)| file.file_size < *limit)
+            .unwrap_or(HISTOGRAM_BUCKETS.len());
+
+        if let Some(bucket) = histogram.get_mut(bucket) {
+            bucket.files += 1;
+            bucket.bytes += file.file_size;
+        }
+
+        let extension = Path::new(&file.file_name)
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .unwrap_or_else(|| "(none)".into());
+
+        let extension_stats =
+            extension_index
+                .entry(extension.clone())
+                .or_insert_with(|| ExtensionStats {
+                    extension,
+                    files: 0,
+                    bytes: 0,
+                });
+
+        extension_stats.files += 1;
+        extension_stats.bytes += file.file_size;
+
+        let hash_group = hash_index.entry(&file.hash).or_insert((0, file.file_size));
+        hash_group.0 += 1;
+    }
+
+    let mut extensions: Vec<ExtensionStats> = extension_index.into_values().collect();
+    extensions.sort_unstable_by(|a, b| {
+        b.bytes
+            .cmp(&a.bytes)
+            .then_with(|| a.extension.cmp(&b.extension))
+    });
+
+    let mut largest_files: Vec<&FileEntry> = data_file.iter().collect();
+    largest_files.sort_by_key(|file| Reverse(file.file_size));
+
+    let largest_files = largest_files
+        .into_iter()
+        .take(top)
+        .map(|file| LargestFile {
+            file_name: file.file_name.clone(),
+            file_size: file.file_size,
+        })
+        .collect();
+
+    let mut most_duplicated: Vec<DuplicatedHash> = hash_index
+        .into_iter()
+        .filter(|(_, (copies, _))| *copies > 1)
+        .map(|(hash, (copies, file_size))| DuplicatedHash {
+            hash: hash.to_owned(),
+            copies,
+            file_size,
+        })
+        .collect();
+
+    most_duplicated.sort_unstable_by(|a, b| {
+        b.copies
+            .cmp(&a.copies)
+            .then_with(|| b.file_size.cmp(&a.file_size))
+            .then_with(|| a.hash.cmp(&b.hash))
+    });
+    most_duplicated.truncate(top);
+
+    return Stats {
+        total_files: data_file.len(),
+        total_bytes: data_file.iter().map(|file| file.file_size).sum(),
+        histogram,
+        extensions,
+        largest_files,
+        most_duplicated,
+    };
+}
+
+fn print_stats(stats: &Stats) {
+    println!(
+        "{} files {}",
+        Count(stats.total_files),
+        ByteSize(stats.total_bytes)
+    );
+
+    println!();
+    println!("{:<10} {:>10} {:>10}", "Size", "Files", "Bytes");
+    for bucket in &stats.histogram {
+        println!(
+            "{:<10} {:>10} {:>10}",
+            bucket.label,
+            Count(bucket.files),
+            ByteSize(bucket.bytes)
+        );
+    }
+
+    println!();
+    println!("{:<10} {:>10} {:>10}", "Extension", "Files", "Bytes");
+    for extension in &stats.extensions {
+        println!(
+            "{:<10} {:>10} {:>10}",
+            extension.extension,
+            Count(extension.files),
+            ByteSize(extension.bytes)
+        );
+    }
+
+    if !stats.largest_files.is_empty() {
+        println!();
+        println!("Largest files");
+        for file in &stats.largest_files {
+            println!("{:>10} {}", ByteSize(file.file_size), file.file_name);
+        }
+    }
+
+    if !stats.most_duplicated.is_empty() {
+        println!();
+        println!("Most duplicated");
+        for duplicated in &stats.most_duplicated {
+            println!(
+                "{:>10} {:>10} {}",
+                Count(duplicated.copies),
+                ByteSize(duplicated.file_size),
+                duplicated.hash
+            );
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use std::collections::HashSet;
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::byte_size::Count;
+use crate::errors::AppError;
+use crate::hash_data::{FileEntry, load_current_hash_data};
+use crate::scan_folders::{ScanOptions, list_files};
+
+/// Compare sizes and modified times in the tree with the hash data, without hashing, for a quick
+/// check on whether a scan has anything to do. Fails when anything has changed.
+pub fn status(starting_dir: &Path, options: &ScanOptions) -> Result<(), AppError> {
+    let hash_data = load_current_hash_data(starting_dir, false)?;
+
+    let (files, unreadable) = list_files(starting_dir, options)?;
+
+    let mut found: HashSet<&str> = HashSet::with_capacity(files.len());
+
+    let mut new = 0;
+    let mut changed = 0;
+
+    for (path, metadata) in &files {
+        let file_name = path.to_string_lossy();
+
+        let entry = hash_data
+            .binary_search_by(|entry| entry.file_name.as_str().cmp(&file_name))
+            .ok()
+            .and_then(|position| hash_data.get(position));
+
+        match entry {
+            Some(entry) => {
+                found.insert(&entry.file_name);
+
+                if !is_unchanged(entry, metadata) {
+                    println!("Changed: {file_name}");
+                    changed += 1;
+                }
+            }
+            None => {
+                println!("New: {file_name}");
+                new += 1;
+            }
+        }
+    }
+
+    for (path, err) in &unreadable {
+        println!("Unreadable: {}: {err}", path.to_string_lossy());
+    }
+
+    // Files that couldn't be read, or are under a directory that couldn't, may well be there
+    let missing: Vec<PathBuf> = hash_data
+        .iter()
+        .filter(|entry| !found.contains(entry.file_name.as_str()))
+        .map(|entry| PathBuf::from(&entry.file_name))
+        .filter(|path| !unreadable.iter().any(|(unreadable, _)| path.starts_with(unreadable)))
+        .collect();
+
+    for path in &missing {
+        println!("Missing: {}", path.to_string_lossy());
+    }
+
+    println!(
+        "{} new, {} changed, {} missing since the last scan, {} unreadable",
+        Count(new),
+        Count(changed),
+        Count(missing.len()),
+        Count(unreadable.len())
+    );
+
+    if new > 0 || changed > 0 || !missing.is_empty() || !unreadable.is_empty() {
+        return Err(AppError::new("The tree differs from the hash data".into()));
+    }
+
+    return Ok(());
+}
+
+fn is_unchanged(entry: &FileEntry, metadata: &Metadata) -> bool {
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok());
+
+    return metadata.len() == entry.file_size
+        && modified.is_some_and(|modified| {
+            modified.as_secs() == entry.modified
+                && entry
+                    .modified_nanos
+                    .is_none_or(|nanos| nanos == modified.subsec_nanos())
+        });
+}
@@ -0,0 +1,85 @@
+use minijinja::value::Serde;
+use minijinja::{Environment, Value, context};
+use serde::Serialize;
+
+use crate::byte_size::SizeFormat;
+use crate::duplicate_report::{Source, SourceLabels};
+use crate::errors::{AppError, AppErrorResult};
+
+#[derive(Serialize)]
+struct TemplateFile {
+    source: String,
+    path: String,
+}
+
+#[derive(Serialize)]
+struct TemplateGroup {
+    size: u64,
+    size_label: String,
+    copies: usize,
+    files: Vec<TemplateFile>,
+}
+
+/// Duplicate groups rendered through a user supplied minijinja template
+pub struct TemplateReport {
+    template: String,
+    size_format: SizeFormat,
+    labels: SourceLabels,
+    groups: Vec<TemplateGroup>,
+}
+
+impl TemplateReport {
+    pub fn new(template: String, size_format: SizeFormat, labels: SourceLabels) -> Self {
+        return TemplateReport {
+            template,
+            size_format,
+            labels,
+            groups: Vec::default(),
+        };
+    }
+
+    pub fn add_group<'a>(
+        &mut self,
+        file_size: u64,
+        files: impl ExactSizeIterator<Item = (Source, &'a str)>,
+    ) {
+        self.groups.push(TemplateGroup {
+            size: file_size,
+            size_label: self.size_format.format(file_size),
+            copies: files.len(),
+            files: files
+                .map(|(source, path)| TemplateFile {
+                    source: self.labels.label(source).to_string(),
+                    path: path.to_string(),
+                })
+                .collect(),
+        });
+    }
+
+    pub fn print(self) -> Result<(), AppError> {
+        let wasted: u64 = self
+            .groups
+            .iter()
+            .map(|group| group.size * (group.copies as u64 - 1))
+            .sum();
+
+        let output = render_template(
+            &self.template,
+            context! {
+                groups => Value::from(Serde(&self.groups)),
+                wasted => wasted,
+                wasted_label => self.size_format.format(wasted),
+            },
+        )?;
+
+        print!("{output}");
+
+        return Ok(());
+    }
+}
+
+pub fn render_template(template: &str, context: Value) -> Result<String, AppError> {
+    let environment = Environment::new();
+
+    return environment.render_str(template, context).app_err();
+}
@@ -0,0 +1,241 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::{Component, Path};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use sha1::{Digest, Sha1};
+
+use crate::byte_size::ByteSize;
+use crate::errors::{AppError, AppErrorResult};
+use crate::hash_data::{FileEntry, load_current_hash_data};
+use crate::utils::{canonical_path, check_exit_key_pressed};
+
+const MIN_PIECE_SIZE: u64 = 256 * 1024;
+const MAX_PIECE_SIZE: u64 = 16 * 1024 * 1024;
+/// Default piece sizes grow until the torrent has no more than this many pieces
+const TARGET_PIECES: u64 = 2000;
+const READ_BUFFER_SIZE: usize = 1024 * 1024;
+
+/// A bencoded value, dictionaries keep their keys sorted as the format requires
+enum Bencode {
+    Integer(i64),
+    Bytes(Vec<u8>),
+    List(Vec<Bencode>),
+    Dictionary(BTreeMap<&'static str, Bencode>),
+}
+
+impl Bencode {
+    fn string(value: &str) -> Bencode {
+        return Bencode::Bytes(value.as_bytes().to_vec());
+    }
+
+    fn encode(&self, output: &mut Vec<u8>) {
+        match self {
+            Bencode::Integer(value) => output.extend(format!("i{value}e").as_bytes()),
+            Bencode::Bytes(value) => {
+                output.extend(format!("{}:", value.len()).as_bytes());
+                output.extend(value);
+            }
+            Bencode::List(values) => {
+                output.push(b'l');
+                for value in values {
+                    value.encode(output);
+                }
+                output.push(b'e');
+            }
+            Bencode::Dictionary(values) => {
+                output.push(b'd');
+                for (key, value) in values {
+                    Bencode::string(key).encode(output);
+                    value.encode(output);
+                }
+                output.push(b'e');
+            }
+        }
+    }
+}
+
+/// Write a `.torrent` file for the files in the base path's hash data, so a scanned folder can
+/// be seeded as it is. Piece hashes span file boundaries so they can't come from the stored
+/// digests, every file is read once, and files changed since the last scan are refused.
+pub fn create_torrent(
+    starting_dir: &Path,
+    output: &Path,
+    trackers: &[String],
+    piece_size: Option<u64>,
+) -> Result<(), AppError> {
+    let hash_data = load_current_hash_data(starting_dir, false)?;
+
+    if hash_data.is_empty() {
+        return Err(AppError::new(format!(
+            "No hash data for {}, scan it first",
+            starting_dir.to_string_lossy()
+        )));
+    }
+
+    let total_size: u64 = hash_data.iter().map(|entry| entry.file_size).sum();
+
+    let piece_size = piece_size.unwrap_or_else(|| default_piece_size(total_size));
+
+    // Clients expect a power of two, which binary units give
+    if !piece_size.is_power_of_two() {
+        return Err(AppError::new(format!(
+            "Piece size {} isn't a power of two, use binary units like 256KiB",
+            ByteSize(piece_size)
+        )));
+    }
+
+    println!(
+        "Hashing {} files, {} in pieces of {}",
+        hash_data.len(),
+        ByteSize(total_size),
+        ByteSize(piece_size)
+    );
+
+    let mut files: Vec<Bencode> = Vec::with_capacity(hash_data.len());
+    let mut pieces: Vec<u8> = Vec::default();
+    let mut piece = Sha1::new();
+    let mut piece_filled = 0;
+    let mut buffer = vec![0u8; READ_BUFFER_SIZE];
+
+    for entry in &hash_data {
+        let path = Path::new(&entry.file_name);
+
+        let mut file = File::open(path).app_err_at("Opening", path)?;
+        check_unchanged(&file, entry, path)?;
+
+        loop {
+            check_exit_key_pressed()?;
+
+            let wanted = buffer.len().min((piece_size - piece_filled) as usize);
+            let read = file
+                .read(&mut buffer[..wanted])
+                .app_err_at("Reading", path)?;
+
+            if read == 0 {
+                break;
+            }
+
+            piece.update(&buffer[..read]);
+            piece_filled += read as u64;
+
+            if piece_filled == piece_size {
+                pieces.extend(piece.finalize_reset());
+                piece_filled = 0;
+            }
+        }
+
+        files.push(Bencode::Dictionary(BTreeMap::from([
+            ("length", Bencode::Integer(entry.file_size as i64)),
+            ("path", Bencode::List(path_components(starting_dir, path)?)),
+        ])));
+    }
+
+    if piece_filled > 0 {
+        pieces.extend(piece.finalize());
+    }
+
+    let name = starting_dir
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "hashfolder".into());
+
+    let info = Bencode::Dictionary(BTreeMap::from([
+        ("files", Bencode::List(files)),
+        ("name", Bencode::string(&name)),
+        ("piece length", Bencode::Integer(piece_size as i64)),
+        ("pieces", Bencode::Bytes(pieces)),
+    ]));
+
+    let mut encoded_info: Vec<u8> = Vec::default();
+    info.encode(&mut encoded_info);
+    let info_hash = hex::encode(Sha1::digest(&encoded_info));
+
+    let mut torrent = BTreeMap::from([
+        ("created by", Bencode::string("hashfolder")),
+        (
+            "creation date",
+            Bencode::Integer(
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .app_err()?
+                    .as_secs() as i64,
+            ),
+        ),
+        ("info", info),
+    ]);
+
+    if let Some(tracker) = trackers.first() {
+        torrent.insert("announce", Bencode::string(tracker));
+        torrent.insert(
+            "announce-list",
+            Bencode::List(
+                trackers
+                    .iter()
+                    .map(|tracker| Bencode::List(vec![Bencode::string(tracker)]))
+                    .collect(),
+            ),
+        );
+    }
+
+    let mut encoded: Vec<u8> = Vec::default();
+    Bencode::Dictionary(torrent).encode(&mut encoded);
+
+    let output = canonical_path(output)?;
+
+    let mut writer = BufWriter::new(File::create(&output).app_err_at("Creating", &output)?);
+    writer.write_all(&encoded).app_err_at("Writing", &output)?;
+    writer.flush().app_err_at("Writing", &output)?;
+
+    println!("Info hash {info_hash}");
+    println!("Wrote {}", output.to_string_lossy());
+
+    return Ok(());
+}
+
+/// Smallest power of two piece size that keeps the piece count near `TARGET_PIECES`
+fn default_piece_size(total_size: u64) -> u64 {
+    return total_size
+        .div_ceil(TARGET_PIECES)
+        .next_power_of_two()
+        .clamp(MIN_PIECE_SIZE, MAX_PIECE_SIZE);
+}
+
+/// The stored size and modified time must still match, as the torrent is meant to describe the
+/// files the scan verified
+fn check_unchanged(file: &File, entry: &FileEntry, path: &Path) -> Result<(), AppError> {
+    let metadata = file.metadata().app_err_at("Reading metadata of", path)?;
+
+    let modified = metadata
+        .modified()
+        .app_err_at("Reading modified time of", path)?
+        .duration_since(UNIX_EPOCH)
+        .app_err_at("Reading modified time of", path)?;
+
+    if metadata.len() != entry.file_size
+        || modified.as_secs() != entry.modified
+        || entry
+            .modified_nanos
+            .is_some_and(|nanos| nanos != modified.subsec_nanos())
+    {
+        return Err(AppError::new(format!(
+            "{} changed since the last scan, scan again before creating a torrent",
+            path.to_string_lossy()
+        )));
+    }
+
+    return Ok(());
+}
+
+fn path_components(starting_dir: &Path, path: &Path) -> Result<Vec<Bencode>, AppError> {
+    let relative = path.strip_prefix(starting_dir).app_err()?;
+
+    return Ok(relative
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(Bencode::string(&part.to_string_lossy())),
+            _ => None,
+        })
+        .collect());
+}
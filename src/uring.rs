@@ -0,0 +1,126 @@
+use std::fs::File;
+use std::io::{Error, ErrorKind};
+use std::mem::forget;
+use std::os::fd::AsRawFd;
+
+use indicatif::ProgressBar;
+use io_uring::{IoUring, opcode, types};
+
+use crate::errors::{AppError, AppErrorResult};
+use crate::hashing::{Digests, Hasher, HasherFactory};
+use crate::or_else;
+use crate::power::wait_while_paused;
+use crate::utils::check_exit_key_pressed;
+
+struct ReadState<'a> {
+    file: &'a File,
+    offset: u64,
+    buffer: Vec<u8>,
+    hasher: Box<dyn Hasher>,
+}
+
+/// Hash several files at once with one read in flight per file
+pub fn hash_files(
+    files: &[(&File, usize)],
+    hasher: &HasherFactory,
+    progress: &ProgressBar,
+) -> Result<Vec<Digests>, AppError> {
+    let queue_size = u32::try_from(files.len().next_power_of_two()).unwrap_or(u32::MAX);
+
+    let mut ring = IoUring::new(queue_size).app_err()?;
+
+    let mut states: Vec<ReadState> = files
+        .iter()
+        .map(|(file, buffer_size)| ReadState {
+            file,
+            offset: 0,
+            buffer: vec![0u8; (*buffer_size).max(1)],
+            hasher: hasher.create(),
+        })
+        .collect();
+
+    for (index, state) in states.iter_mut().enumerate() {
+        queue_read(&mut ring, state, index)?;
+    }
+
+    let mut in_flight = states.len();
+    let mut result: Result<(), AppError> = Ok(());
+
+    while in_flight > 0 {
+        if let Err(err) = ring.submit_and_wait(1) {
+            if err.kind() == ErrorKind::Interrupted {
+                continue;
+            }
+
+            // Reads still in flight write into the buffers, they must outlive the kernel
+            forget(states);
+            return Err(err).app_err();
+        }
+
+        let completions: Vec<(usize, i32)> = ring
+            .completion()
+            .map(|completion| (completion.user_data() as usize, completion.result()))
+            .collect();
+
+        for (index, read) in completions {
+            in_flight -= 1;
+
+            if result.is_err() {
+                continue;
+            }
+
+            let state = or_else!(states.get_mut(index), none => continue);
+
+            if read < 0 {
+                result = Err(Error::from_raw_os_error(-read)).app_err();
+                continue;
+            }
+
+            if read == 0 {
+                continue;
+            }
+
+            let read = read as usize;
+            state.hasher.update(&state.buffer[..read]);
+            state.offset += read as u64;
+            progress.inc(read as u64);
+
+            if let Err(err) = check_exit_key_pressed().and_then(|_| wait_while_paused()) {
+                result = Err(err);
+                continue;
+            }
+
+            if let Err(err) = queue_read(&mut ring, state, index) {
+                result = Err(err);
+                continue;
+            }
+
+            in_flight += 1;
+        }
+    }
+
+    result?;
+
+    return Ok(states
+        .into_iter()
+        .map(|state| state.hasher.finalize())
+        .collect());
+}
+
+fn queue_read(ring: &mut IoUring, state: &mut ReadState, index: usize) -> Result<(), AppError> {
+    let buffer_len = u32::try_from(state.buffer.len()).unwrap_or(u32::MAX);
+
+    let read = opcode::Read::new(
+        types::Fd(state.file.as_raw_fd()),
+        state.buffer.as_mut_ptr(),
+        buffer_len,
+    )
+    .offset(state.offset)
+    .build()
+    .user_data(index as u64);
+
+    // SAFETY: the buffer lives in `states`, which is kept alive until every queued read completes
+    unsafe { ring.submission().push(&read) }.app_err()?;
+
+    return Ok(());
+}
@@ -1,24 +1,468 @@
-use std::time::Duration;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::read;
+use std::hash::{BuildHasher, Hasher, RandomState};
+use std::io::{IsTerminal, Read, stdin};
+use std::path::{Path, PathBuf, absolute};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use crossterm::event::{self, Event, KeyCode, KeyEvent};
+use clap::ValueEnum;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::terminal;
 
 use crate::errors::{AppError, AppErrorResult};
+use crate::or_else;
 
-pub fn check_exit_key_pressed() -> Result<(), AppError> {
-    loop {
-        if event::poll(Duration::ZERO).app_err()? {
-            if let Event::Key(KeyEvent {
-                code,
-                modifiers: _,
-                kind: _,
-                state: _,
-            }) = event::read().app_err()?
-                && let KeyCode::Char('q') = code
-            {
-                Err(AppError::new("Abort key pressed".into()))?;
+const ABORT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A plain abort key has to be pressed again within this long to stop the scan
+const ABORT_CONFIRM_WINDOW: Duration = Duration::from_secs(2);
+
+static ABORT_REQUESTED: AtomicBool = AtomicBool::new(false);
+static ABORT_PENDING: AtomicBool = AtomicBool::new(false);
+
+/// Key that stops a scan, either a plain key pressed twice or a Ctrl combination pressed once
+#[derive(Clone, Copy, Debug)]
+pub struct AbortKey {
+    pub key: char,
+    pub ctrl: bool,
+}
+
+impl Default for AbortKey {
+    fn default() -> Self {
+        return AbortKey {
+            key: 'q',
+            ctrl: false,
+        };
+    }
+}
+
+impl fmt::Display for AbortKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.ctrl {
+            write!(f, "Ctrl+")?;
+        }
+
+        return write!(f, "{}", self.key.to_ascii_uppercase());
+    }
+}
+
+impl AbortKey {
+    /// How to stop the scan, shown before it starts
+    pub fn instructions(&self) -> String {
+        if self.ctrl {
+            return format!("Press {self} to stop and save progress");
+        }
+
+        return format!("Press {self} twice to stop and save progress");
+    }
+
+    fn matches(&self, key_event: &KeyEvent) -> bool {
+        let KeyCode::Char(key) = key_event.code else {
+            return false;
+        };
+
+        return key_event.kind == KeyEventKind::Press
+            && key.eq_ignore_ascii_case(&self.key)
+            && key_event.modifiers.contains(KeyModifiers::CONTROL) == self.ctrl;
+    }
+}
+
+/// Parse an abort key such as `q`, `x` or `ctrl+q`
+pub fn parse_abort_key(value: &str) -> Result<AbortKey, String> {
+    let (ctrl, key) = match value.split_once('+') {
+        Some((modifier, key)) if modifier.eq_ignore_ascii_case("ctrl") => (true, key),
+        Some(_) => return Err(format!("{value} should be a key such as q, or ctrl+q")),
+        None => (false, value),
+    };
+
+    let mut chars = key.chars();
+
+    let key = match (chars.next(), chars.next()) {
+        (Some(key), None) if key.is_ascii_alphanumeric() => key.to_ascii_lowercase(),
+        _ => {
+            return Err(format!(
+                "{value} should be a letter or digit, optionally as ctrl+key"
+            ));
+        }
+    };
+
+    return Ok(AbortKey { key, ctrl });
+}
+
+pub struct AbortListener {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Drop for AbortListener {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+
+        if let Some(handle) = self.handle.take() {
+            _ = handle.join();
+        }
+    }
+}
+
+/// Watch for the abort key on a background thread until the listener is dropped
+pub fn start_abort_listener(abort_key: AbortKey) -> AbortListener {
+    ABORT_REQUESTED.store(false, Ordering::Relaxed);
+    ABORT_PENDING.store(false, Ordering::Relaxed);
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let thread_stop = stop.clone();
+
+    let handle = thread::spawn(move || {
+        // A single stray press, such as while focus briefly moves to this window, only arms the
+        // abort until the confirmation window runs out
+        let mut first_press: Option<Instant> = None;
+
+        while !thread_stop.load(Ordering::Relaxed) {
+            if first_press.is_some_and(|pressed| pressed.elapsed() > ABORT_CONFIRM_WINDOW) {
+                first_press = None;
+                ABORT_PENDING.store(false, Ordering::Relaxed);
             }
+
+            match event::poll(ABORT_POLL_INTERVAL) {
+                Ok(true) => {
+                    let key_event = match event::read() {
+                        Ok(Event::Key(key_event)) => key_event,
+                        Ok(_) => continue,
+                        Err(_) => return,
+                    };
+
+                    if !abort_key.matches(&key_event) {
+                        continue;
+                    }
+
+                    if abort_key.ctrl || first_press.is_some() {
+                        ABORT_PENDING.store(false, Ordering::Relaxed);
+                        ABORT_REQUESTED.store(true, Ordering::Relaxed);
+                    } else {
+                        first_press = Some(Instant::now());
+                        ABORT_PENDING.store(true, Ordering::Relaxed);
+                    }
+                }
+                Ok(false) => {}
+                Err(_) => return,
+            }
+        }
+    });
+
+    return AbortListener {
+        stop,
+        handle: Some(handle),
+    };
+}
+
+/// Run a scan or verify watching for the abort key when there's a terminal to press it in.
+/// Without one, such as under cron or in a container, it runs to completion unattended.
+pub fn listening_for_abort<T>(abort_key: AbortKey, run: impl FnOnce() -> T) -> T {
+    let interactive = stdin().is_terminal();
+
+    if interactive {
+        println!("{}", abort_key.instructions());
+    }
+
+    let abort_listener = interactive.then(|| start_abort_listener(abort_key));
+
+    let result = run();
+
+    drop(abort_listener);
+    _ = terminal::disable_raw_mode();
+    println!();
+
+    return result;
+}
+
+pub fn check_exit_key_pressed() -> Result<(), AppError> {
+    if abort_requested() {
+        Err(AppError::new("Stopped by the abort key".into()))?;
+    }
+
+    return Ok(());
+}
+
+pub fn abort_requested() -> bool {
+    return ABORT_REQUESTED.load(Ordering::Relaxed);
+}
+
+/// The abort key has been pressed once and is waiting to be pressed again
+pub fn abort_pending() -> bool {
+    return ABORT_PENDING.load(Ordering::Relaxed);
+}
+
+/// Parse an interval such as `90s`, `30m`, `24h`, `7d`, `2w` or `1y`
+pub fn parse_interval(value: &str) -> Result<Duration, String> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+
+    let number: u64 = or_else!(number.parse(), _ => {
+        return Err(format!("{value} should be a number followed by s, m, h, d, w or y"));
+    });
+
+    let seconds = match unit.trim() {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        "y" => 365 * 24 * 60 * 60,
+        _ => {
+            return Err(format!(
+                "Unknown interval unit {unit}, expected s, m, h, d, w or y"
+            ));
+        }
+    };
+
+    return Ok(Duration::from_secs(number.saturating_mul(seconds)));
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+
+    return match seconds {
+        0..60 => format!("{seconds}s"),
+        60..3600 => format!("{}m {}s", seconds / 60, seconds % 60),
+        _ => format!("{}h {}m", seconds / 3600, seconds % 3600 / 60),
+    };
+}
+
+/// Format seconds since the Unix epoch as a UTC date and time
+pub fn format_timestamp(timestamp: u64) -> String {
+    let days = (timestamp / 86_400) as i64;
+    let seconds = timestamp % 86_400;
+
+    // Civil date from days since 1970-01-01, counting eras of 400 years from March 0000
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    return format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    );
+}
+
+/// A random duration below `limit`
+pub fn random_duration(limit: Duration) -> Duration {
+    return limit.mul_f64(random_fraction());
+}
+
+/// A random number from 0 to 1, good enough for jitter and sampling but not for secrets
+pub fn random_fraction() -> f64 {
+    let random = RandomState::new().build_hasher().finish();
+
+    return random as f64 / u64::MAX as f64;
+}
+
+/// Parse a percentage such as `5%` or `0.5`, above 0 and up to 100
+pub fn parse_percentage(value: &str) -> Result<f64, String> {
+    let percentage: f64 = or_else!(value.trim().trim_end_matches('%').parse(), _ => {
+        return Err(format!("{value} should be a percentage such as 5%"));
+    });
+
+    if !(percentage > 0.0 && percentage <= 100.0) {
+        return Err(format!("{value} should be above 0% and at most 100%"));
+    }
+
+    return Ok(percentage);
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+/// Seconds since the Unix epoch
+pub fn unix_now() -> u64 {
+    return SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+}
+
+/// The one form paths are stored and compared in: absolute, with symlinks resolved and verbatim
+/// on Windows, so a root given relatively or through a symlink matches the paths stored by a
+/// scan. Paths that don't exist are resolved as far as they do.
+pub fn canonical_path(path: &Path) -> Result<PathBuf, AppError> {
+    let absolute_path = absolute(path).app_err_at("Resolving", path)?;
+
+    for ancestor in absolute_path.ancestors() {
+        let canonical = or_else!(ancestor.canonicalize(), _ => continue);
+
+        let remainder = or_else!(absolute_path.strip_prefix(ancestor), _ => continue);
+
+        let resolved = if remainder.as_os_str().is_empty() {
+            canonical
         } else {
-            return Ok(());
+            canonical.join(remainder)
+        };
+
+        return Ok(verbatim_path(resolved));
+    }
+
+    return Ok(verbatim_path(absolute_path));
+}
+
+/// Paths listed in a file, or on stdin for `-`, separated by NUL characters as from
+/// `find -print0` or otherwise one per line
+pub fn read_path_list(source: &Path) -> Result<Vec<PathBuf>, AppError> {
+    let bytes = if source == Path::new("-") {
+        let mut bytes = Vec::default();
+        stdin().read_to_end(&mut bytes).app_err()?;
+        bytes
+    } else {
+        read(source).app_err_at("Reading", source)?
+    };
+
+    let separator = if bytes.contains(&0) { b'\0' } else { b'\n' };
+
+    return Ok(bytes
+        .split(|byte| *byte == separator)
+        .map(|path| path.strip_suffix(b"\r").unwrap_or(path))
+        .filter(|path| !path.is_empty())
+        .map(|path| PathBuf::from(path_from_bytes(path)))
+        .collect());
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> OsString {
+    use std::os::unix::ffi::OsStrExt;
+
+    return std::ffi::OsStr::from_bytes(bytes).to_owned();
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> OsString {
+    return String::from_utf8_lossy(bytes).into_owned().into();
+}
+
+/// On Windows, paths beyond MAX_PATH and file names like CON or NUL only work in the verbatim
+/// `\\?\` form, so the scan root is converted and every path joined onto it inherits it.
+/// Alternate data streams aren't listed by `read_dir`, so they never reach the scan.
+#[cfg(windows)]
+pub fn verbatim_path(path: PathBuf) -> PathBuf {
+    use std::ffi::OsString;
+    use std::path::{Component, Prefix};
+
+    let is_unc = match path.components().next() {
+        Some(Component::Prefix(prefix)) => match prefix.kind() {
+            Prefix::Disk(_) => false,
+            Prefix::UNC(..) => true,
+            _ => return path,
+        },
+        _ => return path,
+    };
+
+    let mut verbatim = OsString::from(r"\\?\");
+
+    if is_unc {
+        // \\server\share becomes \\?\UNC\server\share
+        verbatim.push("UNC");
+        verbatim.push(&path.as_os_str().to_string_lossy()[1..]);
+    } else {
+        verbatim.push(path.as_os_str());
+    }
+
+    return PathBuf::from(verbatim);
+}
+
+#[cfg(not(windows))]
+pub fn verbatim_path(path: PathBuf) -> PathBuf {
+    return path;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::temp_dir;
+    use std::fs::{create_dir_all, remove_dir_all};
+    use std::process;
+
+    use super::*;
+
+    /// A directory for one test, removed again when it ends
+    struct TestDir(PathBuf);
+
+    impl TestDir {
+        fn new(parent: &Path, name: &str) -> TestDir {
+            let path = parent.join(format!("hashfolder-test-{}-{name}", process::id()));
+
+            create_dir_all(&path).unwrap();
+
+            return TestDir(path);
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            _ = remove_dir_all(&self.0);
         }
     }
+
+    #[test]
+    fn canonical_path_resolves_relative_root() {
+        // Tests run from the package root, so this path is relative to it
+        let test_dir = TestDir::new(Path::new("target"), "relative");
+        assert!(test_dir.0.is_relative());
+
+        let canonical = canonical_path(&test_dir.0).unwrap();
+
+        assert!(canonical.is_absolute());
+        assert_eq!(canonical, test_dir.0.canonicalize().unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn canonical_path_resolves_symlinked_root() {
+        let test_dir = TestDir::new(&temp_dir(), "symlinked");
+        let target = test_dir.0.join("target");
+        let link = test_dir.0.join("link");
+
+        create_dir_all(target.join("photos")).unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        let target = target.canonicalize().unwrap();
+
+        assert_eq!(canonical_path(&link).unwrap(), target);
+        assert_eq!(
+            canonical_path(&link.join("photos")).unwrap(),
+            target.join("photos")
+        );
+        assert_eq!(
+            canonical_path(&link.join("missing")).unwrap(),
+            target.join("missing")
+        );
+    }
+
+    #[test]
+    fn canonical_path_keeps_missing_remainder() {
+        let test_dir = TestDir::new(&temp_dir(), "missing");
+        let missing = Path::new("not").join("there.txt");
+
+        assert_eq!(
+            canonical_path(&test_dir.0.join(&missing)).unwrap(),
+            test_dir.0.canonicalize().unwrap().join(&missing)
+        );
+    }
 }
@@ -0,0 +1,478 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{IsTerminal, stdin};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use crossterm::terminal;
+
+use crate::byte_size::{ByteSize, Count};
+use crate::errors::{AppError, AppErrorResult};
+use crate::hash_data::{
+    FileEntry, load_current_hash_data, load_hash_data_with_info, save_hash_data, try_lock_scan,
+};
+use crate::hashing::HasherFactory;
+use crate::or_else;
+use crate::parity::ParityRecord;
+use crate::pointer::{PointerKind, read_annex_link, read_lfs_pointer};
+use crate::progress::ScanProgress;
+use crate::scan_folders::{hash_file, max_read_buffer_size, read_buffer_size};
+use crate::utils::{
+    AbortKey, abort_requested, check_exit_key_pressed, listening_for_abort, random_fraction,
+    unix_now,
+};
+
+/// How much of the data `verify` reads when sampling
+#[derive(Clone, Copy, Debug)]
+pub enum SampleSize {
+    Percentage(f64),
+    Bytes(u64),
+}
+
+impl SampleSize {
+    fn bytes(self, total_size: u64) -> u64 {
+        return match self {
+            SampleSize::Percentage(percentage) => (total_size as f64 * percentage / 100.0) as u64,
+            SampleSize::Bytes(bytes) => bytes,
+        };
+    }
+}
+
+/// Which files `verify` reads
+#[derive(Clone, Copy, Debug)]
+pub enum VerifySelection {
+    All,
+    /// A random sample, larger files more likely picked
+    Sample(SampleSize),
+    /// The files verified longest ago, until the time or amount of data runs out
+    OldestFirst {
+        time: Option<Duration>,
+        bytes: Option<u64>,
+    },
+}
+
+enum Verified {
+    Matches,
+    Missing,
+    /// Size or modified time changed, so a different hash is expected
+    Modified,
+    /// Same size and modified time but different content, the sign of bit rot
+    Corrupt,
+    /// Couldn't be opened or read, for the reason given
+    Unreadable(String),
+}
+
+/// Read every file in the hash data again, or those selected, and compare it with the stored
+/// hash, recording when each matching file was verified, then check the recorded PAR2 recovery
+/// files are still there
+pub fn verify(
+    starting_dir: &Path,
+    selection: VerifySelection,
+    abort_key: AbortKey,
+    refresh_rate: u8,
+) -> Result<(), AppError> {
+    // Held until the verified times are saved, so a scan can't save in between and lose its
+    // changes when they are
+    let _scan_lock = or_else!(try_lock_scan(starting_dir)?, none => {
+        return Err(AppError::new(format!(
+            "A scan of {} is running, verify once it's done",
+            starting_dir.to_string_lossy()
+        )));
+    });
+
+    let hash_data = load_current_hash_data(starting_dir, false)?;
+
+    let hasher = HasherFactory::default();
+    let max_buffer_size = max_read_buffer_size(starting_dir);
+
+    let total_size: u64 = hash_data.iter().map(|entry| entry.file_size).sum();
+
+    let selected: Vec<usize> = match selection {
+        VerifySelection::All => (0..hash_data.len()).collect(),
+        VerifySelection::Sample(sample) => sample_by_size(&hash_data, sample.bytes(total_size)),
+        VerifySelection::OldestFirst { bytes, .. } => {
+            least_recently_verified(&hash_data, bytes.unwrap_or(u64::MAX))
+        }
+    };
+
+    let selected_size: u64 = selected
+        .iter()
+        .map(|&index| hash_data[index].file_size)
+        .sum();
+
+    match selection {
+        VerifySelection::All => println!(
+            "Verifying {} files, {}",
+            Count(hash_data.len()),
+            ByteSize(total_size)
+        ),
+        VerifySelection::Sample(_) => println!(
+            "Verifying {} of {} files, {} of {}, picked at random by size",
+            Count(selected.len()),
+            Count(hash_data.len()),
+            ByteSize(selected_size),
+            ByteSize(total_size)
+        ),
+        VerifySelection::OldestFirst { .. } => println!(
+            "Verifying up to {} of {} files, {} of {}, least recently verified first",
+            Count(selected.len()),
+            Count(hash_data.len()),
+            ByteSize(selected_size),
+            ByteSize(total_size)
+        ),
+    }
+
+    let deadline = match selection {
+        VerifySelection::OldestFirst {
+            time: Some(time), ..
+        } => Some(Instant::now() + time),
+        _ => None,
+    };
+
+    let mut verified = 0;
+    let mut missing = 0;
+    let mut modified = 0;
+    let mut corrupt = 0;
+    let mut unreadable = 0;
+    let mut verified_at = HashMap::new();
+
+    let stopped = with_progress(abort_key, refresh_rate, selected_size, |progress| {
+        for &index in &selected {
+            check_exit_key_pressed()?;
+
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                progress.println(format!(
+                    "Time budget used up, {} files left for a later run",
+                    Count(selected.len() - verified)
+                ));
+                break;
+            }
+
+            let entry = &hash_data[index];
+
+            let outcome = verify_entry(entry, &hasher, max_buffer_size, progress)?;
+
+            verified += 1;
+            progress.set_file_counts(verified, selected.len());
+
+            match outcome {
+                Verified::Matches => {
+                    verified_at.insert(entry.file_name.as_str(), (entry.hash.as_str(), unix_now()));
+                }
+                Verified::Missing => {
+                    progress.println(format!("Missing: {}", entry.file_name));
+                    missing += 1;
+                }
+                Verified::Modified => {
+                    progress.println(format!("Modified: {}", entry.file_name));
+                    modified += 1;
+                }
+                Verified::Corrupt => {
+                    progress.println(format!("Corrupt: {}", entry.file_name));
+                    corrupt += 1;
+                }
+                Verified::Unreadable(reason) => {
+                    progress.println(format!("Unreadable: {}: {reason}", entry.file_name));
+                    unreadable += 1;
+                }
+            }
+        }
+
+        return Ok(());
+    })
+    .err();
+
+    println!(
+        "{} files match, {} missing, {} modified, {} corrupt, {} unreadable",
+        Count(verified - missing - modified - corrupt - unreadable),
+        Count(missing),
+        Count(modified),
+        Count(corrupt),
+        Count(unreadable)
+    );
+
+    // What was verified before the abort key stopped the run is kept too
+    let saved = save_verified_times(starting_dir, &verified_at);
+
+    if let Some(err) = stopped {
+        return Err(err);
+    }
+
+    saved?;
+
+    if let VerifySelection::Sample(_) = selection {
+        print_sample_confidence(verified - missing - modified - unreadable, corrupt);
+    }
+
+    let parity = ParityRecord::load(starting_dir)?;
+
+    let missing_parity = parity.missing_files();
+
+    for file in &missing_parity {
+        println!("Missing recovery file: {file}");
+    }
+
+    if !parity.is_empty() && missing_parity.is_empty() {
+        println!("All recovery files present");
+    }
+
+    if corrupt > 0 || missing > 0 || unreadable > 0 || !missing_parity.is_empty() {
+        return Err(AppError::new("Verification failed".into()));
+    }
+
+    return Ok(());
+}
+
+/// Stamp when files were verified onto the hash data as it is now rather than as it was loaded,
+/// keeping anything else saved meanwhile, skipping files whose hash has since changed
+fn save_verified_times(
+    starting_dir: &Path,
+    verified_at: &HashMap<&str, (&str, u64)>,
+) -> Result<(), AppError> {
+    if verified_at.is_empty() {
+        return Ok(());
+    }
+
+    let (mut hash_data, info) = load_hash_data_with_info(starting_dir, false)?;
+
+    for entry in &mut hash_data {
+        if let Some(&(hash, time)) = verified_at.get(entry.file_name.as_str())
+            && hash == entry.hash
+        {
+            entry.last_verified = Some(time);
+        }
+    }
+
+    return save_hash_data(starting_dir, &info, &hash_data);
+}
+
+/// Hash the given files and compare them with their entries in the hash data, without updating
+/// it
+pub fn verify_files(
+    starting_dir: &Path,
+    files: &[PathBuf],
+    abort_key: AbortKey,
+    refresh_rate: u8,
+) -> Result<(), AppError> {
+    let hash_data = load_current_hash_data(starting_dir, false)?;
+
+    let entries: HashMap<&str, &FileEntry> = hash_data
+        .iter()
+        .map(|entry| (entry.file_name.as_str(), entry))
+        .collect();
+
+    let hasher = HasherFactory::default();
+    let max_buffer_size = max_read_buffer_size(starting_dir);
+
+    let total_size: u64 = files
+        .iter()
+        .filter_map(|file| entries.get(file.to_string_lossy().as_ref()))
+        .map(|entry| entry.file_size)
+        .sum();
+
+    let mut unknown = 0;
+    let mut differing = 0;
+    let mut unreadable = 0;
+
+    with_progress(abort_key, refresh_rate, total_size, |progress| {
+        for (checked, file) in files.iter().enumerate() {
+            check_exit_key_pressed()?;
+            progress.set_file_counts(checked, files.len());
+
+            let file_name = file.to_string_lossy();
+
+            let entry = or_else!(entries.get(file_name.as_ref()), none => {
+                progress.println(format!("Not in hash data: {file_name}"));
+                unknown += 1;
+                continue;
+            });
+
+            let label = match verify_entry(entry, &hasher, max_buffer_size, progress)? {
+                Verified::Matches => continue,
+                Verified::Missing => "Missing",
+                Verified::Modified => "Modified",
+                Verified::Corrupt => "Corrupt",
+                Verified::Unreadable(reason) => {
+                    progress.println(format!("Unreadable: {file_name}: {reason}"));
+                    unreadable += 1;
+                    continue;
+                }
+            };
+
+            progress.println(format!("{label}: {file_name}"));
+            differing += 1;
+        }
+
+        return Ok(());
+    })?;
+
+    println!(
+        "{} files match, {} differ, {} unreadable, {} not in hash data",
+        Count(files.len() - differing - unreadable - unknown),
+        Count(differing),
+        Count(unreadable),
+        Count(unknown)
+    );
+
+    if differing > 0 || unreadable > 0 || unknown > 0 {
+        return Err(AppError::new("Some files don't match the hash data".into()));
+    }
+
+    return Ok(());
+}
+
+/// Read files with a status line showing progress and the abort key watched for, as a scan
+/// does, the status line and progress display left out without a terminal
+fn with_progress<T>(
+    abort_key: AbortKey,
+    refresh_rate: u8,
+    total_size: u64,
+    verify: impl FnOnce(&ScanProgress) -> Result<T, AppError>,
+) -> Result<T, AppError> {
+    return listening_for_abort(abort_key, || {
+        if stdin().is_terminal() {
+            terminal::enable_raw_mode().app_err()?;
+        }
+
+        let progress = ScanProgress::new(refresh_rate, true, abort_key);
+        progress.overall().set_length(total_size);
+
+        let result = verify(&progress);
+
+        progress.finish();
+
+        return result;
+    });
+}
+
+/// Pick files at random with larger files more likely, as each byte is equally likely to rot,
+/// until they add up to `budget`
+fn sample_by_size(hash_data: &[FileEntry], budget: u64) -> Vec<usize> {
+    // Weighted sampling without replacement: the files with the highest ln(u) / size are a
+    // sample weighted by size, and empty files have nothing to rot
+    let mut keyed: Vec<(f64, usize)> = hash_data
+        .iter()
+        .enumerate()
+        .filter(|(_, entry)| entry.file_size > 0)
+        .map(|(index, entry)| (random_fraction().ln() / entry.file_size as f64, index))
+        .collect();
+
+    keyed.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    return within_budget(hash_data, keyed.into_iter().map(|(_, index)| index), budget);
+}
+
+/// Files never verified first, then those verified longest ago, until they add up to `budget`
+fn least_recently_verified(hash_data: &[FileEntry], budget: u64) -> Vec<usize> {
+    let mut indices: Vec<usize> = (0..hash_data.len()).collect();
+
+    indices.sort_by_key(|&index| hash_data[index].last_verified);
+
+    return within_budget(hash_data, indices.into_iter(), budget);
+}
+
+/// Take files in order until their sizes reach `budget`, including the one that crosses it
+fn within_budget(
+    hash_data: &[FileEntry],
+    indices: impl Iterator<Item = usize>,
+    budget: u64,
+) -> Vec<usize> {
+    let mut taken_size = 0u64;
+
+    return indices
+        .take_while(|&index| {
+            let under_budget = taken_size < budget;
+            taken_size = taken_size.saturating_add(hash_data[index].file_size);
+            under_budget
+        })
+        .collect();
+}
+
+/// How much of the data could be corrupt given what the sample found, by the rule of three when
+/// nothing was
+fn print_sample_confidence(checked: usize, corrupt: usize) {
+    if checked == 0 {
+        println!("No unmodified files were sampled, nothing can be said about the rest");
+    } else if corrupt == 0 {
+        println!(
+            "With 95% confidence less than {:.2}% of the data is in corrupt files",
+            (300.0 / checked as f64).min(100.0)
+        );
+    } else {
+        println!(
+            "About {:.2}% of the data is in corrupt files, run a full verify to find them",
+            100.0 * corrupt as f64 / checked as f64
+        );
+    }
+}
+
+/// Check one file against its entry, failing only when the abort key stops it, so one
+/// unreadable file doesn't end the run
+fn verify_entry(
+    entry: &FileEntry,
+    hasher: &HasherFactory,
+    max_buffer_size: usize,
+    progress: &ScanProgress,
+) -> Result<Verified, AppError> {
+    let path = Path::new(&entry.file_name);
+
+    progress.show_file(progress.overall(), entry.file_name.clone());
+
+    // The file a pointer refers to isn't here to read, only the pointer can be checked
+    if let Some(pointer) = &entry.pointer {
+        if path.symlink_metadata().is_err() {
+            return Ok(Verified::Missing);
+        }
+
+        let current = match pointer.kind {
+            PointerKind::Lfs => File::open(path).ok().and_then(read_lfs_pointer),
+            PointerKind::Annex => read_annex_link(path),
+        };
+
+        return Ok(match current {
+            Some((hash, _)) if hash == entry.hash => Verified::Matches,
+            _ => Verified::Modified,
+        });
+    }
+
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(_) if !path.exists() => return Ok(Verified::Missing),
+        Err(err) => return Ok(Verified::Unreadable(err.to_string())),
+    };
+
+    let metadata = match file.metadata() {
+        Ok(metadata) => metadata,
+        Err(err) => return Ok(Verified::Unreadable(err.to_string())),
+    };
+
+    let unchanged = metadata.len() == entry.file_size
+        && metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .is_some_and(|modified| modified.as_secs() == entry.modified);
+
+    if !unchanged {
+        return Ok(Verified::Modified);
+    }
+
+    let hash = match hash_file(
+        file,
+        hasher.create(),
+        read_buffer_size(entry.file_size, max_buffer_size),
+        0,
+        progress.overall(),
+    ) {
+        Ok(digests) => digests.hash,
+        Err(err) if abort_requested() => return Err(err),
+        Err(AppError::Caught(caught)) => return Ok(Verified::Unreadable(caught.error.to_string())),
+        Err(err) => return Ok(Verified::Unreadable(err.to_string())),
+    };
+
+    if hash != entry.hash {
+        return Ok(Verified::Corrupt);
+    }
+
+    return Ok(Verified::Matches);
+}
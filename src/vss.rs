@@ -0,0 +1,100 @@
+use std::path::{Component, Path, PathBuf, Prefix};
+use std::process::Command;
+
+use crate::errors::{AppError, AppErrorResult};
+use crate::or_else;
+
+/// A Volume Shadow Copy of the volume holding the scanned folder, deleted again when dropped
+pub struct ShadowCopy {
+    id: String,
+    device: PathBuf,
+}
+
+impl ShadowCopy {
+    /// Snapshot the volume `path` is on so locked files can be read as they were at this moment.
+    /// Needs an elevated prompt.
+    pub fn create(path: &Path) -> Result<ShadowCopy, AppError> {
+        let volume = or_else!(volume_root(path), none => {
+            return Err(AppError::new(format!(
+                "{} is not on a local drive that can be shadow copied",
+                path.to_string_lossy()
+            )));
+        });
+
+        let script = format!(
+            "$result = Invoke-CimMethod -ClassName Win32_ShadowCopy -MethodName Create \
+             -Arguments @{{Volume='{volume}'; Context='ClientAccessible'}}; \
+             if ($result.ReturnValue -ne 0) {{ exit $result.ReturnValue }}; \
+             $copy = Get-CimInstance Win32_ShadowCopy -Filter \"ID='$($result.ShadowID)'\"; \
+             $copy.ID; $copy.DeviceObject"
+        );
+
+        let output = run_powershell(&script)?;
+
+        if !output.status.success() {
+            return Err(AppError::new(format!(
+                "Creating a shadow copy of {volume} failed ({}), it needs an elevated prompt",
+                output.status
+            )));
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let mut lines = stdout
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty());
+
+        let (id, device) = or_else!(lines.next().zip(lines.next()), none => {
+            return Err(AppError::new(format!(
+                "Could not read the shadow copy created for {volume}"
+            )));
+        });
+
+        return Ok(ShadowCopy {
+            id: id.into(),
+            device: PathBuf::from(format!(r"{device}\")),
+        });
+    }
+
+    /// The same folder as `path`, read from inside the snapshot
+    pub fn snapshot_path(&self, path: &Path) -> PathBuf {
+        let relative: PathBuf = path
+            .components()
+            .filter(|component| matches!(component, Component::Normal(_)))
+            .collect();
+
+        return self.device.join(relative);
+    }
+}
+
+impl Drop for ShadowCopy {
+    fn drop(&mut self) {
+        let script = format!(
+            "Get-CimInstance Win32_ShadowCopy -Filter \"ID='{}'\" | Remove-CimInstance",
+            self.id
+        );
+
+        if let Err(err) = run_powershell(&script) {
+            println!("Could not delete shadow copy {}: {err}", self.id);
+        }
+    }
+}
+
+fn volume_root(path: &Path) -> Option<String> {
+    let letter = match path.components().next()? {
+        Component::Prefix(prefix) => match prefix.kind() {
+            Prefix::Disk(letter) | Prefix::VerbatimDisk(letter) => letter,
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    return Some(format!(r"{}:\", letter as char));
+}
+
+fn run_powershell(script: &str) -> Result<std::process::Output, AppError> {
+    return Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command", script])
+        .output()
+        .app_err();
+}